@@ -35,11 +35,10 @@ class ShapeKeyExporter(bpy.types.Operator):
             mesh_object: bpy.types.Mesh = selected_object.data
             shape_key_blocks = mesh_object.shape_keys.key_blocks
 
-            toml_string += "[[mesh_groups]]\n"
-            toml_string += "mesh = \"Ungrouped\"\n"
-            toml_string += f"mesh = \"{selected_object.name}\"\n"
+            toml_string += "[[meshes]]\n"
+            toml_string += f"name = {json.dumps(selected_object.name, ensure_ascii=False)}\n"
 
-            toml_string += "options = [\n"
+            toml_string += "shapes = [\n"
             basis_skipped = False
             for name, shape_key in shape_key_blocks.items():
                 if not basis_skipped:
@@ -51,7 +50,7 @@ class ShapeKeyExporter(bpy.types.Operator):
                         f"{name} moves no vertex, skipping..."
                     )
                     continue
-                toml_string += f"  {{ label = \"{name}\", shapes = [\"{name}\"] }},\n"
+                toml_string += f"  {json.dumps(name, ensure_ascii=False)},\n"
 
             toml_string += "]\n"
             toml_string += "\n"
@@ -0,0 +1,181 @@
+use crate::{
+    animator::{layers::expression_parameters, ParameterKind},
+    descriptor::{Descriptor, Diagnostics, ValidationError, ValidationWarning},
+};
+
+use std::io::{prelude::*, Result as IoResult};
+
+/// Synced Expression Parameters can use up to this number of bits in VRChat.
+pub const SYNC_BITS_LIMIT: usize = 256;
+
+/// Usage over this percentage of the budget is warned.
+const NEAR_BUDGET_PERCENT: usize = 90;
+
+/// Network sync usage of the Expression Parameters.
+#[derive(Debug, Clone)]
+pub struct SyncUsage {
+    /// Parameters in the order of definition.
+    pub entries: Vec<SyncEntry>,
+
+    /// Configured budget capped by `SYNC_BITS_LIMIT`.
+    pub budget: usize,
+}
+
+/// An Expression Parameter and its sync cost.
+#[derive(Debug, Clone)]
+pub struct SyncEntry {
+    pub layer: String,
+    pub parameter: String,
+    pub kind: ParameterKind,
+    pub synced: bool,
+}
+
+impl SyncEntry {
+    /// Bits used for the sync. Local-only parameters use nothing.
+    pub fn bits(&self) -> usize {
+        if self.synced {
+            self.kind.sync_bits()
+        } else {
+            0
+        }
+    }
+}
+
+impl SyncUsage {
+    pub fn new(descriptor: &Descriptor) -> SyncUsage {
+        let entries = expression_parameters(descriptor)
+            .into_iter()
+            .map(|p| SyncEntry {
+                layer: p.layer,
+                parameter: p.name,
+                kind: p.kind,
+                synced: p.settings.synced,
+            })
+            .collect();
+        let budget = descriptor
+            .sync_budget
+            .map_or(SYNC_BITS_LIMIT, |b| b.min(SYNC_BITS_LIMIT));
+        SyncUsage { entries, budget }
+    }
+
+    /// Total bits of synced parameters.
+    pub fn total(&self) -> usize {
+        self.entries.iter().map(|e| e.bits()).sum()
+    }
+
+    /// Whether the synced parameters fit in the budget.
+    pub fn is_within_budget(&self) -> bool {
+        self.total() <= self.budget
+    }
+
+    /// Checks the synced parameters against the budget, which is capped by the VRChat limit.
+    pub fn validate(&self, descriptor: &Descriptor, diagnostics: &mut Diagnostics) {
+        let root = descriptor.sources.root.as_deref();
+        if let Some(budget) = descriptor.sync_budget.filter(|&b| b > SYNC_BITS_LIMIT) {
+            diagnostics.warning(
+                ValidationWarning::SyncBudgetOverLimit(budget, SYNC_BITS_LIMIT),
+                root,
+                descriptor.span,
+            );
+        }
+        if !self.is_within_budget() {
+            diagnostics.error(
+                ValidationError::SyncBudgetExceeded(self.total(), self.budget),
+                root,
+                descriptor.span,
+            );
+        } else if self.total() * 100 > self.budget * NEAR_BUDGET_PERCENT {
+            diagnostics.warning(
+                ValidationWarning::SyncBudgetNearLimit(self.total(), self.budget),
+                root,
+                descriptor.span,
+            );
+        }
+    }
+
+    /// Writes a table of the parameters and the total against the budget.
+    pub fn write_report<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        let width = |header: &str, column: fn(&SyncEntry) -> &str| {
+            self.entries
+                .iter()
+                .map(|e| column(e).chars().count())
+                .chain([header.len()])
+                .max()
+                .unwrap_or(0)
+        };
+        let layer_width = width("Layer", |e| &e.layer);
+        let parameter_width = width("Parameter", |e| &e.parameter);
+
+        writeln!(
+            w,
+            "{:layer_width$}  {:parameter_width$}  {:5}  {:>4}  Sync",
+            "Layer", "Parameter", "Type", "Bits"
+        )?;
+        for entry in &self.entries {
+            let sync = if entry.synced { "synced" } else { "local" };
+            writeln!(
+                w,
+                "{:layer_width$}  {:parameter_width$}  {:5}  {:>4}  {sync}",
+                entry.layer,
+                entry.parameter,
+                entry.kind.name(),
+                entry.bits(),
+            )?;
+        }
+        writeln!(w, "Total: {} / {} bits", self.total(), self.budget)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SyncUsage;
+    use crate::descriptor::{
+        validate_descriptor, Descriptor, Severity, ShapeKeyGroup, ShapeKeyOption,
+    };
+
+    /// Descriptor with a single synced group, which uses 8 bits.
+    fn descriptor(budget: usize) -> Descriptor {
+        Descriptor::builder("Avatar")
+            .sync_budget(budget)
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .option(ShapeKeyOption::new("eyelids_close"))
+                    .build(),
+            )
+            .build()
+    }
+
+    fn messages(descriptor: &Descriptor, severity: Severity) -> Vec<String> {
+        validate_descriptor(descriptor)
+            .iter()
+            .filter(|d| d.severity == severity)
+            .map(|d| d.message.clone())
+            .collect()
+    }
+
+    #[test]
+    fn over_budget_is_an_error() {
+        let descriptor = descriptor(7);
+        assert_eq!(SyncUsage::new(&descriptor).total(), 8);
+        assert_eq!(
+            messages(&descriptor, Severity::Error),
+            ["synced parameters use 8 bits, exceeding the budget of 7 bits"]
+        );
+    }
+
+    #[test]
+    fn near_budget_is_a_warning() {
+        let descriptor = descriptor(8);
+        assert!(messages(&descriptor, Severity::Error).is_empty());
+        assert_eq!(
+            messages(&descriptor, Severity::Warning),
+            ["synced parameters use 8 of 8 bits"]
+        );
+    }
+
+    #[test]
+    fn usage_within_budget_is_not_reported() {
+        assert!(validate_descriptor(&descriptor(16)).iter().next().is_none());
+    }
+}
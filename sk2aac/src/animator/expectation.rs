@@ -0,0 +1,163 @@
+use crate::{
+    animator::{simulate, Assignment, ParameterValue},
+    descriptor::{parse_file, LoadError, ValidatedDescriptor},
+};
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Formatter, Result as FmtResult},
+    fs::read_dir,
+    io::Result as IoResult,
+    path::{Path, PathBuf},
+};
+
+use serde::{
+    de::{MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+
+/// Blend shape values within this difference are regarded as equal.
+const TOLERANCE: f64 = 0.001;
+
+const TEST_FILE_EXTENSION: &str = ".test.toml";
+
+/// Expectations on a descriptor, e.g. `avatar.test.toml` for `avatar.toml`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestFile {
+    pub cases: Vec<TestCase>,
+}
+
+impl TestFile {
+    pub fn load(path: impl AsRef<Path>) -> Result<TestFile, LoadError> {
+        parse_file(path.as_ref())
+    }
+
+    /// Finds `<stem>.test.toml` and `<stem>.<suffix>.test.toml` next to the descriptor.
+    pub fn find(descriptor_path: impl AsRef<Path>) -> IoResult<Vec<PathBuf>> {
+        let descriptor_path = descriptor_path.as_ref();
+        let stem = match descriptor_path.file_stem().and_then(|s| s.to_str()) {
+            Some(stem) => stem,
+            None => return Ok(vec![]),
+        };
+        let directory = match descriptor_path.parent() {
+            Some(d) if d.as_os_str().is_empty() => Path::new("."),
+            Some(d) => d,
+            None => Path::new("."),
+        };
+
+        let mut paths = vec![];
+        for entry in read_dir(directory)? {
+            let path = entry?.path();
+            let Some(file_name) = path.file_name().and_then(|n| n.to_str()) else {
+                continue;
+            };
+            let Some(middle) = file_name
+                .strip_prefix(stem)
+                .and_then(|n| n.strip_suffix(TEST_FILE_EXTENSION))
+            else {
+                continue;
+            };
+            if middle.is_empty() || middle.starts_with('.') {
+                paths.push(path);
+            }
+        }
+        paths.sort();
+        Ok(paths)
+    }
+}
+
+/// Parameters to set and blend shape values expected after that.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TestCase {
+    /// Displayed name. Cases are numbered if omitted.
+    pub name: Option<String>,
+
+    /// Assignments in the written order.
+    #[serde(default)]
+    pub set: Assignments,
+
+    /// Blend shape values by mesh and shape key, e.g. `Face.eyelids_close = 0.5`.
+    pub expect: BTreeMap<String, BTreeMap<String, f64>>,
+}
+
+impl TestCase {
+    /// Simulates the descriptor and compares the blend shapes.
+    /// Returns the failures, which are empty if the case passes.
+    pub fn run(&self, descriptor: &ValidatedDescriptor) -> Vec<String> {
+        let simulation = match simulate(descriptor, &self.set.0) {
+            Ok(s) => s,
+            Err(e) => return vec![e.to_string()],
+        };
+
+        let mut failures = vec![];
+        for (mesh, shapes) in &self.expect {
+            for (shape, &expected) in shapes {
+                match simulation.blend_shape(mesh, shape) {
+                    Some(actual) if (actual - expected).abs() <= TOLERANCE => (),
+                    Some(actual) => failures.push(format!(
+                        "{mesh}.{shape}: expected {expected:.3}, got {actual:.3}"
+                    )),
+                    None => failures.push(format!(
+                        "{mesh}.{shape}: expected {expected:.3}, but never animated"
+                    )),
+                }
+            }
+        }
+        failures
+    }
+}
+
+/// Assignments kept in the order of a TOML table.
+#[derive(Debug, Clone, Default)]
+pub struct Assignments(pub Vec<Assignment>);
+
+impl<'de> Deserialize<'de> for Assignments {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_map(AssignmentsVisitor)
+    }
+}
+
+struct AssignmentsVisitor;
+
+impl<'de> Visitor<'de> for AssignmentsVisitor {
+    type Value = Assignments;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter.write_str("table of parameter values")
+    }
+
+    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+    where
+        A: MapAccess<'de>,
+    {
+        let mut assignments = vec![];
+        while let Some((name, value)) = map.next_entry::<String, RawAssignedValue>()? {
+            assignments.push(Assignment::new(name, value.into()));
+        }
+        Ok(Assignments(assignments))
+    }
+}
+
+/// Bool, integer or float in TOML.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum RawAssignedValue {
+    Bool(bool),
+    Int(usize),
+    Float(f64),
+}
+
+impl From<RawAssignedValue> for ParameterValue {
+    fn from(raw: RawAssignedValue) -> ParameterValue {
+        match raw {
+            RawAssignedValue::Bool(b) => ParameterValue::Bool(b),
+            RawAssignedValue::Int(i) => ParameterValue::Int(i),
+            RawAssignedValue::Float(f) => ParameterValue::Float(f),
+        }
+    }
+}
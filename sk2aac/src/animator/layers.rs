@@ -0,0 +1,944 @@
+use crate::{
+    animator::{
+        AnimationTarget, BlendTree, BlendTreePosition, BlendTreeType, ClipItem, Cond, Expr,
+        ExpressionParameter, Layer, LayerKind, LayerParameter, Material, Motion, ParameterKind,
+        State, TrackingControl, Transition, ALIGN_UNIT, FLOAT_PREVENTION_THRESHOLD,
+    },
+    descriptor::{
+        to_identifier, Descriptor, ObjectToggle, ParameterSettings, PuppetBlendType,
+        ResolvedDriver, ResolvedGestureMap, ShapeKeyCommon, ShapeKeyDrive, ShapeKeyGroup,
+        ShapeKeyPuppet, ShapeKeySlider, ShapeKeySwitch, ValidatedDescriptor,
+    },
+};
+
+use std::collections::HashMap;
+
+/// Variable name of the parameter of the layer itself.
+const PARAMETER_VAR: &str = "parameter";
+const PARAMETER_X_VAR: &str = "parameterX";
+const PARAMETER_Y_VAR: &str = "parameterY";
+
+/// Blend shape value keyed by renderer index and shape key name.
+type BlendShapeValue = ((usize, String), f64);
+
+/// Collects the Expression Parameters of all layers.
+pub fn expression_parameters(descriptor: &Descriptor) -> Vec<ExpressionParameter> {
+    let parameter = |layer: &str, name: &str, kind, settings| ExpressionParameter {
+        layer: layer.to_string(),
+        name: name.to_string(),
+        kind,
+        settings,
+    };
+    let layer_parameter = |name: &str, kind, settings| parameter(name, name, kind, settings);
+
+    let switches = descriptor
+        .shape_switches
+        .iter()
+        .map(|s| layer_parameter(&s.common.name, ParameterKind::Bool, s.common.parameter));
+    // Bit-packed groups sync the bits, and the index is kept local for the menu.
+    let groups = descriptor.shape_groups.iter().flat_map(|g| {
+        let index = ExpressionParameter {
+            settings: ParameterSettings {
+                synced: g.common.parameter.synced && !g.bit_packed,
+                ..g.common.parameter
+            },
+            ..layer_parameter(&g.common.name, ParameterKind::Int, g.common.parameter)
+        };
+        let default_index = g.common.parameter.default as usize;
+        let bits = g
+            .bit_parameters()
+            .into_iter()
+            .enumerate()
+            .map(move |(i, name)| {
+                let settings = ParameterSettings {
+                    saved: false,
+                    synced: g.common.parameter.synced,
+                    default: ((default_index >> i) & 1) as f64,
+                    span: None,
+                };
+                parameter(&g.common.name, &name, ParameterKind::Bool, settings)
+            });
+        [index].into_iter().chain(bits)
+    });
+    let sliders = descriptor
+        .shape_sliders
+        .iter()
+        .map(|s| layer_parameter(&s.common.name, ParameterKind::Float, s.common.parameter));
+    let puppets = descriptor.shape_puppets.iter().flat_map(|p| {
+        [&p.parameter_x, &p.parameter_y].map(|name| {
+            parameter(
+                &p.common.name,
+                name,
+                ParameterKind::Float,
+                p.common.parameter,
+            )
+        })
+    });
+    let toggles = descriptor
+        .object_toggles
+        .iter()
+        .map(|t| layer_parameter(&t.name, ParameterKind::Bool, t.parameter));
+    let drivers = descriptor
+        .drivers
+        .iter()
+        .map(|d| layer_parameter(&d.name, ParameterKind::Int, d.parameter));
+
+    switches
+        .chain(groups)
+        .chain(sliders)
+        .chain(puppets)
+        .chain(toggles)
+        .chain(drivers)
+        .collect()
+}
+
+/// Builds all layers in the order of evaluation.
+pub fn layers(descriptor: &ValidatedDescriptor) -> Vec<Layer> {
+    let mut layers = vec![];
+    for target in [AnimationTarget::Eyelids, AnimationTarget::JawAndMouth] {
+        let params = prevention_parameters(descriptor, target);
+        let gestures = prevention_gestures(descriptor, target);
+        if !params.is_empty() || !gestures.is_empty() {
+            layers.push(tracking_control(target, params, gestures));
+        }
+    }
+    for switch in &descriptor.shape_switches {
+        layers.push(shape_key_switch(switch));
+    }
+    for group in &descriptor.shape_groups {
+        let gesture_maps: Vec<_> = descriptor
+            .gesture_maps
+            .iter()
+            .filter(|g| g.group == group.common.name)
+            .collect();
+        let weights: HashMap<_, _> = gesture_maps
+            .iter()
+            .flat_map(|g| g.mappings.iter())
+            .filter(|m| m.weighted)
+            .filter_map(|m| Some((m.label.clone(), m.hand.weight_parameter_name()?)))
+            .collect();
+        let gesture_maps: Vec<_> = gesture_maps
+            .into_iter()
+            .map(|g| ResolvedGestureMap::resolve(descriptor, g))
+            .collect();
+        if group.bit_packed {
+            layers.push(bit_encoder(group));
+        }
+        layers.push(shape_key_group(group, &gesture_maps, &weights));
+    }
+    for slider in &descriptor.shape_sliders {
+        layers.push(shape_key_slider(slider));
+    }
+    for puppet in &descriptor.shape_puppets {
+        layers.push(shape_key_puppet(puppet));
+    }
+    for toggle in &descriptor.object_toggles {
+        layers.push(object_toggle(toggle));
+    }
+    for driver in &descriptor.drivers {
+        layers.push(driver_layer(ResolvedDriver::resolve(descriptor, driver)));
+    }
+    layers
+}
+
+/// Variable name of a parameter defined in a shared layer.
+fn param_var(name: &str) -> String {
+    format!("param{}", to_identifier(name))
+}
+
+impl Layer {
+    fn new(name: impl Into<String>, kind: LayerKind) -> Layer {
+        Layer {
+            name: name.into(),
+            kind,
+            renderers: vec![],
+            objects: vec![],
+            parameters: vec![],
+            states: vec![],
+            transitions: vec![],
+        }
+    }
+
+    fn with_parameter(mut self, var: &str, name: &str, kind: ParameterKind) -> Layer {
+        self.parameters.push(LayerParameter {
+            var: var.to_string(),
+            name: name.to_string(),
+            kind,
+        });
+        self
+    }
+
+    fn add_transition(&mut self, from: &str, to: Option<&str>, condition: Cond) {
+        self.transitions.push(Transition {
+            from: from.to_string(),
+            to: to.map(|t| t.to_string()),
+            condition,
+        });
+    }
+}
+
+/// Parameters whose layers prevent the tracking, without duplicates.
+fn prevention_parameters(
+    descriptor: &Descriptor,
+    target: AnimationTarget,
+) -> Vec<(String, ParameterKind)> {
+    let prevents = |common: &ShapeKeyCommon| match target {
+        AnimationTarget::Eyelids => common.prevent_eyelids,
+        AnimationTarget::JawAndMouth => common.prevent_mouth,
+    };
+
+    let groups = descriptor
+        .shape_groups
+        .iter()
+        .filter(|g| prevents(&g.common))
+        .flat_map(|g| {
+            if g.bit_packed {
+                let bits = g.bit_parameters().into_iter();
+                bits.map(|b| (b, ParameterKind::Bool)).collect()
+            } else {
+                vec![(g.common.name.clone(), ParameterKind::Int)]
+            }
+        });
+    let switches = descriptor
+        .shape_switches
+        .iter()
+        .filter(|s| prevents(&s.common))
+        .map(|s| (s.common.name.clone(), ParameterKind::Bool));
+    let sliders = descriptor
+        .shape_sliders
+        .iter()
+        .filter(|s| prevents(&s.common))
+        .map(|s| (s.common.name.clone(), ParameterKind::Float));
+    let puppets = descriptor
+        .shape_puppets
+        .iter()
+        .filter(|p| prevents(&p.common))
+        .flat_map(|p| {
+            [
+                (p.parameter_x.clone(), ParameterKind::Float),
+                (p.parameter_y.clone(), ParameterKind::Float),
+            ]
+        });
+
+    let mut unique_params = vec![];
+    for param in groups.chain(switches).chain(sliders).chain(puppets) {
+        if !unique_params.contains(&param) {
+            unique_params.push(param);
+        }
+    }
+    unique_params
+}
+
+/// Gestures mapped to groups which prevent the tracking, as pairs of the hand and the gesture value.
+fn prevention_gestures(
+    descriptor: &ValidatedDescriptor,
+    target: AnimationTarget,
+) -> Vec<(&'static str, usize)> {
+    let mut unique_gestures = vec![];
+    for gesture_map in &descriptor.gesture_maps {
+        // Group name is already validated.
+        let group = descriptor
+            .shape_groups
+            .iter()
+            .find(|g| g.common.name == gesture_map.group)
+            .expect("Group not found");
+        let prevents = match target {
+            AnimationTarget::Eyelids => group.common.prevent_eyelids,
+            AnimationTarget::JawAndMouth => group.common.prevent_mouth,
+        };
+        if !prevents {
+            continue;
+        }
+
+        let resolved = ResolvedGestureMap::resolve(descriptor, gesture_map);
+        let primary = resolved.priority.parameter_name();
+        let secondary = resolved.priority.other().parameter_name();
+        for state in &resolved.states {
+            let primary = state.primary.iter().map(|g| (primary, g.value()));
+            let secondary = state.secondary.iter().map(|g| (secondary, g.value()));
+            for gesture in primary.chain(secondary) {
+                if !unique_gestures.contains(&gesture) {
+                    unique_gestures.push(gesture);
+                }
+            }
+        }
+    }
+    unique_gestures
+}
+
+/// Blocks the tracking while any of the parameters animates or any of the gestures is made.
+fn tracking_control(
+    target: AnimationTarget,
+    params: Vec<(String, ParameterKind)>,
+    gestures: Vec<(&'static str, usize)>,
+) -> Layer {
+    let name = format!("{}_TrackingControl", target.displayed_name());
+    let mut layer = Layer::new(name, LayerKind::TrackingControl(target));
+    for (name, kind) in &params {
+        layer = layer.with_parameter(&param_var(name), name, *kind);
+    }
+    let mut hands: Vec<_> = gestures.iter().map(|(hand, _)| *hand).collect();
+    hands.sort();
+    hands.dedup();
+    for hand in hands {
+        layer = layer.with_parameter(&param_var(hand), hand, ParameterKind::Int);
+    }
+
+    // Float parameters may be signed (puppets), so they are compared by magnitude.
+    let animated_condition = Cond::Or(
+        params
+            .iter()
+            .flat_map(|(p, kind)| match kind {
+                ParameterKind::Bool => vec![Cond::Term(Expr::IsTrue(param_var(p)))],
+                ParameterKind::Int => vec![Cond::Term(Expr::IntNotEqual(param_var(p), 0))],
+                ParameterKind::Float => vec![
+                    Cond::Term(Expr::FloatGreaterThan(
+                        param_var(p),
+                        FLOAT_PREVENTION_THRESHOLD,
+                    )),
+                    Cond::Term(Expr::FloatLessThan(
+                        param_var(p),
+                        -FLOAT_PREVENTION_THRESHOLD,
+                    )),
+                ],
+            })
+            .chain(
+                gestures
+                    .iter()
+                    .map(|&(hand, g)| Cond::Term(Expr::IntEqual(param_var(hand), g))),
+            )
+            .collect(),
+    );
+    let tracking_condition = Cond::And(
+        params
+            .iter()
+            .flat_map(|(p, kind)| match kind {
+                ParameterKind::Bool => vec![Cond::Term(Expr::IsFalse(param_var(p)))],
+                ParameterKind::Int => vec![Cond::Term(Expr::IntEqual(param_var(p), 0))],
+                ParameterKind::Float => vec![
+                    Cond::Term(Expr::FloatLessThan(
+                        param_var(p),
+                        FLOAT_PREVENTION_THRESHOLD,
+                    )),
+                    Cond::Term(Expr::FloatGreaterThan(
+                        param_var(p),
+                        -FLOAT_PREVENTION_THRESHOLD,
+                    )),
+                ],
+            })
+            .chain(
+                gestures
+                    .iter()
+                    .map(|&(hand, g)| Cond::Term(Expr::IntNotEqual(param_var(hand), g))),
+            )
+            .collect(),
+    );
+
+    layer.states = vec![
+        State {
+            tracking: Some(TrackingControl::Tracks(target)),
+            ..State::new("tracking", "Tracking")
+        },
+        State {
+            tracking: Some(TrackingControl::Animates(target)),
+            ..State::new("animated", "Animated")
+        },
+    ];
+    layer.add_transition("tracking", Some("animated"), animated_condition);
+    layer.add_transition("animated", Some("tracking"), tracking_condition);
+    layer
+}
+
+fn shape_key_switch(switch: &ShapeKeySwitch) -> Layer {
+    let renderers = Renderers::new(&switch.common.mesh, []);
+    let clip = |value: f64| {
+        let items = renderers
+            .targets(None)
+            .map(|renderer| ClipItem::BlendShape {
+                renderer,
+                shape: switch.shape.clone(),
+                value,
+            });
+        Motion::Clip(items.collect())
+    };
+
+    let mut layer = Layer::new(&switch.common.name, LayerKind::ShapeKeySwitch).with_parameter(
+        PARAMETER_VAR,
+        &switch.common.name,
+        ParameterKind::Bool,
+    );
+    layer.states = vec![
+        State {
+            motion: clip(switch.disabled_value.get()),
+            ..State::new("disabled", "false: Disabled")
+        },
+        State {
+            motion: clip(switch.enabled_value.get()),
+            ..State::new("enabled", "true: Enabled")
+        },
+    ];
+    layer.renderers = renderers.meshes;
+    add_bool_transitions(&mut layer);
+    layer
+}
+
+/// Switches `disabled` and `enabled` states by the Bool parameter.
+fn add_bool_transitions(layer: &mut Layer) {
+    layer.add_transition(
+        "disabled",
+        Some("enabled"),
+        Cond::Term(Expr::IsTrue(PARAMETER_VAR.into())),
+    );
+    layer.add_transition(
+        "enabled",
+        Some("disabled"),
+        Cond::Term(Expr::IsFalse(PARAMETER_VAR.into())),
+    );
+}
+
+/// `weights` maps option labels to Float parameters by which they are blended.
+/// Selects the option of the group parameter, or the one of the gesture while a mapped gesture is made.
+/// Weighted options blend by the gesture weight only when selected by the gesture.
+fn shape_key_group(
+    group: &ShapeKeyGroup,
+    gesture_maps: &[ResolvedGestureMap],
+    weights: &HashMap<String, &str>,
+) -> Layer {
+    let renderers = Renderers::new(
+        &group.common.mesh,
+        group
+            .defaults
+            .iter()
+            .chain(group.options.iter().flat_map(|o| o.shapes.iter())),
+    );
+    let default_values: HashMap<_, _> = renderers.drives(&group.defaults).into_iter().collect();
+    let mut drive_keys: Vec<_> = renderers
+        .drives(group.options.iter().flat_map(|o| o.shapes.iter()))
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    drive_keys.sort();
+    drive_keys.dedup();
+    let default_drives = drive_keys.into_iter().map(|k| {
+        let value = default_values.get(&k).copied().unwrap_or(0.0);
+        (k, value)
+    });
+    let mut weight_parameters: Vec<_> = weights.values().copied().collect();
+    weight_parameters.sort();
+    weight_parameters.dedup();
+    let mut material_slots: Vec<_> = group
+        .options
+        .iter()
+        .flat_map(|o| o.materials.iter())
+        .map(|m| m.slot)
+        .collect();
+    material_slots.sort();
+    material_slots.dedup();
+    let default_materials = material_slots.into_iter().map(|slot| ClipItem::Material {
+        renderer: 0,
+        slot,
+        material: Material::Original,
+    });
+
+    let gestures = GestureConditions::new(gesture_maps);
+
+    let bit_parameters = group.bit_parameters();
+    let mut layer = Layer::new(&group.common.name, LayerKind::ShapeKeyGroup);
+    if bit_parameters.is_empty() {
+        layer = layer.with_parameter(PARAMETER_VAR, &group.common.name, ParameterKind::Int);
+    }
+    for (i, bit_parameter) in bit_parameters.iter().enumerate() {
+        layer = layer.with_parameter(&bit_var(i), bit_parameter, ParameterKind::Bool);
+    }
+    for hand in &gestures.hands {
+        layer = layer.with_parameter(&param_var(hand), hand, ParameterKind::Int);
+    }
+    for weight_parameter in weight_parameters {
+        layer = layer.with_parameter(
+            &param_var(weight_parameter),
+            weight_parameter,
+            ParameterKind::Float,
+        );
+    }
+    layer.states.push(State {
+        motion: Motion::Clip(
+            default_drives
+                .map(ClipItem::blend_shape)
+                .chain(default_materials)
+                .collect(),
+        ),
+        ..State::new("disabled", "0: Disabled")
+    });
+
+    // Indices are already validated to be unique.
+    let mut right_of = "disabled".to_string();
+    let mut gesture_states = vec![];
+    for (i, option) in group.options.iter().enumerate() {
+        let index = option.index.unwrap_or(i + 1);
+
+        let state_var = format!("enabled{index}");
+        let state_name = format!("{index}: {}", option.label);
+        let blend_shapes = renderers.drives(&option.shapes);
+        let materials = option.materials.iter().map(|m| ClipItem::Material {
+            renderer: 0,
+            slot: m.slot,
+            material: Material::Asset(m.material.clone()),
+        });
+        let max_items: Vec<_> = blend_shapes
+            .iter()
+            .cloned()
+            .map(ClipItem::blend_shape)
+            .chain(materials)
+            .collect();
+
+        if let Some((_, gesture_cond)) = gestures.states.iter().find(|(i, _)| *i == index) {
+            let motion = match weights.get(&option.label) {
+                Some(weight_parameter) => {
+                    let min_drives = blend_shapes.iter().map(|(k, _)| {
+                        let value = default_values.get(k).copied().unwrap_or(0.0);
+                        (k.clone(), value)
+                    });
+                    Motion::BlendTree(BlendTree {
+                        var: format!("tree{index}"),
+                        blend_type: BlendTreeType::Simple1D,
+                        parameters: vec![param_var(weight_parameter)],
+                        children: vec![
+                            (
+                                BlendTreePosition::Threshold(0.0),
+                                min_drives.map(ClipItem::blend_shape).collect(),
+                            ),
+                            (BlendTreePosition::Threshold(1.0), max_items.clone()),
+                        ],
+                    })
+                }
+                None => Motion::Clip(max_items.clone()),
+            };
+            let gesture_state = State {
+                motion,
+                ..State::new(
+                    format!("gesture{index}"),
+                    format!("{index}: {} (Gesture)", option.label),
+                )
+            };
+            gesture_states.push((gesture_state, gesture_cond.clone()));
+        }
+
+        let mut state = State {
+            motion: Motion::Clip(max_items),
+            ..State::new(&state_var, state_name)
+        };
+        if i % ALIGN_UNIT == 0 {
+            state.right_of = Some(right_of);
+            right_of = state_var.clone();
+        }
+        layer.states.push(state);
+
+        // Gestures take over the menu selection while made.
+        let (match_terms, mismatch_terms) = if bit_parameters.is_empty() {
+            (
+                vec![Cond::Term(Expr::IntEqual(PARAMETER_VAR.into(), index))],
+                vec![Cond::Term(Expr::IntNotEqual(PARAMETER_VAR.into(), index))],
+            )
+        } else {
+            bit_conditions(bit_parameters.len(), index)
+        };
+        let enter_cond = Cond::And(
+            match_terms
+                .into_iter()
+                .chain(gestures.free.clone())
+                .collect(),
+        );
+        let exit_cond = Cond::Or(
+            mismatch_terms
+                .into_iter()
+                .chain(gestures.made.clone())
+                .collect(),
+        );
+        layer.add_transition("disabled", Some(&state_var), enter_cond);
+        layer.add_transition(&state_var, None, exit_cond);
+    }
+
+    // Gestures are mutually exclusive, so each of them is entered directly from the others.
+    for (i, (mut state, _)) in gesture_states.iter().cloned().enumerate() {
+        if i % ALIGN_UNIT == 0 {
+            state.right_of = Some(right_of);
+            right_of = state.var.clone();
+        }
+        layer.states.push(state);
+    }
+    for (to, cond) in &gesture_states {
+        layer.add_transition("disabled", Some(&to.var), cond.clone());
+        for (from, _) in &gesture_states {
+            if from.var != to.var {
+                layer.add_transition(&from.var, Some(&to.var), cond.clone());
+            }
+        }
+    }
+    for (from, _) in &gesture_states {
+        layer.add_transition(&from.var, None, Cond::And(gestures.free.clone()));
+    }
+    layer.renderers = renderers.meshes;
+    layer
+}
+
+/// Conditions of the gestures mapped to a group.
+/// Earlier gesture maps win, and so does the preferred hand of each map.
+#[derive(Debug, Clone, Default)]
+struct GestureConditions {
+    /// Gesture parameters referred by the conditions.
+    hands: Vec<&'static str>,
+
+    /// Conditions which select the options, keyed by the option index.
+    states: Vec<(usize, Cond)>,
+
+    /// Terms that all hold while no mapped gesture is made.
+    free: Vec<Cond>,
+
+    /// Terms any of which holds while a mapped gesture is made.
+    made: Vec<Cond>,
+}
+
+impl GestureConditions {
+    fn new(gesture_maps: &[ResolvedGestureMap]) -> GestureConditions {
+        let mut conditions = GestureConditions::default();
+        for gesture_map in gesture_maps {
+            let hands = [gesture_map.priority, gesture_map.priority.other()];
+            let [primary_var, secondary_var] = hands.map(|h| param_var(h.parameter_name()));
+            for hand in hands {
+                if !conditions.hands.contains(&hand.parameter_name()) {
+                    conditions.hands.push(hand.parameter_name());
+                }
+            }
+
+            // The other hand is considered only while the preferred one makes no mapped gesture.
+            let primary_gestures = gesture_map.states.iter().flat_map(|s| s.primary.iter());
+            let primary_free: Vec<_> = primary_gestures
+                .map(|g| Cond::Term(Expr::IntNotEqual(primary_var.clone(), g.value())))
+                .collect();
+            for state in &gesture_map.states {
+                let primary_clauses = state.primary.iter().map(|g| {
+                    let mut terms =
+                        vec![Cond::Term(Expr::IntEqual(primary_var.clone(), g.value()))];
+                    terms.extend(conditions.free.iter().cloned());
+                    Cond::And(terms)
+                });
+                let secondary_clauses = state.secondary.iter().map(|g| {
+                    let mut terms =
+                        vec![Cond::Term(Expr::IntEqual(secondary_var.clone(), g.value()))];
+                    terms.extend(primary_free.iter().cloned());
+                    terms.extend(conditions.free.iter().cloned());
+                    Cond::And(terms)
+                });
+                let clauses: Vec<_> = primary_clauses.chain(secondary_clauses).collect();
+                match conditions
+                    .states
+                    .iter_mut()
+                    .find(|(i, _)| *i == state.index)
+                {
+                    Some((_, Cond::Or(existing))) => existing.extend(clauses),
+                    _ => conditions.states.push((state.index, Cond::Or(clauses))),
+                }
+            }
+
+            let mapped = gesture_map.states.iter().flat_map(|s| {
+                let primary = s.primary.iter().map(|g| (&primary_var, g.value()));
+                let secondary = s.secondary.iter().map(|g| (&secondary_var, g.value()));
+                primary.chain(secondary)
+            });
+            for (var, gesture) in mapped {
+                let free = Cond::Term(Expr::IntNotEqual(var.clone(), gesture));
+                if !conditions.free.contains(&free) {
+                    conditions.free.push(free);
+                    conditions
+                        .made
+                        .push(Cond::Term(Expr::IntEqual(var.clone(), gesture)));
+                }
+            }
+        }
+        conditions
+    }
+}
+
+/// Variable name of a bit parameter of a bit-packed group.
+fn bit_var(bit: usize) -> String {
+    format!("parameterBit{bit}")
+}
+
+/// Terms that all hold while the bits match the index, and terms any of which holds otherwise.
+fn bit_conditions(bits: usize, index: usize) -> (Vec<Cond>, Vec<Cond>) {
+    let bit_expr = |bit: usize, matches: bool| {
+        if ((index >> bit) & 1 == 1) == matches {
+            Cond::Term(Expr::IsTrue(bit_var(bit)))
+        } else {
+            Cond::Term(Expr::IsFalse(bit_var(bit)))
+        }
+    };
+    (
+        (0..bits).map(|b| bit_expr(b, true)).collect(),
+        (0..bits).map(|b| bit_expr(b, false)).collect(),
+    )
+}
+
+/// Drives the bits of a bit-packed group from its local Int parameter.
+fn bit_encoder(group: &ShapeKeyGroup) -> Layer {
+    let name = group.encoder_layer_name().expect("Bit-packed group");
+    let mut layer = Layer::new(name, LayerKind::BitEncoder).with_parameter(
+        PARAMETER_VAR,
+        &group.common.name,
+        ParameterKind::Int,
+    );
+    layer.states.push(State::new("waiting", "Waiting"));
+
+    let labels = group
+        .option_indices()
+        .zip(group.options.iter().map(|o| o.label.as_str()));
+    let mut right_of = "waiting".to_string();
+    for (i, (index, label)) in [(0, "Disabled")].into_iter().chain(labels).enumerate() {
+        let state_var = format!("index{index}");
+        let mut state = State {
+            drives: group.bit_drives(index),
+            ..State::new(&state_var, format!("{index}: {label}"))
+        };
+        if i % ALIGN_UNIT == 0 {
+            state.right_of = Some(right_of);
+            right_of = state_var.clone();
+        }
+        layer.states.push(state);
+
+        layer.add_transition(
+            "waiting",
+            Some(&state_var),
+            Cond::Term(Expr::IntEqual(PARAMETER_VAR.into(), index)),
+        );
+        layer.add_transition(
+            &state_var,
+            None,
+            Cond::Term(Expr::IntNotEqual(PARAMETER_VAR.into(), index)),
+        );
+    }
+    layer
+}
+
+fn shape_key_slider(slider: &ShapeKeySlider) -> Layer {
+    let renderers = Renderers::new(
+        &slider.common.mesh,
+        slider.defaults.iter().chain(slider.shapes.iter()),
+    );
+    let default_values: HashMap<_, _> = renderers.drives(&slider.defaults).into_iter().collect();
+    let max_drives = renderers.drives(&slider.shapes);
+    let mut drive_keys: Vec<_> = max_drives.iter().map(|(k, _)| k.clone()).collect();
+    drive_keys.sort();
+    drive_keys.dedup();
+    let min_drives = drive_keys.into_iter().map(|k| {
+        let value = default_values.get(&k).copied().unwrap_or(0.0);
+        (k, value)
+    });
+
+    let tree = BlendTree {
+        var: "tree".into(),
+        blend_type: BlendTreeType::Simple1D,
+        parameters: vec![PARAMETER_VAR.into()],
+        children: vec![
+            (
+                BlendTreePosition::Threshold(0.0),
+                min_drives.map(ClipItem::blend_shape).collect(),
+            ),
+            (
+                BlendTreePosition::Threshold(1.0),
+                max_drives.into_iter().map(ClipItem::blend_shape).collect(),
+            ),
+        ],
+    };
+    let mut layer = Layer::new(&slider.common.name, LayerKind::ShapeKeySlider).with_parameter(
+        PARAMETER_VAR,
+        &slider.common.name,
+        ParameterKind::Float,
+    );
+    layer.states.push(State {
+        motion: Motion::BlendTree(tree),
+        ..State::new("blend", "Blend")
+    });
+    layer.renderers = renderers.meshes;
+    layer
+}
+
+fn shape_key_puppet(puppet: &ShapeKeyPuppet) -> Layer {
+    let renderers = Renderers::new(
+        &puppet.common.mesh,
+        puppet
+            .defaults
+            .iter()
+            .chain(puppet.samples.iter().flat_map(|s| s.shapes.iter())),
+    );
+    let default_values: HashMap<_, _> = renderers.drives(&puppet.defaults).into_iter().collect();
+    let mut drive_keys: Vec<_> = renderers
+        .drives(puppet.samples.iter().flat_map(|s| s.shapes.iter()))
+        .into_iter()
+        .map(|(k, _)| k)
+        .collect();
+    drive_keys.sort();
+    drive_keys.dedup();
+    let blend_type = match puppet.blend_type {
+        PuppetBlendType::SimpleDirectional => BlendTreeType::SimpleDirectional2D,
+        PuppetBlendType::FreeformDirectional => BlendTreeType::FreeformDirectional2D,
+        PuppetBlendType::FreeformCartesian => BlendTreeType::FreeformCartesian2D,
+    };
+
+    // Every sample drives all shape keys so that blending never leaves stale values.
+    let children = puppet.samples.iter().map(|sample| {
+        let sample_values: HashMap<_, _> = renderers.drives(&sample.shapes).into_iter().collect();
+        let drives = drive_keys.iter().map(|k| {
+            let value = sample_values
+                .get(k)
+                .or_else(|| default_values.get(k))
+                .copied()
+                .unwrap_or(0.0);
+            (k.clone(), value)
+        });
+        (
+            BlendTreePosition::Position(sample.x, sample.y),
+            drives.map(ClipItem::blend_shape).collect(),
+        )
+    });
+    let tree = BlendTree {
+        var: "tree".into(),
+        blend_type,
+        parameters: vec![PARAMETER_X_VAR.into(), PARAMETER_Y_VAR.into()],
+        children: children.collect(),
+    };
+
+    let mut layer = Layer::new(&puppet.common.name, LayerKind::ShapeKeyPuppet)
+        .with_parameter(PARAMETER_X_VAR, &puppet.parameter_x, ParameterKind::Float)
+        .with_parameter(PARAMETER_Y_VAR, &puppet.parameter_y, ParameterKind::Float);
+    layer.states.push(State {
+        motion: Motion::BlendTree(tree),
+        ..State::new("blend", "Blend")
+    });
+    layer.renderers = renderers.meshes;
+    layer
+}
+
+fn object_toggle(toggle: &ObjectToggle) -> Layer {
+    let mut layer = Layer::new(&toggle.name, LayerKind::ObjectToggle).with_parameter(
+        PARAMETER_VAR,
+        &toggle.name,
+        ParameterKind::Bool,
+    );
+    layer.objects = toggle.objects.clone();
+    layer.states = vec![
+        State {
+            motion: Motion::Clip(vec![ClipItem::ObjectActive(false)]),
+            ..State::new("disabled", "false: Disabled")
+        },
+        State {
+            motion: Motion::Clip(vec![ClipItem::ObjectActive(true)]),
+            ..State::new("enabled", "true: Enabled")
+        },
+    ];
+    add_bool_transitions(&mut layer);
+    layer
+}
+
+fn driver_layer(driver: ResolvedDriver) -> Layer {
+    let mut layer = Layer::new(&driver.name, LayerKind::Driver).with_parameter(
+        PARAMETER_VAR,
+        &driver.name,
+        ParameterKind::Int,
+    );
+    layer.states.push(State::new("waiting", "0: Waiting"));
+
+    let mut right_of = "waiting".to_string();
+    for (i, option) in driver.options.into_iter().enumerate() {
+        let index = i + 1;
+
+        let state_var = format!("option{index}");
+        let mut state = State {
+            drives: option.drives,
+            ..State::new(&state_var, format!("{index}: {}", option.label))
+        };
+        if i % ALIGN_UNIT == 0 {
+            state.right_of = Some(right_of);
+            right_of = state_var.clone();
+        }
+        layer.states.push(state);
+
+        layer.add_transition(
+            "waiting",
+            Some(&state_var),
+            Cond::Term(Expr::IntEqual(PARAMETER_VAR.into(), index)),
+        );
+        layer.add_transition(
+            &state_var,
+            None,
+            Cond::Term(Expr::IntNotEqual(PARAMETER_VAR.into(), index)),
+        );
+    }
+    layer
+}
+
+impl ClipItem {
+    fn blend_shape(((renderer, shape), value): BlendShapeValue) -> ClipItem {
+        ClipItem::BlendShape {
+            renderer,
+            shape,
+            value,
+        }
+    }
+}
+
+/// Target SkinnedMeshRenderers of a layer.
+#[derive(Debug, Clone)]
+struct Renderers {
+    meshes: Vec<String>,
+    layer_meshes: usize,
+}
+
+impl Renderers {
+    /// Collects distinct meshes of the layer and drive overrides.
+    fn new<'a>(
+        layer_meshes: &[String],
+        drives: impl IntoIterator<Item = &'a ShapeKeyDrive>,
+    ) -> Self {
+        let mut meshes: Vec<String> = vec![];
+        for mesh in layer_meshes {
+            if !meshes.contains(mesh) {
+                meshes.push(mesh.clone());
+            }
+        }
+        let layer_meshes = meshes.len();
+        for mesh in drives.into_iter().filter_map(|d| d.mesh.as_ref()) {
+            if !meshes.contains(mesh) {
+                meshes.push(mesh.clone());
+            }
+        }
+        Renderers {
+            meshes,
+            layer_meshes,
+        }
+    }
+
+    /// Renderers driven by a drive with given mesh override.
+    fn targets(&self, mesh: Option<&str>) -> impl Iterator<Item = usize> {
+        match mesh {
+            Some(mesh) => {
+                let index = self.meshes.iter().position(|m| m == mesh);
+                index.map_or(0..0, |i| i..i + 1)
+            }
+            None => 0..self.layer_meshes,
+        }
+    }
+
+    /// Binds drives to renderers.
+    fn drives<'a>(
+        &self,
+        drives: impl IntoIterator<Item = &'a ShapeKeyDrive>,
+    ) -> Vec<BlendShapeValue> {
+        drives
+            .into_iter()
+            .flat_map(|d| {
+                self.targets(d.mesh.as_deref())
+                    .map(|r| ((r, d.shape.clone()), d.value.get()))
+            })
+            .collect()
+    }
+}
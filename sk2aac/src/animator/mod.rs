@@ -0,0 +1,328 @@
+//! FX layers built from descriptors, shared by the code generator and the simulator.
+
+mod budget;
+mod expectation;
+mod layers;
+mod simulation;
+
+pub use self::{
+    budget::{SyncEntry, SyncUsage, SYNC_BITS_LIMIT},
+    expectation::{Assignments, TestCase, TestFile},
+    simulation::{simulate, Assignment, ParameterValue, Simulation, SimulationError},
+};
+
+use crate::descriptor::{ParameterSettings, ResolvedDrive, ValidatedDescriptor};
+
+/// States in a row are aligned by this count in the editor.
+const ALIGN_UNIT: usize = 8;
+
+/// Float parameters whose magnitude is below this are regarded as zero by tracking control.
+pub const FLOAT_PREVENTION_THRESHOLD: f64 = 0.01;
+
+/// Expression Parameters and FX layers generated from a descriptor.
+#[derive(Debug, Clone)]
+pub struct Animator {
+    /// Expression Parameters in the order of definition.
+    pub parameters: Vec<ExpressionParameter>,
+
+    /// FX layers in the order of evaluation.
+    pub layers: Vec<Layer>,
+}
+
+impl Animator {
+    /// Builds the layers of the descriptor.
+    pub fn new(descriptor: &ValidatedDescriptor) -> Animator {
+        Animator {
+            parameters: layers::expression_parameters(descriptor),
+            layers: layers::layers(descriptor),
+        }
+    }
+}
+
+/// Expression Parameter of a layer.
+#[derive(Debug, Clone)]
+pub struct ExpressionParameter {
+    /// Name of the layer which defines the parameter.
+    pub layer: String,
+
+    pub name: String,
+    pub kind: ParameterKind,
+    pub settings: ParameterSettings,
+}
+
+/// Type of an Animator parameter.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ParameterKind {
+    Bool,
+    Int,
+    Float,
+}
+
+impl ParameterKind {
+    /// Type name in Unity.
+    pub fn name(&self) -> &str {
+        match self {
+            ParameterKind::Bool => "Bool",
+            ParameterKind::Int => "Int",
+            ParameterKind::Float => "Float",
+        }
+    }
+
+    /// Bits used to sync the parameter over the network.
+    pub fn sync_bits(&self) -> usize {
+        match self {
+            ParameterKind::Bool => 1,
+            ParameterKind::Int | ParameterKind::Float => 8,
+        }
+    }
+}
+
+/// An FX layer.
+#[derive(Debug, Clone)]
+pub struct Layer {
+    pub name: String,
+    pub kind: LayerKind,
+
+    /// Transform paths of target SkinnedMeshRenderers. Clips refer to them by index.
+    pub renderers: Vec<String>,
+
+    /// Transform paths of GameObjects toggled by `ClipItem::ObjectActive`.
+    pub objects: Vec<String>,
+
+    /// Parameters referred by conditions and blend trees.
+    pub parameters: Vec<LayerParameter>,
+
+    /// States. The first one is the default state.
+    pub states: Vec<State>,
+
+    pub transitions: Vec<Transition>,
+}
+
+impl Layer {
+    /// Finds the parameter by its variable name.
+    pub fn parameter(&self, var: &str) -> Option<&LayerParameter> {
+        self.parameters.iter().find(|p| p.var == var)
+    }
+
+    /// Finds the state by its variable name.
+    pub fn state(&self, var: &str) -> Option<&State> {
+        self.states.iter().find(|s| s.var == var)
+    }
+}
+
+/// Descriptor layer which a layer is generated from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LayerKind {
+    TrackingControl(AnimationTarget),
+    ShapeKeySwitch,
+    ShapeKeyGroup,
+    BitEncoder,
+    ShapeKeySlider,
+    ShapeKeyPuppet,
+    ObjectToggle,
+    Driver,
+}
+
+/// Tracking layer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AnimationTarget {
+    Eyelids,
+    JawAndMouth,
+}
+
+impl AnimationTarget {
+    /// Name used for the tracking control layer.
+    pub fn displayed_name(&self) -> &str {
+        match self {
+            AnimationTarget::Eyelids => "Eyes",
+            AnimationTarget::JawAndMouth => "Mouth",
+        }
+    }
+}
+
+/// Parameter used in a layer.
+#[derive(Debug, Clone)]
+pub struct LayerParameter {
+    /// Identifier referring to the parameter in the layer.
+    pub var: String,
+
+    /// Animator parameter name.
+    pub name: String,
+
+    pub kind: ParameterKind,
+}
+
+/// A state in a layer.
+#[derive(Debug, Clone)]
+pub struct State {
+    /// Identifier referring to the state in the layer.
+    pub var: String,
+
+    /// Displayed name, e.g. `2: eyelids_close`.
+    pub name: String,
+
+    pub motion: Motion,
+
+    /// Tracking control of the state.
+    pub tracking: Option<TrackingControl>,
+
+    /// Parameters driven locally on entering the state.
+    pub drives: Vec<ResolvedDrive>,
+
+    /// Starts a new row right of the state in the editor.
+    pub right_of: Option<String>,
+}
+
+impl State {
+    fn new(var: impl Into<String>, name: impl Into<String>) -> State {
+        State {
+            var: var.into(),
+            name: name.into(),
+            motion: Motion::Clip(vec![]),
+            tracking: None,
+            drives: vec![],
+            right_of: None,
+        }
+    }
+}
+
+/// Motion of a state.
+#[derive(Debug, Clone)]
+pub enum Motion {
+    Clip(Vec<ClipItem>),
+    BlendTree(BlendTree),
+}
+
+/// Animated property in a clip.
+#[derive(Debug, Clone)]
+pub enum ClipItem {
+    BlendShape {
+        renderer: usize,
+        shape: String,
+        value: f64,
+    },
+    Material {
+        renderer: usize,
+        slot: usize,
+        material: Material,
+    },
+    /// Activates or deactivates all objects of the layer.
+    ObjectActive(bool),
+}
+
+/// Material set to a slot.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Material {
+    /// The material originally set to the slot.
+    Original,
+
+    /// Material asset path.
+    Asset(String),
+}
+
+/// Blend tree motion.
+#[derive(Debug, Clone)]
+pub struct BlendTree {
+    /// Identifier referring to the blend tree in the layer.
+    pub var: String,
+
+    pub blend_type: BlendTreeType,
+
+    /// Variable names of the blend parameters. 2D blend trees have two.
+    pub parameters: Vec<String>,
+
+    pub children: Vec<(BlendTreePosition, Vec<ClipItem>)>,
+}
+
+/// `BlendTreeType` in Unity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BlendTreeType {
+    Simple1D,
+    SimpleDirectional2D,
+    FreeformDirectional2D,
+    FreeformCartesian2D,
+}
+
+impl BlendTreeType {
+    pub fn name(&self) -> &str {
+        match self {
+            BlendTreeType::Simple1D => "Simple1D",
+            BlendTreeType::SimpleDirectional2D => "SimpleDirectional2D",
+            BlendTreeType::FreeformDirectional2D => "FreeformDirectional2D",
+            BlendTreeType::FreeformCartesian2D => "FreeformCartesian2D",
+        }
+    }
+
+    pub fn is_2d(&self) -> bool {
+        !matches!(self, BlendTreeType::Simple1D)
+    }
+}
+
+/// Position of a blend tree child motion.
+#[derive(Debug, Clone, Copy)]
+pub enum BlendTreePosition {
+    Threshold(f64),
+    Position(f64, f64),
+}
+
+/// Tracking control of a state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrackingControl {
+    Tracks(AnimationTarget),
+    Animates(AnimationTarget),
+}
+
+/// Transition between states.
+#[derive(Debug, Clone)]
+pub struct Transition {
+    pub from: String,
+
+    /// Destination state. `None` exits to the default state.
+    pub to: Option<String>,
+
+    pub condition: Cond,
+}
+
+/// Condition term on a parameter variable.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    IntEqual(String, usize),
+    IntNotEqual(String, usize),
+    IsTrue(String),
+    IsFalse(String),
+    FloatGreaterThan(String, f64),
+    FloatLessThan(String, f64),
+}
+
+/// Transition condition in disjunctive normal form.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Cond {
+    Or(Vec<Cond>),
+    And(Vec<Cond>),
+    Term(Expr),
+}
+
+impl Cond {
+    pub fn is_valid(&self) -> bool {
+        match self {
+            Cond::Or(_) => self.is_valid_or(),
+            Cond::And(_) => self.is_valid_and(),
+            Cond::Term(_) => true,
+        }
+    }
+
+    fn is_valid_and(&self) -> bool {
+        match self {
+            Cond::And(terms) => terms.iter().all(|t| matches!(t, Cond::Term(_))),
+            Cond::Term(_) => true,
+            _ => false,
+        }
+    }
+
+    fn is_valid_or(&self) -> bool {
+        match self {
+            Cond::Or(terms) => terms.iter().all(|t| t.is_valid_and()),
+            _ => false,
+        }
+    }
+}
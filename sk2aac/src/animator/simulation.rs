@@ -0,0 +1,841 @@
+use crate::{
+    animator::{
+        AnimationTarget, Animator, BlendTree, BlendTreePosition, ClipItem, Cond, Expr, Layer,
+        LayerKind, Material, Motion, ParameterKind, TrackingControl,
+    },
+    descriptor::{ResolvedDrive, ValidatedDescriptor},
+};
+
+use std::{
+    collections::BTreeMap,
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{prelude::*, Result as IoResult},
+    str::FromStr,
+};
+
+use thiserror::Error as ThisError;
+
+/// Layers taking more frames than this to settle are regarded as oscillating.
+const MAX_FRAMES: usize = 256;
+
+#[derive(Debug, ThisError)]
+pub enum SimulationError {
+    /// An assignment is not in the form of `Name=value`.
+    #[error("invalid assignment {0:?}, expected `Name=value`")]
+    InvalidAssignment(String),
+
+    /// No layer uses the parameter.
+    #[error("unknown parameter {0:?}")]
+    UnknownParameter(String),
+
+    /// The value cannot be assigned to the parameter.
+    #[error("{0:?} is a {} parameter, but {2} is assigned", .1.name())]
+    TypeMismatch(String, ParameterKind, ParameterValue),
+
+    /// Transitions keep firing, e.g. drivers drive each other.
+    #[error("layers did not settle within {MAX_FRAMES} frames after setting {0:?}")]
+    NotSettled(String),
+}
+
+/// Value of an Animator parameter.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ParameterValue {
+    Bool(bool),
+    Int(usize),
+    Float(f64),
+}
+
+impl ParameterValue {
+    /// Value of the parameter type from an Expression Parameter default.
+    fn from_default(kind: ParameterKind, default: f64) -> ParameterValue {
+        match kind {
+            ParameterKind::Bool => ParameterValue::Bool(default != 0.0),
+            ParameterKind::Int => ParameterValue::Int(default.max(0.0) as usize),
+            ParameterKind::Float => ParameterValue::Float(default),
+        }
+    }
+
+    /// Converts to the parameter type. Only Int values are converted into Float.
+    fn coerce(self, kind: ParameterKind) -> Option<ParameterValue> {
+        match (self, kind) {
+            (ParameterValue::Bool(_), ParameterKind::Bool)
+            | (ParameterValue::Int(_), ParameterKind::Int)
+            | (ParameterValue::Float(_), ParameterKind::Float) => Some(self),
+            (ParameterValue::Int(i), ParameterKind::Float) => Some(ParameterValue::Float(i as f64)),
+            _ => None,
+        }
+    }
+
+    fn as_bool(&self) -> bool {
+        match *self {
+            ParameterValue::Bool(b) => b,
+            ParameterValue::Int(i) => i != 0,
+            ParameterValue::Float(f) => f != 0.0,
+        }
+    }
+
+    fn as_int(&self) -> usize {
+        match *self {
+            ParameterValue::Bool(b) => b as usize,
+            ParameterValue::Int(i) => i,
+            ParameterValue::Float(f) => f.max(0.0) as usize,
+        }
+    }
+
+    fn as_float(&self) -> f64 {
+        match *self {
+            ParameterValue::Bool(b) => b as usize as f64,
+            ParameterValue::Int(i) => i as f64,
+            ParameterValue::Float(f) => f,
+        }
+    }
+}
+
+impl Display for ParameterValue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            ParameterValue::Bool(b) => write!(f, "{b}"),
+            ParameterValue::Int(i) => write!(f, "{i}"),
+            ParameterValue::Float(v) => write!(f, "{v:?}"),
+        }
+    }
+}
+
+impl FromStr for ParameterValue {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "true" => Ok(ParameterValue::Bool(true)),
+            "false" => Ok(ParameterValue::Bool(false)),
+            s => match s.parse() {
+                Ok(i) => Ok(ParameterValue::Int(i)),
+                Err(_) => s.parse().map(ParameterValue::Float).map_err(|_| ()),
+            },
+        }
+    }
+}
+
+/// Parameter set from outside of the animator, e.g. by the Expressions Menu.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Assignment {
+    pub name: String,
+    pub value: ParameterValue,
+}
+
+impl Assignment {
+    pub fn new(name: impl Into<String>, value: ParameterValue) -> Assignment {
+        Assignment {
+            name: name.into(),
+            value,
+        }
+    }
+
+    /// Parses comma-separated assignments, e.g. `Eyelids=2, Cheek=true`.
+    pub fn parse_list(s: &str) -> Result<Vec<Assignment>, SimulationError> {
+        s.split(',')
+            .map(str::trim)
+            .filter(|a| !a.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+impl FromStr for Assignment {
+    type Err = SimulationError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = || SimulationError::InvalidAssignment(s.to_string());
+        let (name, value) = s.split_once('=').ok_or_else(invalid)?;
+        let (name, value) = (name.trim(), value.trim());
+        if name.is_empty() {
+            return Err(invalid());
+        }
+        let value = value.parse().map_err(|_| invalid())?;
+        Ok(Assignment::new(name, value))
+    }
+}
+
+/// State of the avatar after the layers settle.
+#[derive(Debug, Clone)]
+pub struct Simulation {
+    /// Current state name of each layer.
+    pub states: Vec<(String, String)>,
+
+    /// Tracking control of eyelids and mouth.
+    pub tracking: Vec<TrackingControl>,
+
+    /// Animated blend shape values in [0, 1] by mesh and shape key.
+    pub blend_shapes: BTreeMap<String, BTreeMap<String, f64>>,
+
+    /// Animated materials by mesh and slot.
+    pub materials: BTreeMap<(String, usize), Material>,
+
+    /// Animated activity of GameObjects.
+    pub objects: BTreeMap<String, bool>,
+
+    /// Final values of all parameters used in the layers.
+    pub parameters: Vec<(String, ParameterValue)>,
+}
+
+impl Simulation {
+    /// Animated value of the blend shape.
+    pub fn blend_shape(&self, mesh: &str, shape: &str) -> Option<f64> {
+        self.blend_shapes.get(mesh)?.get(shape).copied()
+    }
+
+    /// Final value of the parameter.
+    pub fn parameter(&self, name: &str) -> Option<ParameterValue> {
+        self.parameters
+            .iter()
+            .find(|(n, _)| n == name)
+            .map(|(_, v)| *v)
+    }
+
+    /// Writes the result in a human-readable form.
+    pub fn write_report<W: Write>(&self, w: &mut W) -> IoResult<()> {
+        writeln!(w, "States:")?;
+        for (layer, state) in &self.states {
+            writeln!(w, "    {layer}: {state}")?;
+        }
+
+        writeln!(w, "Tracking:")?;
+        for tracking in &self.tracking {
+            match tracking {
+                TrackingControl::Tracks(t) => writeln!(w, "    {}: tracking", t.displayed_name())?,
+                TrackingControl::Animates(t) => {
+                    writeln!(w, "    {}: animated", t.displayed_name())?
+                }
+            }
+        }
+
+        writeln!(w, "Blend shapes:")?;
+        for (mesh, shapes) in &self.blend_shapes {
+            writeln!(w, "    {mesh}:")?;
+            for (shape, value) in shapes {
+                writeln!(w, "        {shape} = {value:.3}")?;
+            }
+        }
+
+        if !self.materials.is_empty() {
+            writeln!(w, "Materials:")?;
+            for ((mesh, slot), material) in &self.materials {
+                match material {
+                    Material::Original => writeln!(w, "    {mesh}[{slot}] = (original)")?,
+                    Material::Asset(path) => writeln!(w, "    {mesh}[{slot}] = {path}")?,
+                }
+            }
+        }
+
+        if !self.objects.is_empty() {
+            writeln!(w, "Objects:")?;
+            for (object, active) in &self.objects {
+                let activity = if *active { "active" } else { "inactive" };
+                writeln!(w, "    {object}: {activity}")?;
+            }
+        }
+
+        writeln!(w, "Parameters:")?;
+        for (name, value) in &self.parameters {
+            writeln!(w, "    {name} = {value}")?;
+        }
+        Ok(())
+    }
+}
+
+/// Sets the parameters in order and evaluates the layers that `codegen` generates.
+/// The layers settle after each assignment, so drivers see every intermediate value.
+pub fn simulate(
+    descriptor: &ValidatedDescriptor,
+    assignments: &[Assignment],
+) -> Result<Simulation, SimulationError> {
+    let animator = Animator::new(descriptor);
+    let mut simulator = Simulator::new(&animator);
+    simulator
+        .settle()
+        .map_err(|_| SimulationError::NotSettled("the defaults".into()))?;
+    for assignment in assignments {
+        simulator.assign(assignment)?;
+        simulator.settle().map_err(|_| {
+            SimulationError::NotSettled(format!("{}={}", assignment.name, assignment.value))
+        })?;
+    }
+    Ok(simulator.result())
+}
+
+/// Runs the layers frame by frame.
+#[derive(Debug, Clone)]
+struct Simulator<'a> {
+    animator: &'a Animator,
+    parameters: Vec<(String, ParameterValue)>,
+
+    /// Index of the current state of each layer.
+    states: Vec<usize>,
+
+    /// Properties keep their values while no state animates them (WriteDefaultsOff).
+    animated: AnimatedValues,
+}
+
+/// Last animated values of properties.
+#[derive(Debug, Clone, Default)]
+struct AnimatedValues {
+    blend_shapes: BTreeMap<String, BTreeMap<String, f64>>,
+    materials: BTreeMap<(String, usize), Material>,
+    objects: BTreeMap<String, bool>,
+}
+
+impl<'a> Simulator<'a> {
+    /// Enters the default states with Expression Parameter defaults. Other parameters are zero.
+    fn new(animator: &'a Animator) -> Simulator<'a> {
+        let mut parameters: Vec<(String, ParameterValue)> = vec![];
+        for parameter in &animator.parameters {
+            let value = ParameterValue::from_default(parameter.kind, parameter.settings.default);
+            parameters.push((parameter.name.clone(), value));
+        }
+        for parameter in animator.layers.iter().flat_map(|l| l.parameters.iter()) {
+            if !parameters.iter().any(|(n, _)| n == &parameter.name) {
+                let value = ParameterValue::from_default(parameter.kind, 0.0);
+                parameters.push((parameter.name.clone(), value));
+            }
+        }
+
+        let mut simulator = Simulator {
+            animator,
+            parameters,
+            states: vec![0; animator.layers.len()],
+            animated: AnimatedValues::default(),
+        };
+        for (i, layer) in animator.layers.iter().enumerate() {
+            simulator.enter(layer, i, 0);
+        }
+        simulator
+    }
+
+    fn assign(&mut self, assignment: &Assignment) -> Result<(), SimulationError> {
+        let Assignment { name, value } = assignment;
+        let (_, current) = self
+            .parameters
+            .iter_mut()
+            .find(|(n, _)| n == name)
+            .ok_or_else(|| SimulationError::UnknownParameter(name.clone()))?;
+        let kind = match current {
+            ParameterValue::Bool(_) => ParameterKind::Bool,
+            ParameterValue::Int(_) => ParameterKind::Int,
+            ParameterValue::Float(_) => ParameterKind::Float,
+        };
+        *current = value
+            .coerce(kind)
+            .ok_or_else(|| SimulationError::TypeMismatch(name.clone(), kind, *value))?;
+        Ok(())
+    }
+
+    /// Runs frames until no transition fires.
+    fn settle(&mut self) -> Result<(), ()> {
+        for _ in 0..MAX_FRAMES {
+            let changed = self.step();
+            self.animate();
+            if !changed {
+                return Ok(());
+            }
+        }
+        Err(())
+    }
+
+    /// Evaluates the layers in order, taking at most one transition per layer.
+    /// Returns whether any transition fired.
+    fn step(&mut self) -> bool {
+        let animator = self.animator;
+        let mut changed = false;
+        for (i, layer) in animator.layers.iter().enumerate() {
+            let current = &layer.states[self.states[i]];
+            let transition = layer
+                .transitions
+                .iter()
+                .filter(|t| t.from == current.var && t.condition.is_valid())
+                .find(|t| self.satisfies(layer, &t.condition));
+            let Some(transition) = transition else {
+                continue;
+            };
+
+            // Exiting goes back to the default state through the entry.
+            let next = match &transition.to {
+                Some(to) => layer.states.iter().position(|s| &s.var == to),
+                None => Some(0),
+            };
+            if let Some(next) = next {
+                self.enter(layer, i, next);
+                changed = true;
+            }
+        }
+        changed
+    }
+
+    /// Enters the state, running its ParameterDriver.
+    fn enter(&mut self, layer: &Layer, layer_index: usize, state_index: usize) {
+        self.states[layer_index] = state_index;
+        for drive in &layer.states[state_index].drives {
+            let (name, value) = match drive {
+                ResolvedDrive::Integer { name, index } => (name, ParameterValue::Int(*index)),
+                ResolvedDrive::Bool { name, enabled } => (name, ParameterValue::Bool(*enabled)),
+            };
+            match self.parameters.iter_mut().find(|(n, _)| n == name) {
+                Some((_, current)) => *current = value,
+                None => self.parameters.push((name.clone(), value)),
+            }
+        }
+    }
+
+    fn value(&self, layer: &Layer, var: &str) -> ParameterValue {
+        layer
+            .parameter(var)
+            .and_then(|p| self.parameters.iter().find(|(n, _)| n == &p.name))
+            .map(|(_, v)| *v)
+            .unwrap_or(ParameterValue::Int(0))
+    }
+
+    fn satisfies(&self, layer: &Layer, cond: &Cond) -> bool {
+        match cond {
+            Cond::Or(clauses) => clauses.iter().any(|c| self.satisfies(layer, c)),
+            Cond::And(terms) => terms.iter().all(|t| self.satisfies(layer, t)),
+            Cond::Term(expr) => match expr {
+                Expr::IntEqual(p, v) => self.value(layer, p).as_int() == *v,
+                Expr::IntNotEqual(p, v) => self.value(layer, p).as_int() != *v,
+                Expr::IsTrue(p) => self.value(layer, p).as_bool(),
+                Expr::IsFalse(p) => !self.value(layer, p).as_bool(),
+                Expr::FloatGreaterThan(p, v) => self.value(layer, p).as_float() > *v,
+                Expr::FloatLessThan(p, v) => self.value(layer, p).as_float() < *v,
+            },
+        }
+    }
+
+    /// Applies the motions of the current states. Later layers override earlier ones.
+    fn animate(&mut self) {
+        let animator = self.animator;
+        for (layer, &state_index) in animator.layers.iter().zip(&self.states) {
+            let weighted_clips = match &layer.states[state_index].motion {
+                Motion::Clip(items) => vec![(items, 1.0)],
+                Motion::BlendTree(tree) => self.blend(layer, tree),
+            };
+            self.animated.apply(layer, &weighted_clips);
+        }
+    }
+
+    fn result(self) -> Simulation {
+        let AnimatedValues {
+            blend_shapes,
+            materials,
+            objects,
+        } = self.animated;
+        let mut simulation = Simulation {
+            states: vec![],
+            tracking: vec![],
+            blend_shapes,
+            materials,
+            objects,
+            parameters: self.parameters,
+        };
+
+        for (layer, &state_index) in self.animator.layers.iter().zip(&self.states) {
+            let state = &layer.states[state_index];
+            simulation
+                .states
+                .push((layer.name.clone(), state.name.clone()));
+        }
+
+        for target in [AnimationTarget::Eyelids, AnimationTarget::JawAndMouth] {
+            let tracking = self
+                .animator
+                .layers
+                .iter()
+                .zip(&self.states)
+                .find(|(l, _)| l.kind == LayerKind::TrackingControl(target))
+                .and_then(|(l, &s)| l.states[s].tracking);
+            simulation
+                .tracking
+                .push(tracking.unwrap_or(TrackingControl::Tracks(target)));
+        }
+        simulation
+    }
+
+    /// Weights of the blend tree children.
+    /// 2D trees use gradient band interpolation in Cartesian space for all types,
+    /// which approximates directional ones.
+    fn blend<'t>(&self, layer: &Layer, tree: &'t BlendTree) -> Vec<(&'t Vec<ClipItem>, f64)> {
+        let parameters: Vec<_> = tree
+            .parameters
+            .iter()
+            .map(|p| self.value(layer, p).as_float())
+            .collect();
+        let weights = if tree.blend_type.is_2d() {
+            let points: Vec<_> = tree
+                .children
+                .iter()
+                .map(|(position, _)| match *position {
+                    BlendTreePosition::Position(x, y) => (x, y),
+                    BlendTreePosition::Threshold(t) => (t, 0.0),
+                })
+                .collect();
+            gradient_band_weights(&points, (parameters[0], parameters[1]))
+        } else {
+            let thresholds: Vec<_> = tree
+                .children
+                .iter()
+                .map(|(position, _)| match *position {
+                    BlendTreePosition::Threshold(t) => t,
+                    BlendTreePosition::Position(x, _) => x,
+                })
+                .collect();
+            linear_weights(&thresholds, parameters[0])
+        };
+
+        tree.children
+            .iter()
+            .zip(weights)
+            .filter(|(_, w)| *w > 0.0)
+            .map(|((_, items), w)| (items, w))
+            .collect()
+    }
+}
+
+impl AnimatedValues {
+    /// Blends clips of weights. Blend shapes are averaged, and other properties follow
+    /// the heaviest clip which animates them.
+    fn apply(&mut self, layer: &Layer, weighted_clips: &[(&Vec<ClipItem>, f64)]) {
+        let mut blend_shapes: BTreeMap<(&str, &str), (f64, f64)> = BTreeMap::new();
+        let mut materials: BTreeMap<(&str, usize), (&Material, f64)> = BTreeMap::new();
+        let mut objects: Option<(bool, f64)> = None;
+        for (items, weight) in weighted_clips {
+            for item in items.iter() {
+                match item {
+                    ClipItem::BlendShape {
+                        renderer,
+                        shape,
+                        value,
+                    } => {
+                        let key = (layer.renderers[*renderer].as_str(), shape.as_str());
+                        let (sum, total) = blend_shapes.entry(key).or_insert((0.0, 0.0));
+                        *sum += value * weight;
+                        *total += weight;
+                    }
+                    ClipItem::Material {
+                        renderer,
+                        slot,
+                        material,
+                    } => {
+                        let key = (layer.renderers[*renderer].as_str(), *slot);
+                        let heaviest = materials.entry(key).or_insert((material, *weight));
+                        if *weight > heaviest.1 {
+                            *heaviest = (material, *weight);
+                        }
+                    }
+                    ClipItem::ObjectActive(active) => match objects {
+                        Some((_, w)) if w >= *weight => (),
+                        _ => objects = Some((*active, *weight)),
+                    },
+                }
+            }
+        }
+
+        for ((mesh, shape), (sum, total)) in blend_shapes {
+            let value = if total > 0.0 { sum / total } else { 0.0 };
+            self.blend_shapes
+                .entry(mesh.to_string())
+                .or_default()
+                .insert(shape.to_string(), value);
+        }
+        for ((mesh, slot), (material, _)) in materials {
+            self.materials
+                .insert((mesh.to_string(), slot), material.clone());
+        }
+        if let Some((active, _)) = objects {
+            for object in &layer.objects {
+                self.objects.insert(object.clone(), active);
+            }
+        }
+    }
+}
+
+/// Weights of a 1D blend tree. The value is clamped into the thresholds.
+fn linear_weights(thresholds: &[f64], value: f64) -> Vec<f64> {
+    let mut weights = vec![0.0; thresholds.len()];
+    let mut order: Vec<_> = (0..thresholds.len()).collect();
+    order.sort_by(|&a, &b| thresholds[a].total_cmp(&thresholds[b]));
+    let (Some(&first), Some(&last)) = (order.first(), order.last()) else {
+        return weights;
+    };
+
+    if value <= thresholds[first] {
+        weights[first] = 1.0;
+    } else if value >= thresholds[last] {
+        weights[last] = 1.0;
+    } else {
+        for pair in order.windows(2) {
+            let (lower, upper) = (pair[0], pair[1]);
+            if (thresholds[lower]..=thresholds[upper]).contains(&value) {
+                let t = (value - thresholds[lower]) / (thresholds[upper] - thresholds[lower]);
+                weights[lower] = 1.0 - t;
+                weights[upper] = t;
+                break;
+            }
+        }
+    }
+    weights
+}
+
+/// Weights of a 2D blend tree by gradient band interpolation.
+fn gradient_band_weights(points: &[(f64, f64)], (x, y): (f64, f64)) -> Vec<f64> {
+    let mut weights: Vec<_> = points
+        .iter()
+        .enumerate()
+        .map(|(i, &(xi, yi))| {
+            points
+                .iter()
+                .enumerate()
+                .filter(|&(j, &(xj, yj))| j != i && (xj, yj) != (xi, yi))
+                .map(|(_, &(xj, yj))| {
+                    let (dx, dy) = (xj - xi, yj - yi);
+                    let projected = ((x - xi) * dx + (y - yi) * dy) / (dx * dx + dy * dy);
+                    (1.0 - projected).max(0.0)
+                })
+                .fold(1.0, f64::min)
+        })
+        .collect();
+
+    let total: f64 = weights.iter().sum();
+    if total > 0.0 {
+        weights.iter_mut().for_each(|w| *w /= total);
+    }
+    weights
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{gradient_band_weights, linear_weights, simulate, Assignment, ParameterValue};
+    use crate::descriptor::{
+        Descriptor, Drive, Driver, DriverOption, Gesture, GestureHand, GestureMap, GestureMapping,
+        GesturePriority, ShapeKeyGroup, ShapeKeyOption, ShapeKeySwitch, Span, ValidatedDescriptor,
+    };
+
+    /// Eyelids group whose `eyelids_close` is mapped to the fist of the left hand.
+    fn gesture_descriptor(weighted: bool) -> ValidatedDescriptor {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .option(ShapeKeyOption::new("eyelids_smile"))
+                    .option(ShapeKeyOption::new("eyelids_close"))
+                    .build(),
+            )
+            .gesture_map(GestureMap {
+                name: "HandExpression".into(),
+                span: Span::default(),
+                group: "Eyelids".into(),
+                priority: GesturePriority::Left,
+                mappings: vec![GestureMapping {
+                    gesture: Gesture::Fist,
+                    hand: GestureHand::Left,
+                    label: "eyelids_close".into(),
+                    weighted,
+                    span: Span::default(),
+                }],
+            })
+            .build();
+        ValidatedDescriptor::new(descriptor).unwrap()
+    }
+
+    fn assert_weights(actual: &[f64], expected: &[f64]) {
+        assert_eq!(actual.len(), expected.len(), "{actual:?}");
+        for (a, e) in actual.iter().zip(expected) {
+            assert!((a - e).abs() < 1e-9, "{actual:?} != {expected:?}");
+        }
+    }
+
+    #[test]
+    fn linear_weights_interpolate_neighbors() {
+        assert_weights(&linear_weights(&[0.0, 1.0], 0.25), &[0.75, 0.25]);
+        assert_weights(&linear_weights(&[0.0, 0.5, 1.0], 0.75), &[0.0, 0.5, 0.5]);
+        assert_weights(&linear_weights(&[1.0, 0.0], 0.25), &[0.25, 0.75]);
+        assert_weights(&linear_weights(&[0.0, 1.0], 1.0), &[0.0, 1.0]);
+    }
+
+    #[test]
+    fn linear_weights_clamp_outside_thresholds() {
+        assert_weights(&linear_weights(&[0.0, 1.0], -1.0), &[1.0, 0.0]);
+        assert_weights(&linear_weights(&[0.0, 1.0], 2.0), &[0.0, 1.0]);
+        assert!(linear_weights(&[], 0.5).is_empty());
+    }
+
+    #[test]
+    fn gradient_band_weights_match_points() {
+        let points = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)];
+        assert_weights(
+            &gradient_band_weights(&points, (0.0, 0.0)),
+            &[1.0, 0.0, 0.0, 0.0, 0.0],
+        );
+        assert_weights(
+            &gradient_band_weights(&points, (1.0, 0.0)),
+            &[0.0, 1.0, 0.0, 0.0, 0.0],
+        );
+        assert_weights(
+            &gradient_band_weights(&points, (0.0, -1.0)),
+            &[0.0, 0.0, 0.0, 0.0, 1.0],
+        );
+    }
+
+    #[test]
+    fn gradient_band_weights_are_normalized() {
+        let points = [(0.0, 0.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0), (0.0, -1.0)];
+        let weights = gradient_band_weights(&points, (0.5, 0.5));
+        assert!((weights.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+        let third = 1.0 / 3.0;
+        assert_weights(&weights, &[third, third, third, 0.0, 0.0]);
+    }
+
+    #[test]
+    fn drivers_propagate_within_settle() {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .option(ShapeKeyOption::new("eyelids_smile"))
+                    .option(ShapeKeyOption::new("eyelids_close"))
+                    .build(),
+            )
+            .shape_switch(
+                ShapeKeySwitch::builder("Cheek", "cheek")
+                    .mesh("Face")
+                    .build(),
+            )
+            .driver(
+                Driver::builder("Expression")
+                    .option(DriverOption::new(
+                        "Smile",
+                        [
+                            Drive::Group {
+                                name: "Eyelids".into(),
+                                label: "eyelids_close".into(),
+                            },
+                            Drive::Switch {
+                                name: "Cheek".into(),
+                                enabled: true,
+                            },
+                        ],
+                    ))
+                    .build(),
+            )
+            .build();
+        let descriptor = ValidatedDescriptor::new(descriptor).unwrap();
+
+        let assignments = [Assignment::new("Expression", ParameterValue::Int(1))];
+        let simulation = simulate(&descriptor, &assignments).unwrap();
+        assert_eq!(
+            simulation.parameter("Eyelids"),
+            Some(ParameterValue::Int(2))
+        );
+        assert_eq!(
+            simulation.parameter("Cheek"),
+            Some(ParameterValue::Bool(true))
+        );
+        assert_eq!(simulation.blend_shape("Face", "eyelids_smile"), Some(0.0));
+        assert_eq!(simulation.blend_shape("Face", "eyelids_close"), Some(1.0));
+        assert_eq!(simulation.blend_shape("Face", "cheek"), Some(1.0));
+    }
+
+    #[test]
+    fn drivers_keep_bit_packed_index_in_sync() {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .bit_packed()
+                    .option(ShapeKeyOption::new("eyelids_smile"))
+                    .option(ShapeKeyOption::new("eyelids_close"))
+                    .option(ShapeKeyOption::new("eyelids_wink"))
+                    .build(),
+            )
+            .driver(
+                Driver::builder("Expression")
+                    .option(DriverOption::new(
+                        "Smile",
+                        [Drive::Group {
+                            name: "Eyelids".into(),
+                            label: "eyelids_smile".into(),
+                        }],
+                    ))
+                    .build(),
+            )
+            .build();
+        let descriptor = ValidatedDescriptor::new(descriptor).unwrap();
+
+        // Selecting the same index again must re-encode it after the driver changed the bits.
+        let assignments = [
+            Assignment::new("Eyelids", ParameterValue::Int(3)),
+            Assignment::new("Expression", ParameterValue::Int(1)),
+            Assignment::new("Expression", ParameterValue::Int(0)),
+            Assignment::new("Eyelids", ParameterValue::Int(3)),
+        ];
+        let simulation = simulate(&descriptor, &assignments).unwrap();
+        assert_eq!(
+            simulation.parameter("Eyelids"),
+            Some(ParameterValue::Int(3))
+        );
+        assert_eq!(simulation.blend_shape("Face", "eyelids_smile"), Some(0.0));
+        assert_eq!(simulation.blend_shape("Face", "eyelids_wink"), Some(1.0));
+    }
+
+    #[test]
+    fn gestures_keep_menu_selection() {
+        let descriptor = gesture_descriptor(false);
+
+        let assignments = [
+            Assignment::new("Eyelids", ParameterValue::Int(1)),
+            Assignment::new("GestureLeft", ParameterValue::Int(1)),
+        ];
+        let simulation = simulate(&descriptor, &assignments).unwrap();
+        assert_eq!(simulation.blend_shape("Face", "eyelids_smile"), Some(0.0));
+        assert_eq!(simulation.blend_shape("Face", "eyelids_close"), Some(1.0));
+
+        let assignments = [
+            Assignment::new("Eyelids", ParameterValue::Int(1)),
+            Assignment::new("GestureLeft", ParameterValue::Int(1)),
+            Assignment::new("GestureLeft", ParameterValue::Int(0)),
+        ];
+        let simulation = simulate(&descriptor, &assignments).unwrap();
+        assert_eq!(
+            simulation.parameter("Eyelids"),
+            Some(ParameterValue::Int(1))
+        );
+        assert_eq!(simulation.blend_shape("Face", "eyelids_smile"), Some(1.0));
+        assert_eq!(simulation.blend_shape("Face", "eyelids_close"), Some(0.0));
+    }
+
+    #[test]
+    fn weighted_options_blend_only_by_gestures() {
+        let descriptor = gesture_descriptor(true);
+
+        let assignments = [Assignment::new("Eyelids", ParameterValue::Int(2))];
+        let simulation = simulate(&descriptor, &assignments).unwrap();
+        assert_eq!(simulation.blend_shape("Face", "eyelids_close"), Some(1.0));
+
+        let assignments = [
+            Assignment::new("GestureLeft", ParameterValue::Int(1)),
+            Assignment::new("GestureLeftWeight", ParameterValue::Float(0.5)),
+        ];
+        let simulation = simulate(&descriptor, &assignments).unwrap();
+        assert_eq!(simulation.blend_shape("Face", "eyelids_close"), Some(0.5));
+    }
+
+    #[test]
+    fn unresolved_names_are_not_simulated() {
+        let descriptor = Descriptor::builder("Avatar")
+            .gesture_map(GestureMap {
+                name: "HandExpression".into(),
+                span: Span::default(),
+                group: "Eyelids".into(),
+                priority: GesturePriority::Left,
+                mappings: vec![],
+            })
+            .build();
+        assert!(ValidatedDescriptor::new(descriptor).is_err());
+    }
+}
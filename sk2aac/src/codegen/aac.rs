@@ -1,22 +1,33 @@
 use crate::{
-    codegen::CodeWriter,
+    animator::{
+        AnimationTarget, Animator, BlendTree, BlendTreePosition, ClipItem, Cond, Expr,
+        ExpressionParameter, Layer, LayerKind, LayerParameter, Material, Motion, ParameterKind,
+        State, TrackingControl, Transition,
+    },
+    codegen::{
+        literal::{CsEscaped, CsString},
+        CodeWriter,
+    },
     descriptor::{
-        Descriptor, Driver, ResolvedDrive, ResolvedDriver, ShapeKeyGroup, ShapeKeySwitch,
+        to_identifier, Descriptor, ParameterSettings, ResolvedDrive, ValidatedDescriptor,
+        MENU_MAX_CONTROLS,
     },
 };
 
 use std::{
-    collections::HashMap,
     io::{prelude::*, Result as IoResult},
     iter::{once, repeat, zip},
 };
 
-const ALIGN_UNIT: usize = 8;
+const MENU_NEXT_PAGE_NAME: &str = "Next";
 
 /// Reads the descriptor and generates AAC code.
-pub fn write_descriptor_code<W: Write>(writer: &mut W, descriptor: Descriptor) -> IoResult<String> {
+pub fn write_descriptor_code<W: Write>(
+    writer: &mut W,
+    descriptor: ValidatedDescriptor,
+) -> IoResult<String> {
     let mut writer = CodeWriter::new(writer, 4);
-    let class_name = format!("SK2AACGenerator_{}", descriptor.name);
+    let class_name = format!("SK2AACGenerator_{}", to_identifier(&descriptor.name));
 
     Preamble.write_into(&mut writer)?;
     writer.write_empty()?;
@@ -32,29 +43,6 @@ trait AacObject {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()>;
 }
 
-/// Tracking layer.
-#[derive(Debug, Clone, Copy)]
-enum AnimationTarget {
-    Eyelids,
-    JawAndMouth,
-}
-
-impl AnimationTarget {
-    fn tracking_element(&self) -> &str {
-        match self {
-            AnimationTarget::Eyelids => "TrackingElement.Eyes",
-            AnimationTarget::JawAndMouth => "TrackingElement.Mouth",
-        }
-    }
-
-    fn displayed_name(&self) -> &str {
-        match self {
-            AnimationTarget::Eyelids => "Eyes",
-            AnimationTarget::JawAndMouth => "Mouth",
-        }
-    }
-}
-
 #[derive(Debug, Clone)]
 struct Preamble;
 
@@ -66,6 +54,7 @@ impl AacObject for Preamble {
             cw.write(r#"using UnityEditor;"#)?;
             cw.write(r#"using UnityEditor.Animations;"#)?;
             cw.write(r#"using VRC.SDK3.Avatars.Components;"#)?;
+            cw.write(r#"using VRC.SDK3.Avatars.ScriptableObjects;"#)?;
             cw.write(r#"using static AnimatorAsCode.V0.AacFlState;"#)?;
             cw.write(r#"using AnimatorAsCodeFramework.Examples;"#)
         })
@@ -108,11 +97,11 @@ impl AacObject for CustomEditorClass {
 #[derive(Debug, Clone)]
 struct BehaviourClass {
     class_name: String,
-    descriptor: Descriptor,
+    descriptor: ValidatedDescriptor,
 }
 
 impl BehaviourClass {
-    fn new(class_name: impl Into<String>, descriptor: Descriptor) -> Self {
+    fn new(class_name: impl Into<String>, descriptor: ValidatedDescriptor) -> Self {
         BehaviourClass {
             class_name: class_name.into(),
             descriptor,
@@ -127,11 +116,9 @@ impl AacObject for BehaviourClass {
             descriptor,
         } = self;
 
-        let resolved_drivers: Vec<_> = descriptor
-            .drivers
-            .iter()
-            .map(|d| ResolvedDriver::resolve(&descriptor, d))
-            .collect();
+        let animator = Animator::new(&descriptor);
+        let expression_parameters = ExpressionParametersMethod::new(animator.parameters);
+        let expressions_menu = ExpressionsMenuMethod::new(&descriptor);
 
         w.write(format_args!(r#"public class {class_name} : MonoBehaviour"#))?;
         w.with_block(|mut cw| {
@@ -144,290 +131,612 @@ impl AacObject for BehaviourClass {
                 cw.write(r#"var aac = AacExample.AnimatorAsCode("SK2AAC", avatarDescriptor, TargetContainer, AssetKey, AacExample.Options().WriteDefaultsOff());"#)?;
                 cw.write(r#"// var fxDefault = aac.CreateMainFxLayer();"#)?;
 
-                let eyelids_preventions = descriptor
-                    .shape_groups
-                    .iter()
-                    .filter_map(|g| {
-                        if g.common.prevent_eyelids {
-                            Some(ParameterType::Integer(g.common.name.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .chain(descriptor.shape_switches.iter().filter_map(|g| {
-                        if g.common.prevent_eyelids {
-                            Some(ParameterType::Bool(g.common.name.clone()))
-                        } else {
-                            None
-                        }
-                    }));
-                cw.write_empty()?;
-                PreventionLayer::new(AnimationTarget::Eyelids, eyelids_preventions)
-                    .write_into(&mut cw)?;
-
-                let mouth_preventions = descriptor
-                    .shape_groups
+                // Tracking control layers are omitted without parameters, but their blank lines are kept.
+                let tracking_layers = animator
+                    .layers
                     .iter()
-                    .filter_map(|g| {
-                        if g.common.prevent_mouth {
-                            Some(ParameterType::Integer(g.common.name.clone()))
-                        } else {
-                            None
-                        }
-                    })
-                    .chain(descriptor.shape_switches.iter().filter_map(|g| {
-                        if g.common.prevent_mouth {
-                            Some(ParameterType::Bool(g.common.name.clone()))
-                        } else {
-                            None
-                        }
-                    }));
-                cw.write_empty()?;
-                PreventionLayer::new(AnimationTarget::JawAndMouth, mouth_preventions)
-                    .write_into(&mut cw)?;
-
-                for switch in descriptor.shape_switches {
+                    .filter(|l| matches!(l.kind, LayerKind::TrackingControl(_)))
+                    .count();
+                for _ in tracking_layers..2 {
                     cw.write_empty()?;
-                    ShapeKeySwitchLayer::new(switch).write_into(&mut cw)?;
                 }
-                for group in descriptor.shape_groups {
+                for layer in animator.layers {
                     cw.write_empty()?;
-                    ShapeKeyGroupLayer::new(group).write_into(&mut cw)?;
-                }
-                for driver in resolved_drivers {
-                    cw.write_empty()?;
-                    DriverLayer::new(driver).write_into(&mut cw)?;
+                    layer.write_into(&mut cw)?;
                 }
+
+                cw.write_empty()?;
+                cw.write(r#"UpdateExpressionParameters(avatarDescriptor);"#)?;
+                cw.write(r#"UpdateExpressionsMenu(avatarDescriptor);"#)?;
                 Ok(())
-            })
+            })?;
+            cw.write_empty()?;
+            expression_parameters.write_into(&mut cw)?;
+            cw.write_empty()?;
+            expressions_menu.write_into(&mut cw)
         })
     }
 }
 
-/// `Blocks default animation...`
+/// `public void UpdateExpressionParameters(...)`
 #[derive(Debug, Clone)]
-struct PreventionLayer {
-    target: AnimationTarget,
-    params: Vec<ParameterType>,
-}
-
-impl PreventionLayer {
-    fn new(
-        target: AnimationTarget,
-        params: impl IntoIterator<Item = ParameterType>,
-    ) -> PreventionLayer {
-        PreventionLayer {
-            target,
-            params: params.into_iter().collect(),
-        }
+struct ExpressionParametersMethod(Vec<ExpressionParameter>);
+
+impl ExpressionParametersMethod {
+    fn new(parameters: Vec<ExpressionParameter>) -> Self {
+        ExpressionParametersMethod(parameters)
     }
 }
 
-impl AacObject for PreventionLayer {
+impl AacObject for ExpressionParametersMethod {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        if self.params.is_empty() {
-            return Ok(());
-        }
+        let parameters = self.0;
 
-        let animated_condition = Cond::Or(
-            self.params
-                .iter()
-                .filter_map(|p| match p {
-                    ParameterType::Bool(p) => Some(Cond::Term(Expr::IsTrue(format!("param{p}")))),
-                    ParameterType::Integer(p) => {
-                        Some(Cond::Term(Expr::IntNotEqual(format!("param{p}"), 0)))
-                    }
-                    _ => None,
-                })
-                .collect(),
-        );
-        let tracking_condition = Cond::And(
-            self.params
-                .iter()
-                .filter_map(|p| match p {
-                    ParameterType::Bool(p) => Some(Cond::Term(Expr::IsFalse(format!("param{p}")))),
-                    ParameterType::Integer(p) => {
-                        Some(Cond::Term(Expr::IntEqual(format!("param{p}"), 0)))
-                    }
-                    _ => None,
-                })
-                .collect(),
-        );
-
-        w.write(format_args!(r#"// Prevents Animation"#))?;
+        w.write(r#"public void UpdateExpressionParameters(VRCAvatarDescriptor avatarDescriptor)"#)?;
         w.with_block(|mut b| {
-            LayerDefinition::new(format!("{}_TrackingControl", self.target.displayed_name()))
-                .write_into(&mut b)?;
-
-            for param in self.params {
-                let var_name = match &param {
-                    ParameterType::Bool(p) => format!("param{p}"),
-                    ParameterType::Integer(p) => format!("param{p}"),
-                    _ => continue,
-                };
-                ParameterDefinition::new(param)
-                    .var_name(var_name)
-                    .write_into(&mut b)?;
-            }
+            b.write(r#"var expressionParameters = avatarDescriptor.expressionParameters;"#)?;
+            b.write(r#"if (expressionParameters == null)"#)?;
+            b.with_block(|mut b| {
+                b.write(r#"var directory = System.IO.Path.GetDirectoryName(AssetDatabase.GetAssetPath(TargetContainer));"#)?;
+                b.write(r#"expressionParameters = ScriptableObject.CreateInstance<VRCExpressionParameters>();"#)?;
+                b.write(r#"expressionParameters.parameters = new VRCExpressionParameters.Parameter[0];"#)?;
+                b.write(r#"AssetDatabase.CreateAsset(expressionParameters, $"{directory}/{AssetKey}_ExpressionParameters.asset");"#)?;
+                b.write(r#"avatarDescriptor.customExpressions = true;"#)?;
+                b.write(r#"avatarDescriptor.expressionParameters = expressionParameters;"#)
+            })?;
             b.write_empty()?;
 
-            // States
-            StateDefinition::new("tracking", "Tracking").write_into(&mut b)?;
-            StateOptions::new("tracking")
-                .tracks(self.target)
-                .write_into(&mut b)?;
-            StateDefinition::new("animated", "Animated").write_into(&mut b)?;
-            StateOptions::new("animated")
-                .animates(self.target)
-                .write_into(&mut b)?;
+            for ExpressionParameter {
+                name,
+                kind,
+                settings,
+                ..
+            } in parameters
+            {
+                let value_type = kind.name();
+                let ParameterSettings {
+                    saved,
+                    synced,
+                    default,
+                    ..
+                } = settings;
+                let name = CsString(&name);
+                b.write(format_args!(
+                    r#"SetExpressionParameter(expressionParameters, {name}, VRCExpressionParameters.ValueType.{value_type}, {default}f, {saved}, {synced});"#
+                ))?;
+            }
             b.write_empty()?;
+            b.write(r#"EditorUtility.SetDirty(expressionParameters);"#)?;
+            b.write(r#"AssetDatabase.SaveAssets();"#)
+        })?;
+        w.write_empty()?;
 
-            // Transitions
-            Transition::new("tracking", "animated")
-                .cond(animated_condition)
-                .write_into(&mut b)?;
-            Transition::new("animated", "tracking")
-                .cond(tracking_condition)
-                .write_into(&mut b)
+        w.write(r#"private static void SetExpressionParameter(VRCExpressionParameters expressionParameters, string name, VRCExpressionParameters.ValueType valueType, float defaultValue, bool saved, bool synced)"#)?;
+        w.with_block(|mut b| {
+            b.write(r#"var existing = System.Array.Find(expressionParameters.parameters, p => p.name == name);"#)?;
+            b.write(r#"if (existing == null)"#)?;
+            b.with_block(|mut b| {
+                b.write(r#"existing = new VRCExpressionParameters.Parameter { name = name, valueType = valueType };"#)?;
+                b.write(r#"ArrayUtility.Add(ref expressionParameters.parameters, existing);"#)
+            })?;
+            b.write(r#"else if (existing.valueType != valueType)"#)?;
+            b.with_block(|mut b| {
+                b.write(r#"throw new System.InvalidOperationException($"Expression Parameter \"{name}\" already exists as {existing.valueType}, but sk2aac requires {valueType}");"#)
+            })?;
+            b.write_empty()?;
+            b.write(r#"existing.defaultValue = defaultValue;"#)?;
+            b.write(r#"existing.saved = saved;"#)?;
+            b.write(r#"existing.networkSynced = synced;"#)
         })
     }
 }
 
-/// `// Shape Key Switch ...`
+/// `public void UpdateExpressionsMenu(...)`
 #[derive(Debug, Clone)]
-struct ShapeKeySwitchLayer(ShapeKeySwitch);
+struct ExpressionsMenuMethod(Vec<MenuControl>);
+
+impl ExpressionsMenuMethod {
+    fn new(descriptor: &Descriptor) -> Self {
+        let mut root = vec![];
+
+        for switch in &descriptor.shape_switches {
+            let menu = &switch.common.menu;
+            let control = MenuControl::Toggle {
+                name: menu.label_or(&switch.common.name).to_string(),
+                icon: menu.icon.clone(),
+                parameter: switch.common.name.clone(),
+                value: 1,
+            };
+            MenuControl::insert(&mut root, &menu.path, control);
+        }
+        for group in &descriptor.shape_groups {
+            let menu = &group.common.menu;
+            let options = group
+                .options
+                .iter()
+                .enumerate()
+                .map(|(i, option)| MenuControl::Toggle {
+                    name: option.label.clone(),
+                    icon: option.icon.clone(),
+                    parameter: group.common.name.clone(),
+                    value: option.index.unwrap_or(i + 1),
+                })
+                .collect();
+            let control = MenuControl::SubMenu {
+                name: menu.label_or(&group.common.name).to_string(),
+                icon: menu.icon.clone(),
+                controls: options,
+            };
+            MenuControl::insert(&mut root, &menu.path, control);
+        }
+        for slider in &descriptor.shape_sliders {
+            let menu = &slider.common.menu;
+            let control = MenuControl::RadialPuppet {
+                name: menu.label_or(&slider.common.name).to_string(),
+                icon: menu.icon.clone(),
+                parameter: slider.common.name.clone(),
+            };
+            MenuControl::insert(&mut root, &menu.path, control);
+        }
+        for puppet in &descriptor.shape_puppets {
+            let menu = &puppet.common.menu;
+            let control = MenuControl::TwoAxisPuppet {
+                name: menu.label_or(&puppet.common.name).to_string(),
+                icon: menu.icon.clone(),
+                parameter_x: puppet.parameter_x.clone(),
+                parameter_y: puppet.parameter_y.clone(),
+            };
+            MenuControl::insert(&mut root, &menu.path, control);
+        }
+        for toggle in &descriptor.object_toggles {
+            let menu = &toggle.menu;
+            let control = MenuControl::Toggle {
+                name: menu.label_or(&toggle.name).to_string(),
+                icon: menu.icon.clone(),
+                parameter: toggle.name.clone(),
+                value: 1,
+            };
+            MenuControl::insert(&mut root, &menu.path, control);
+        }
+        for driver in &descriptor.drivers {
+            let menu = &driver.menu;
+            let options = driver
+                .options
+                .iter()
+                .enumerate()
+                .map(|(i, option)| MenuControl::Toggle {
+                    name: option.label.clone(),
+                    icon: option.icon.clone(),
+                    parameter: driver.name.clone(),
+                    value: i + 1,
+                })
+                .collect();
+            let control = MenuControl::SubMenu {
+                name: menu.label_or(&driver.name).to_string(),
+                icon: menu.icon.clone(),
+                controls: options,
+            };
+            MenuControl::insert(&mut root, &menu.path, control);
+        }
 
-impl ShapeKeySwitchLayer {
-    fn new(switch: ShapeKeySwitch) -> Self {
-        ShapeKeySwitchLayer(switch)
+        // Root menu may contain other controls, so only sub-menus are paginated.
+        // The number of generated root controls is checked by validation.
+        ExpressionsMenuMethod(root.into_iter().map(MenuControl::paginated).collect())
+    }
+
+    fn write_controls<W: Write>(
+        w: &mut CodeWriter<W>,
+        menu_var: &str,
+        asset_suffix: &str,
+        controls: Vec<MenuControl>,
+        menu_count: &mut usize,
+    ) -> IoResult<()> {
+        for control in controls {
+            match control {
+                MenuControl::Toggle {
+                    name,
+                    icon,
+                    parameter,
+                    value,
+                } => {
+                    let icon = MenuControl::icon_expr(icon.as_deref());
+                    let (name, parameter) = (CsString(&name), CsString(&parameter));
+                    w.write(format_args!(
+                        r#"SetMenuControl({menu_var}, NewToggle({name}, {icon}, {parameter}, {value}));"#
+                    ))?;
+                }
+                MenuControl::RadialPuppet {
+                    name,
+                    icon,
+                    parameter,
+                } => {
+                    let icon = MenuControl::icon_expr(icon.as_deref());
+                    let (name, parameter) = (CsString(&name), CsString(&parameter));
+                    w.write(format_args!(
+                        r#"SetMenuControl({menu_var}, NewRadialPuppet({name}, {icon}, {parameter}));"#
+                    ))?;
+                }
+                MenuControl::TwoAxisPuppet {
+                    name,
+                    icon,
+                    parameter_x,
+                    parameter_y,
+                } => {
+                    let icon = MenuControl::icon_expr(icon.as_deref());
+                    let name = CsString(&name);
+                    let (parameter_x, parameter_y) =
+                        (CsString(&parameter_x), CsString(&parameter_y));
+                    w.write(format_args!(
+                        r#"SetMenuControl({menu_var}, NewTwoAxisPuppet({name}, {icon}, {parameter_x}, {parameter_y}));"#
+                    ))?;
+                }
+                MenuControl::SubMenu {
+                    name,
+                    icon,
+                    controls,
+                } => {
+                    *menu_count += 1;
+                    let sub_var = format!("menu{menu_count}");
+                    let sub_suffix = format!("{asset_suffix}_{name}");
+                    let icon = MenuControl::icon_expr(icon.as_deref());
+                    let name = CsString(&name);
+                    let asset_suffix = CsEscaped::interpolated(&sub_suffix);
+                    w.write(format_args!(
+                        r#"var {sub_var} = GetOrCreateSubMenu({menu_var}, {name}, {icon}, $"{{directory}}/{{AssetKey}}_Menu{asset_suffix}.asset");"#
+                    ))?;
+                    Self::write_controls(w, &sub_var, &sub_suffix, controls, menu_count)?;
+                }
+            }
+        }
+        Ok(())
     }
 }
 
-impl AacObject for ShapeKeySwitchLayer {
+impl AacObject for ExpressionsMenuMethod {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let switch = self.0;
+        let controls = self.0;
 
-        w.write(format_args!(
-            r#"// Shape Key Switch "{}""#,
-            switch.common.name
-        ))?;
+        w.write(r#"public void UpdateExpressionsMenu(VRCAvatarDescriptor avatarDescriptor)"#)?;
         w.with_block(|mut b| {
-            LayerDefinition::new(format!("{}", switch.common.name)).write_into(&mut b)?;
-            RendererFetch::new(switch.common.mesh).write_into(&mut b)?;
-            ParameterDefinition::bool(switch.common.name).write_into(&mut b)?;
+            b.write(r#"var directory = System.IO.Path.GetDirectoryName(AssetDatabase.GetAssetPath(TargetContainer));"#)?;
+            b.write(r#"var expressionsMenu = avatarDescriptor.expressionsMenu;"#)?;
+            b.write(r#"if (expressionsMenu == null)"#)?;
+            b.with_block(|mut b| {
+                b.write(r#"expressionsMenu = ScriptableObject.CreateInstance<VRCExpressionsMenu>();"#)?;
+                b.write(r#"AssetDatabase.CreateAsset(expressionsMenu, $"{directory}/{AssetKey}_Menu.asset");"#)?;
+                b.write(r#"avatarDescriptor.customExpressions = true;"#)?;
+                b.write(r#"avatarDescriptor.expressionsMenu = expressionsMenu;"#)
+            })?;
             b.write_empty()?;
 
-            // States
-            StateDefinition::new("disabled", "false: Disabled")
-                .blend_shapes([(switch.shape.clone(), switch.disabled_value.get())])
-                .write_into(&mut b)?;
-            StateDefinition::new("enabled", "true: Enabled")
-                .blend_shapes([(switch.shape.clone(), switch.enabled_value.get())])
-                .write_into(&mut b)?;
+            let mut menu_count = 0;
+            Self::write_controls(&mut b, "expressionsMenu", "", controls, &mut menu_count)?;
             b.write_empty()?;
+            b.write(r#"AssetDatabase.SaveAssets();"#)
+        })?;
+        w.write_empty()?;
 
-            // Transitions
-            Transition::new("disabled", "enabled")
-                .cond(Cond::Term(Expr::IsTrue(
-                    ParameterDefinition::DEFAULT_VARNAME.into(),
-                )))
-                .write_into(&mut b)?;
-            Transition::new("enabled", "disabled")
-                .cond(Cond::Term(Expr::IsFalse(
-                    ParameterDefinition::DEFAULT_VARNAME.into(),
-                )))
-                .write_into(&mut b)
+        w.write(r#"private static VRCExpressionsMenu GetOrCreateSubMenu(VRCExpressionsMenu parent, string name, Texture2D icon, string assetPath)"#)?;
+        w.with_block(|mut b| {
+            b.write(r#"var existing = parent.controls.Find(c => c.name == name && c.type == VRCExpressionsMenu.Control.ControlType.SubMenu);"#)?;
+            b.write(r#"if (existing != null && existing.subMenu != null)"#)?;
+            b.with_block(|mut b| {
+                b.write(r#"if (icon != null) existing.icon = icon;"#)?;
+                b.write(r#"EditorUtility.SetDirty(parent);"#)?;
+                b.write(r#"return existing.subMenu;"#)
+            })?;
+            b.write_empty()?;
+            b.write(r#"var subMenu = ScriptableObject.CreateInstance<VRCExpressionsMenu>();"#)?;
+            b.write(r#"AssetDatabase.CreateAsset(subMenu, assetPath);"#)?;
+            b.write(r#"SetMenuControl(parent, new VRCExpressionsMenu.Control { name = name, icon = icon, type = VRCExpressionsMenu.Control.ControlType.SubMenu, subMenu = subMenu });"#)?;
+            b.write(r#"return subMenu;"#)
+        })?;
+        w.write_empty()?;
+
+        w.write(r#"private static void SetMenuControl(VRCExpressionsMenu menu, VRCExpressionsMenu.Control control)"#)?;
+        w.with_block(|mut b| {
+            b.write(r#"var index = menu.controls.FindIndex(c => c.name == control.name);"#)?;
+            b.write(r#"if (index >= 0)"#)?;
+            b.with_block(|mut b| b.write(r#"menu.controls[index] = control;"#))?;
+            b.write(r#"else if (menu.controls.Count >= VRCExpressionsMenu.MAX_CONTROLS)"#)?;
+            b.with_block(|mut b| {
+                b.write(r#"throw new System.InvalidOperationException($"Expressions Menu \"{menu.name}\" has no room for \"{control.name}\"");"#)
+            })?;
+            b.write(r#"else"#)?;
+            b.with_block(|mut b| b.write(r#"menu.controls.Add(control);"#))?;
+            b.write(r#"EditorUtility.SetDirty(menu);"#)
+        })?;
+        w.write_empty()?;
+
+        w.write(r#"private static VRCExpressionsMenu.Control NewToggle(string name, Texture2D icon, string parameter, int value)"#)?;
+        w.with_block(|mut b| {
+            b.write(r#"return new VRCExpressionsMenu.Control"#)?;
+            b.write(r#"{"#)?;
+            b.write(r#"    name = name,"#)?;
+            b.write(r#"    icon = icon,"#)?;
+            b.write(r#"    type = VRCExpressionsMenu.Control.ControlType.Toggle,"#)?;
+            b.write(
+                r#"    parameter = new VRCExpressionsMenu.Control.Parameter { name = parameter },"#,
+            )?;
+            b.write(r#"    value = value,"#)?;
+            b.write(r#"};"#)
+        })?;
+        w.write_empty()?;
+
+        w.write(r#"private static VRCExpressionsMenu.Control NewRadialPuppet(string name, Texture2D icon, string parameter)"#)?;
+        w.with_block(|mut b| {
+            b.write(r#"return new VRCExpressionsMenu.Control"#)?;
+            b.write(r#"{"#)?;
+            b.write(r#"    name = name,"#)?;
+            b.write(r#"    icon = icon,"#)?;
+            b.write(r#"    type = VRCExpressionsMenu.Control.ControlType.RadialPuppet,"#)?;
+            b.write(r#"    subParameters = new[] { new VRCExpressionsMenu.Control.Parameter { name = parameter } },"#)?;
+            b.write(r#"};"#)
+        })?;
+        w.write_empty()?;
+
+        w.write(r#"private static VRCExpressionsMenu.Control NewTwoAxisPuppet(string name, Texture2D icon, string parameterX, string parameterY)"#)?;
+        w.with_block(|mut b| {
+            b.write(r#"return new VRCExpressionsMenu.Control"#)?;
+            b.write(r#"{"#)?;
+            b.write(r#"    name = name,"#)?;
+            b.write(r#"    icon = icon,"#)?;
+            b.write(r#"    type = VRCExpressionsMenu.Control.ControlType.TwoAxisPuppet,"#)?;
+            b.write(r#"    subParameters = new[]"#)?;
+            b.write(r#"    {"#)?;
+            b.write(r#"        new VRCExpressionsMenu.Control.Parameter { name = parameterX },"#)?;
+            b.write(r#"        new VRCExpressionsMenu.Control.Parameter { name = parameterY },"#)?;
+            b.write(r#"    },"#)?;
+            b.write(r#"};"#)
         })
     }
 }
 
-/// `// Shape Key Group ...`
+/// Expressions Menu control.
 #[derive(Debug, Clone)]
-struct ShapeKeyGroupLayer(ShapeKeyGroup);
+enum MenuControl {
+    Toggle {
+        name: String,
+        icon: Option<String>,
+        parameter: String,
+        value: usize,
+    },
+    RadialPuppet {
+        name: String,
+        icon: Option<String>,
+        parameter: String,
+    },
+    TwoAxisPuppet {
+        name: String,
+        icon: Option<String>,
+        parameter_x: String,
+        parameter_y: String,
+    },
+    SubMenu {
+        name: String,
+        icon: Option<String>,
+        controls: Vec<MenuControl>,
+    },
+}
 
-impl ShapeKeyGroupLayer {
-    fn new(group: ShapeKeyGroup) -> Self {
-        ShapeKeyGroupLayer(group)
+impl MenuControl {
+    /// Inserts the control into nested sub-menus, creating them if missing.
+    fn insert(controls: &mut Vec<MenuControl>, path: &[String], control: MenuControl) {
+        let (head, rest) = match path.split_first() {
+            Some(split) => split,
+            None => {
+                controls.push(control);
+                return;
+            }
+        };
+
+        let existing = controls.iter_mut().find_map(|c| match c {
+            MenuControl::SubMenu { name, controls, .. } if name == head => Some(controls),
+            _ => None,
+        });
+        match existing {
+            Some(sub_controls) => MenuControl::insert(sub_controls, rest, control),
+            None => {
+                let mut sub_controls = vec![];
+                MenuControl::insert(&mut sub_controls, rest, control);
+                controls.push(MenuControl::SubMenu {
+                    name: head.clone(),
+                    icon: None,
+                    controls: sub_controls,
+                });
+            }
+        }
+    }
+
+    /// Splits sub-menu controls into chained pages so that each menu fits VRChat's limit.
+    fn paginated(self) -> MenuControl {
+        match self {
+            MenuControl::SubMenu {
+                name,
+                icon,
+                controls,
+            } => MenuControl::SubMenu {
+                name,
+                icon,
+                controls: MenuControl::paginate(controls),
+            },
+            control => control,
+        }
+    }
+
+    fn paginate(controls: Vec<MenuControl>) -> Vec<MenuControl> {
+        let mut page: Vec<_> = controls.into_iter().map(MenuControl::paginated).collect();
+        if page.len() <= MENU_MAX_CONTROLS {
+            return page;
+        }
+
+        let rest = page.split_off(MENU_MAX_CONTROLS - 1);
+        page.push(MenuControl::SubMenu {
+            name: MENU_NEXT_PAGE_NAME.to_string(),
+            icon: None,
+            controls: MenuControl::paginate(rest),
+        });
+        page
+    }
+
+    fn icon_expr(icon: Option<&str>) -> String {
+        match icon {
+            Some(path) => format!(
+                r#"AssetDatabase.LoadAssetAtPath<Texture2D>({})"#,
+                CsString(path)
+            ),
+            None => "null".to_string(),
+        }
     }
 }
 
-impl AacObject for ShapeKeyGroupLayer {
+impl AacObject for Layer {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let group = self.0;
-
-        let default_values: HashMap<_, _> = group
-            .defaults
-            .into_iter()
-            .map(|d| (d.shape, d.value.get()))
-            .collect();
-        let mut drive_names: Vec<_> = group
-            .options
-            .iter()
-            .map(|o| o.shapes.iter())
-            .flatten()
-            .map(|d| d.shape.clone())
-            .collect();
-        drive_names.sort();
-        drive_names.dedup();
-        let default_drives = drive_names.into_iter().map(|n| {
-            let value = default_values.get(&n).copied().unwrap_or(0.0);
-            (n, value)
-        });
+        let name = CsString(&self.name);
+        match self.kind {
+            LayerKind::TrackingControl(_) => w.write(r#"// Prevents Animation"#)?,
+            // Groups have been commented as switches.
+            LayerKind::ShapeKeySwitch | LayerKind::ShapeKeyGroup => {
+                w.write(format_args!(r#"// Shape Key Switch {name}"#))?
+            }
+            LayerKind::ShapeKeySlider => w.write(format_args!(r#"// Shape Key Slider {name}"#))?,
+            LayerKind::ShapeKeyPuppet => w.write(format_args!(r#"// Shape Key Puppet {name}"#))?,
+            LayerKind::ObjectToggle => w.write(format_args!(r#"// Object Toggle {name}"#))?,
+            LayerKind::Driver => w.write(format_args!(r#"// Driver {name}"#))?,
+            LayerKind::BitEncoder => w.write(format_args!(r#"// Bit Encoder {name}"#))?,
+        }
 
-        w.write(format_args!(
-            r#"// Shape Key Switch "{}""#,
-            group.common.name
-        ))?;
         w.with_block(|mut b| {
-            LayerDefinition::new(format!("{}", group.common.name)).write_into(&mut b)?;
-            RendererFetch::new(group.common.mesh).write_into(&mut b)?;
-            ParameterDefinition::integer(group.common.name).write_into(&mut b)?;
-            b.write_empty()?;
+            LayerDefinition::new(self.name.clone()).write_into(&mut b)?;
+            RendererFetch::new(&self.renderers).write_into(&mut b)?;
+            if !self.objects.is_empty() {
+                ObjectFetch::new(self.objects.clone()).write_into(&mut b)?;
+            }
+            for parameter in &self.parameters {
+                ParameterDefinition::new(parameter).write_into(&mut b)?;
+            }
 
-            StateDefinition::new("disabled", "0: Disabled")
-                .blend_shapes(default_drives)
-                .indented()
-                .write_into(&mut b)?;
+            match self.kind {
+                LayerKind::TrackingControl(_)
+                | LayerKind::ShapeKeySwitch
+                | LayerKind::ObjectToggle => {
+                    b.write_empty()?;
 
-            // TODO: Check id duplicate
-            let mut right_of = "disabled".to_string();
-            for (i, option) in group.options.into_iter().enumerate() {
-                let index = option.index.map(|i| i.get()).unwrap_or(i + 1);
+                    // States
+                    for state in &self.states {
+                        StateDefinition::new(state).write_into(&mut b)?;
+                        StateOptions::new(state).write_into(&mut b)?;
+                    }
+                    b.write_empty()?;
+
+                    // Transitions
+                    for transition in self.transitions {
+                        transition.write_into(&mut b)?;
+                    }
+                }
+                LayerKind::ShapeKeySlider | LayerKind::ShapeKeyPuppet => {
+                    b.write_empty()?;
+                    for state in &self.states {
+                        // Blend Tree
+                        if let Motion::BlendTree(tree) = &state.motion {
+                            tree.clone().write_into(&mut b)?;
+                            b.write_empty()?;
+                        }
 
-                let state_name = format!("enabled{index}");
-                let state_label = format!("{index}: {}", option.label);
-                let blend_shapes = option.shapes.into_iter().map(|d| (d.shape, d.value.get()));
+                        // State
+                        StateDefinition::new(state).write_into(&mut b)?;
+                    }
+                }
+                LayerKind::ShapeKeyGroup | LayerKind::Driver | LayerKind::BitEncoder => {
+                    let (default_state, option_states) = match self.states.split_first() {
+                        Some(states) => states,
+                        None => return Ok(()),
+                    };
+                    let position = |var: &str| self.states.iter().position(|s| s.var == var);
+                    let default_state = StateDefinition::new(default_state);
+                    if self.kind == LayerKind::ShapeKeyGroup {
+                        b.write_empty()?;
+                        default_state.indented().write_into(&mut b)?;
+                    } else {
+                        default_state.write_into(&mut b)?;
+                    }
 
-                b.write_empty()?;
+                    for state in option_states {
+                        b.write_empty()?;
 
-                // State
-                let mut statedef = StateDefinition::new(state_name.clone(), state_label)
-                    .blend_shapes(blend_shapes);
-                if i % ALIGN_UNIT == 0 {
-                    statedef = statedef.right_of(right_of);
-                    right_of = state_name.clone();
+                        // State
+                        if let Motion::BlendTree(tree) = &state.motion {
+                            tree.clone().write_into(&mut b)?;
+                        }
+                        StateDefinition::new(state).write_into(&mut b)?;
+                        StateOptions::new(state).write_into(&mut b)?;
+
+                        // Transitions follow the latter of their states, which are declared by then.
+                        let transitions = self.transitions.iter().filter(|t| {
+                            let to = t.to.as_deref().unwrap_or(&t.from);
+                            position(&t.from).max(position(to)) == position(&state.var)
+                        });
+                        for transition in transitions {
+                            transition.clone().write_into(&mut b)?;
+                        }
+                    }
                 }
-                statedef.write_into(&mut b)?;
-
-                // Transitions
-                Transition::new("disabled", state_name.clone())
-                    .cond(Cond::Term(Expr::IntEqual(
-                        ParameterDefinition::DEFAULT_VARNAME.into(),
-                        index,
-                    )))
-                    .write_into(&mut b)?;
-                Transition::exits(state_name.clone())
-                    .cond(Cond::Term(Expr::IntNotEqual(
-                        ParameterDefinition::DEFAULT_VARNAME.into(),
-                        index,
-                    )))
-                    .write_into(&mut b)?;
             }
             Ok(())
         })
     }
 }
 
+impl AacObject for BlendTree {
+    fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
+        let BlendTree {
+            var: tree_var,
+            blend_type,
+            parameters,
+            children,
+        } = self;
+
+        w.write(format_args!(r#"var {tree_var} = aac.NewBlendTreeAsRaw();"#))?;
+        w.write(format_args!(
+            r#"{tree_var}.blendType = BlendTreeType.{};"#,
+            blend_type.name()
+        ))?;
+        if blend_type.is_2d() {
+            w.write(format_args!(
+                r#"{tree_var}.blendParameter = {}.Name;"#,
+                parameters[0]
+            ))?;
+            w.write(format_args!(
+                r#"{tree_var}.blendParameterY = {}.Name;"#,
+                parameters[1]
+            ))?;
+        } else {
+            w.write(format_args!(
+                r#"{tree_var}.blendParameter = {}.Name;"#,
+                parameters[0]
+            ))?;
+            w.write(format_args!(
+                r#"{tree_var}.useAutomaticThresholds = false;"#
+            ))?;
+        }
+        for (position, clip_items) in children {
+            w.write_yield(|w| {
+                write!(w, r#"{tree_var}.AddChild(aac.NewClip()"#)?;
+                for item in &clip_items {
+                    write_clip_item(w, item)?;
+                }
+                match position {
+                    BlendTreePosition::Threshold(t) => write!(w, r#".Clip, {t:.1}f);"#),
+                    BlendTreePosition::Position(x, y) => {
+                        write!(w, r#".Clip, new Vector2({x}f, {y}f));"#)
+                    }
+                }
+            })?;
+        }
+        Ok(())
+    }
+}
+
 /// `var layer = ...`
 #[derive(Debug, Clone)]
 struct LayerDefinition(String);
@@ -440,12 +749,12 @@ impl LayerDefinition {
 
 impl AacObject for LayerDefinition {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let layer_name = self.0;
+        let layer_name = CsString(&self.0);
 
         w.write_yield(|w| {
             write!(
                 w,
-                r#"var layer = aac.CreateSupportingFxLayer("{layer_name}");"#
+                r#"var layer = aac.CreateSupportingFxLayer({layer_name});"#
             )
         })
     }
@@ -453,168 +762,144 @@ impl AacObject for LayerDefinition {
 
 /// `var renderer = ...`
 #[derive(Debug, Clone)]
-struct RendererFetch(String);
+struct RendererFetch<'a>(&'a [String]);
 
-impl RendererFetch {
-    fn new(name: impl Into<String>) -> Self {
-        RendererFetch(name.into())
+impl<'a> RendererFetch<'a> {
+    fn new(meshes: &'a [String]) -> Self {
+        RendererFetch(meshes)
+    }
+
+    fn var_name(index: usize) -> String {
+        match index {
+            0 => "renderer".into(),
+            i => format!("renderer{i}"),
+        }
     }
 }
 
-impl AacObject for RendererFetch {
+impl AacObject for RendererFetch<'_> {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let object_name = self.0;
-
-        w.write_yield(|w| {
-            write!(
-                w,
-                r#"var renderer = (SkinnedMeshRenderer) gameObject.transform.Find("{object_name}").GetComponent<SkinnedMeshRenderer>();"#
-            )
-        })
+        for (i, object_name) in self.0.iter().enumerate() {
+            let var_name = Self::var_name(i);
+            let object_name = CsString(object_name);
+            w.write_yield(|w| {
+                write!(
+                    w,
+                    r#"var {var_name} = (SkinnedMeshRenderer) gameObject.transform.Find({object_name}).GetComponent<SkinnedMeshRenderer>();"#
+                )
+            })?;
+        }
+        Ok(())
     }
 }
 
-/// `var parameter = ...`
+/// `var objects = ...`
 #[derive(Debug, Clone)]
-struct ParameterDefinition {
-    var_name: String,
-    param_type: ParameterType,
-}
-
-impl ParameterDefinition {
-    const DEFAULT_VARNAME: &'static str = "parameter";
-
-    fn new(param_type: ParameterType) -> ParameterDefinition {
-        ParameterDefinition {
-            var_name: Self::DEFAULT_VARNAME.into(),
-            param_type,
-        }
-    }
+struct ObjectFetch(Vec<String>);
 
-    fn var_name(mut self, var_name: impl Into<String>) -> ParameterDefinition {
-        self.var_name = var_name.into();
-        self
-    }
+impl ObjectFetch {
+    const VARNAME: &'static str = "objects";
 
-    fn integer(name: impl Into<String>) -> ParameterDefinition {
-        ParameterDefinition {
-            var_name: Self::DEFAULT_VARNAME.into(),
-            param_type: ParameterType::Integer(name.into()),
-        }
+    fn new(names: impl IntoIterator<Item = String>) -> Self {
+        ObjectFetch(names.into_iter().collect())
     }
+}
 
-    fn bool(name: impl Into<String>) -> ParameterDefinition {
-        ParameterDefinition {
-            var_name: Self::DEFAULT_VARNAME.into(),
-            param_type: ParameterType::Bool(name.into()),
-        }
+impl AacObject for ObjectFetch {
+    fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
+        let object_names = self.0;
+
+        w.write(format_args!(r#"var {} = new GameObject[]"#, Self::VARNAME))?;
+        w.write(r#"{"#)?;
+        w.with_indent(|mut b| {
+            for object_name in object_names {
+                let object_name = CsString(&object_name);
+                b.write(format_args!(
+                    r#"gameObject.transform.Find({object_name}).gameObject,"#
+                ))?;
+            }
+            Ok(())
+        })?;
+        w.write(r#"};"#)
     }
+}
 
-    fn integer_group(names: impl IntoIterator<Item = String>) -> ParameterDefinition {
-        ParameterDefinition {
-            var_name: Self::DEFAULT_VARNAME.into(),
-            param_type: ParameterType::IntegerGroup(names.into_iter().collect()),
-        }
-    }
+/// `var parameter = ...`
+#[derive(Debug, Clone)]
+struct ParameterDefinition<'a>(&'a LayerParameter);
 
-    fn bool_group(names: impl IntoIterator<Item = String>) -> ParameterDefinition {
-        ParameterDefinition {
-            var_name: Self::DEFAULT_VARNAME.into(),
-            param_type: ParameterType::BoolGroup(names.into_iter().collect()),
-        }
+impl<'a> ParameterDefinition<'a> {
+    fn new(parameter: &'a LayerParameter) -> Self {
+        ParameterDefinition(parameter)
     }
 }
 
-impl AacObject for ParameterDefinition {
+impl AacObject for ParameterDefinition<'_> {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let ParameterDefinition {
-            var_name,
-            param_type,
-        } = self;
-
-        match param_type {
-            ParameterType::Bool(p) => w.write(format_args!(
-                r#"var {var_name} = layer.BoolParameter("{p}");"#
-            )),
-            ParameterType::Integer(p) => w.write(format_args!(
-                r#"var {var_name} = layer.IntParameter("{p}");"#
-            )),
-            ParameterType::BoolGroup(ps) => {
-                let joined = ps.join(r#"", ""#);
-                w.write(format_args!(
-                    r#"var {var_name} = layer.BoolParameters("{joined}");"#
-                ))
+        let LayerParameter { var, name, kind } = self.0;
+        let name = CsString(name);
+        match kind {
+            ParameterKind::Bool => {
+                w.write(format_args!(r#"var {var} = layer.BoolParameter({name});"#))
+            }
+            ParameterKind::Int => {
+                w.write(format_args!(r#"var {var} = layer.IntParameter({name});"#))
             }
-            ParameterType::IntegerGroup(ps) => {
-                let joined = ps.join(r#"", ""#);
-                w.write(format_args!(
-                    r#"var {var_name} = layer.IntParameters("{joined}");"#
-                ))
+            ParameterKind::Float => {
+                w.write(format_args!(r#"var {var} = layer.FloatParameter({name});"#))
             }
         }
     }
 }
 
-#[derive(Debug, Clone)]
-enum ParameterType {
-    Bool(String),
-    Integer(String),
-    BoolGroup(Vec<String>),
-    IntegerGroup(Vec<String>),
-}
-
 /// `var state = ...`
 #[derive(Debug, Clone)]
-struct StateDefinition {
-    state_var: String,
-    state_name: String,
-    blend_shapes: Option<Vec<(String, f64)>>,
-    renderer: String,
-    right_of: Option<String>,
+struct StateDefinition<'a> {
+    state: &'a State,
     indented: bool,
 }
 
-impl StateDefinition {
-    fn new(state_var: impl Into<String>, state_name: impl Into<String>) -> Self {
+impl<'a> StateDefinition<'a> {
+    fn new(state: &'a State) -> Self {
         StateDefinition {
-            state_var: state_var.into(),
-            state_name: state_name.into(),
-            blend_shapes: None,
-            renderer: "renderer".into(),
-            right_of: None,
+            state,
             indented: false,
         }
     }
 
-    fn right_of(mut self, state_name: impl Into<String>) -> Self {
-        self.right_of = Some(state_name.into());
-        self
-    }
-
     fn indented(mut self) -> Self {
         self.indented = true;
         self
     }
-
-    fn blend_shapes(mut self, items: impl IntoIterator<Item = (String, f64)>) -> Self {
-        self.blend_shapes = Some(items.into_iter().collect());
-        self
-    }
 }
 
-impl AacObject for StateDefinition {
+impl AacObject for StateDefinition<'_> {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let StateDefinition {
-            state_var,
-            state_name,
-            renderer,
+        let State {
+            var: state_var,
+            name: state_name,
+            motion,
+            right_of,
             ..
-        } = self;
-
+        } = self.state;
+        let state_name = CsString(state_name);
+
+        let clip_items = match motion {
+            Motion::BlendTree(tree) => {
+                return w.write_yield(|w| {
+                    write!(w, r#"var {state_var} = layer.NewState({state_name})"#)?;
+                    if let Some(ro) = right_of {
+                        write!(w, r#".RightOf({ro})"#)?;
+                    }
+                    write!(w, r#".WithAnimation({});"#, tree.var)
+                });
+            }
+            Motion::Clip(clip_items) => clip_items,
+        };
         if self.indented {
             w.write_yield(|w| {
-                write!(w, r#"var {state_var} = layer.NewState("{state_name}")"#)?;
-                if let Some(ro) = self.right_of {
+                write!(w, r#"var {state_var} = layer.NewState({state_name})"#)?;
+                if let Some(ro) = right_of {
                     write!(w, r#".RightOf({ro})"#)?;
                 }
                 write!(w, r#".WithAnimation("#)
@@ -622,33 +907,24 @@ impl AacObject for StateDefinition {
 
             w.with_indent(|mut b| {
                 b.write(r#"aac.NewClip()"#)?;
-                if let Some(blend_shapes) = self.blend_shapes {
-                    b.with_indent(|mut b| {
-                        for (name, value) in blend_shapes {
-                            let value = value * 100.0;
-                            b.write(format_args!(
-                                r#".BlendShape({renderer}, "{name}", {value:.1}f)"#
-                            ))?;
-                        }
-                        Ok(())
-                    })?;
-                }
-                Ok(())
+                b.with_indent(|mut b| {
+                    for item in clip_items {
+                        b.write_yield(|w| write_clip_item(w, item))?;
+                    }
+                    Ok(())
+                })
             })?;
 
             w.write(r#");"#)
         } else {
             w.write_yield(|w| {
-                write!(w, r#"var {state_var} = layer.NewState("{state_name}")"#)?;
-                if let Some(ro) = self.right_of {
+                write!(w, r#"var {state_var} = layer.NewState({state_name})"#)?;
+                if let Some(ro) = right_of {
                     write!(w, r#".RightOf({ro})"#)?;
                 }
                 write!(w, r#".WithAnimation(aac.NewClip()"#)?;
-                if let Some(blend_shapes) = self.blend_shapes {
-                    for (name, value) in blend_shapes {
-                        let value = value * 100.0;
-                        write!(w, r#".BlendShape({renderer}, "{name}", {value:.1}f)"#)?;
-                    }
+                for item in clip_items {
+                    write_clip_item(w, item)?;
                 }
                 write!(w, r#");"#)
             })
@@ -656,279 +932,158 @@ impl AacObject for StateDefinition {
     }
 }
 
-/// `state.Tracks/Animates()...`
-#[derive(Debug, Clone)]
-struct StateOptions {
-    state_var: String,
-    options: Vec<StateOption>,
-    driving: bool,
-}
-
-impl StateOptions {
-    fn new(state_var: impl Into<String>) -> StateOptions {
-        StateOptions {
-            state_var: state_var.into(),
-            options: vec![],
-            driving: false,
+/// Writes `.BlendShape(...)` and so on in a clip.
+fn write_clip_item<W: Write>(w: &mut W, item: &ClipItem) -> IoResult<()> {
+    match item {
+        ClipItem::BlendShape {
+            renderer,
+            shape,
+            value,
+        } => {
+            let renderer = RendererFetch::var_name(*renderer);
+            let (name, value) = (CsString(shape), value * 100.0);
+            write!(w, r#".BlendShape({renderer}, {name}, {value:.1}f)"#)
+        }
+        ClipItem::Material {
+            renderer,
+            slot,
+            material,
+        } => {
+            let renderer = RendererFetch::var_name(*renderer);
+            match material {
+                Material::Original => write!(
+                    w,
+                    r#".SwappingMaterial({renderer}, {slot}, {renderer}.sharedMaterials[{slot}])"#
+                ),
+                Material::Asset(path) => write!(
+                    w,
+                    r#".SwappingMaterial({renderer}, {slot}, AssetDatabase.LoadAssetAtPath<Material>({}))"#,
+                    CsString(path)
+                ),
+            }
+        }
+        ClipItem::ObjectActive(active) => {
+            write!(w, r#".Toggling({}, {active})"#, ObjectFetch::VARNAME)
         }
     }
+}
 
-    fn tracks(mut self, target: AnimationTarget) -> Self {
-        self.options.push(StateOption::Tracks(target));
-        self
-    }
-
-    fn animates(mut self, target: AnimationTarget) -> Self {
-        self.options.push(StateOption::Animates(target));
-        self
-    }
+/// `state.Tracks/Animates()...`
+#[derive(Debug, Clone)]
+struct StateOptions<'a>(&'a State);
 
-    fn drives(mut self, drive: ResolvedDrive) -> Self {
-        self.driving = true;
-        self.options.push(StateOption::DrivesParameter(drive));
-        self
+impl<'a> StateOptions<'a> {
+    fn new(state: &'a State) -> Self {
+        StateOptions(state)
     }
 }
 
-impl AacObject for StateOptions {
+impl AacObject for StateOptions<'_> {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        if self.options.is_empty() {
+        let State {
+            var: state_var,
+            tracking,
+            drives,
+            ..
+        } = self.0;
+        if tracking.is_none() && drives.is_empty() {
             return Ok(());
         }
 
-        let StateOptions {
-            state_var,
-            options,
-            driving,
-        } = self;
         w.write_yield(|w| {
             write!(w, r#"{state_var}"#)?;
-            if driving {
+            if !drives.is_empty() {
                 write!(w, r#".DrivingLocally()"#)?;
             }
 
-            for option in options {
-                match option {
-                    StateOption::Tracks(at) => {
-                        write!(w, r#".TrackingTracks({})"#, at.tracking_element())?
-                    }
-                    StateOption::Animates(at) => {
-                        write!(w, r#".TrackingAnimates({})"#, at.tracking_element())?
+            match tracking {
+                Some(TrackingControl::Tracks(at)) => {
+                    write!(w, r#".TrackingTracks({})"#, tracking_element(*at))?
+                }
+                Some(TrackingControl::Animates(at)) => {
+                    write!(w, r#".TrackingAnimates({})"#, tracking_element(*at))?
+                }
+                None => (),
+            }
+            for drive in drives {
+                write!(w, r#".Drives("#)?;
+                match drive {
+                    ResolvedDrive::Integer { name, index } => {
+                        write!(w, r#"layer.IntParameter({}), {index}"#, CsString(name))?;
                     }
-                    StateOption::DrivesParameter(drive) => {
-                        write!(w, r#".Drives("#)?;
-                        match drive {
-                            ResolvedDrive::Integer { name, index } => {
-                                write!(w, r#"layer.IntParameter("{name}"), {index}"#,)?;
-                            }
-                            ResolvedDrive::Bool { name, enabled } => {
-                                write!(w, r#"layer.BoolParameter("{name}"), {enabled}"#,)?;
-                            }
-                        }
-                        write!(w, r#")"#)?
+                    ResolvedDrive::Bool { name, enabled } => {
+                        write!(w, r#"layer.BoolParameter({}), {enabled}"#, CsString(name))?;
                     }
                 }
+                write!(w, r#")"#)?
             }
             write!(w, r#";"#)
         })
     }
 }
 
-#[derive(Debug, Clone)]
-enum StateOption {
-    Tracks(AnimationTarget),
-    Animates(AnimationTarget),
-    DrivesParameter(ResolvedDrive),
-}
-
-/// `state.TransitionTo()...`
-#[derive(Debug, Clone)]
-struct Transition {
-    from: Option<String>,
-    to: Option<String>,
-    condition: Option<Cond>,
-}
-
-impl Transition {
-    fn new(from: impl Into<String>, to: impl Into<String>) -> Self {
-        Transition {
-            from: Some(from.into()),
-            to: Some(to.into()),
-            condition: None,
-        }
-    }
-
-    fn exits(from: impl Into<String>) -> Self {
-        Transition {
-            from: Some(from.into()),
-            to: None,
-            condition: None,
-        }
-    }
-
-    fn cond(mut self, condition: Cond) -> Self {
-        if condition.is_valid() {
-            self.condition = Some(condition);
-        }
-        self
+fn tracking_element(target: AnimationTarget) -> &'static str {
+    match target {
+        AnimationTarget::Eyelids => "TrackingElement.Eyes",
+        AnimationTarget::JawAndMouth => "TrackingElement.Mouth",
     }
 }
 
+/// `state.TransitionTo()...`
 impl AacObject for Transition {
     fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let condition = match self.condition {
-            Some(c) => c,
-            None => return Ok(()),
-        };
+        if !self.condition.is_valid() {
+            return Ok(());
+        }
 
         w.write_yield(|w| {
-            match (self.from, self.to) {
-                (Some(f), Some(t)) => write!(w, r#"{f}.TransitionsTo({t})"#)?,
-                (Some(f), None) => write!(w, r#"{f}.Exits()"#)?,
-                _ => unreachable!("Invalid transition"),
+            match (&self.from, &self.to) {
+                (f, Some(t)) => write!(w, r#"{f}.TransitionsTo({t})"#)?,
+                (f, None) => write!(w, r#"{f}.Exits()"#)?,
             }
-            condition.write(w)?;
+            write_cond(w, &self.condition)?;
             write!(w, r#";"#)
         })
     }
 }
 
-#[derive(Debug, Clone)]
-enum Expr {
-    IntEqual(String, usize),
-    IntNotEqual(String, usize),
-    IsTrue(String),
-    IsFalse(String),
-}
-
-impl Expr {
-    fn write<W: Write>(&self, w: &mut W) -> IoResult<()> {
-        match self {
-            Expr::IntEqual(p, v) => write!(w, r#"{p}.IsEqualTo({v})"#),
-            Expr::IntNotEqual(p, v) => write!(w, r#"{p}.IsNotEqualTo({v})"#),
-            Expr::IsTrue(p) => write!(w, r#"{p}.IsTrue()"#),
-            Expr::IsFalse(p) => write!(w, r#"{p}.IsFalse()"#),
-        }
+fn write_expr<W: Write>(w: &mut W, expr: &Expr) -> IoResult<()> {
+    match expr {
+        Expr::IntEqual(p, v) => write!(w, r#"{p}.IsEqualTo({v})"#),
+        Expr::IntNotEqual(p, v) => write!(w, r#"{p}.IsNotEqualTo({v})"#),
+        Expr::IsTrue(p) => write!(w, r#"{p}.IsTrue()"#),
+        Expr::IsFalse(p) => write!(w, r#"{p}.IsFalse()"#),
+        Expr::FloatGreaterThan(p, v) => write!(w, r#"{p}.IsGreaterThan({v}f)"#),
+        Expr::FloatLessThan(p, v) => write!(w, r#"{p}.IsLessThan({v}f)"#),
     }
 }
 
-#[derive(Debug, Clone)]
-enum Cond {
-    Or(Vec<Cond>),
-    And(Vec<Cond>),
-    Term(Expr),
-}
-
-impl Cond {
-    fn is_valid(&self) -> bool {
-        match self {
-            Cond::Or(_) => self.is_valid_or(),
-            Cond::And(_) => self.is_valid_and(),
-            Cond::Term(_) => true,
-        }
-    }
-
-    fn is_valid_and(&self) -> bool {
-        match self {
-            Cond::And(terms) => terms.iter().all(|t| matches!(t, Cond::Term(_))),
-            Cond::Term(_) => true,
-            _ => false,
-        }
-    }
-
-    fn is_valid_or(&self) -> bool {
-        match self {
-            Cond::Or(terms) => terms.iter().all(|t| t.is_valid_and()),
-            _ => false,
-        }
-    }
-
-    fn write<W: Write>(&self, w: &mut W) -> IoResult<()> {
-        match self {
-            Cond::Or(and_clauses) => {
-                let or_splits = once("").chain(repeat(".Or()"));
-                for (and_clause, or) in zip(and_clauses, or_splits) {
-                    write!(w, r#"{or}"#)?;
-                    and_clause.write(w)?;
-                }
+fn write_cond<W: Write>(w: &mut W, cond: &Cond) -> IoResult<()> {
+    match cond {
+        Cond::Or(and_clauses) => {
+            let or_splits = once("").chain(repeat(".Or()"));
+            for (and_clause, or) in zip(and_clauses, or_splits) {
+                write!(w, r#"{or}"#)?;
+                write_cond(w, and_clause)?;
             }
-            Cond::And(terms) => {
-                let method_names = once("When").chain(repeat("And"));
-                for (term, method) in zip(terms, method_names) {
-                    let term = match term {
-                        Cond::Term(t) => t,
-                        _ => unreachable!("Should be validated"),
-                    };
-                    write!(w, r#".{method}("#)?;
-                    term.write(w)?;
-                    write!(w, r#")"#)?;
-                }
-            }
-            Cond::Term(t) => {
-                write!(w, r#".When("#)?;
-                t.write(w)?;
+        }
+        Cond::And(terms) => {
+            let method_names = once("When").chain(repeat("And"));
+            for (term, method) in zip(terms, method_names) {
+                let term = match term {
+                    Cond::Term(t) => t,
+                    _ => unreachable!("Should be validated"),
+                };
+                write!(w, r#".{method}("#)?;
+                write_expr(w, term)?;
                 write!(w, r#")"#)?;
             }
         }
-        Ok(())
-    }
-}
-
-/// `// Driver ...`
-struct DriverLayer(ResolvedDriver);
-
-impl DriverLayer {
-    fn new(driver: ResolvedDriver) -> DriverLayer {
-        DriverLayer(driver)
-    }
-}
-
-impl AacObject for DriverLayer {
-    fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
-        let driver = self.0;
-
-        w.write(format_args!(r#"// Driver "{}""#, driver.name))?;
-        w.with_block(|mut b| {
-            LayerDefinition::new(format!("{}", driver.name)).write_into(&mut b)?;
-            ParameterDefinition::integer(driver.name).write_into(&mut b)?;
-            StateDefinition::new("waiting", "0: Waiting").write_into(&mut b)?;
-
-            let mut right_of = "waiting".to_string();
-            for (i, option) in driver.options.into_iter().enumerate() {
-                let index = i + 1;
-
-                let state_name = format!("option{index}");
-                let state_label = format!("{index}: {}", option.label);
-
-                // State
-                b.write_empty()?;
-                let mut statedef = StateDefinition::new(state_name.clone(), state_label);
-                if i % ALIGN_UNIT == 0 {
-                    statedef = statedef.right_of(right_of);
-                    right_of = state_name.clone();
-                }
-                statedef.write_into(&mut b)?;
-                let mut state_options = StateOptions::new(state_name.clone());
-                for drive in option.drives {
-                    state_options = state_options.drives(drive);
-                }
-                state_options.write_into(&mut b)?;
-
-                // Transitions
-                Transition::new("waiting", state_name.clone())
-                    .cond(Cond::Term(Expr::IntEqual(
-                        ParameterDefinition::DEFAULT_VARNAME.into(),
-                        index,
-                    )))
-                    .write_into(&mut b)?;
-                Transition::exits(state_name.clone())
-                    .cond(Cond::Term(Expr::IntNotEqual(
-                        ParameterDefinition::DEFAULT_VARNAME.into(),
-                        index,
-                    )))
-                    .write_into(&mut b)?;
-            }
-            Ok(())
-        })
+        Cond::Term(t) => {
+            write!(w, r#".When("#)?;
+            write_expr(w, t)?;
+            write!(w, r#")"#)?;
+        }
     }
+    Ok(())
 }
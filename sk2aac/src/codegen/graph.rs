@@ -0,0 +1,312 @@
+use crate::{
+    animator::{Animator, Cond, Expr, Layer},
+    codegen::CodeWriter,
+    descriptor::{ResolvedDrive, ValidatedDescriptor},
+};
+
+use std::{
+    fmt::{Display, Formatter, Result as FmtResult},
+    io::{prelude::*, Result as IoResult},
+    str::FromStr,
+};
+
+/// Output format of state machine graphs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GraphFormat {
+    /// Graphviz DOT.
+    Dot,
+
+    /// Mermaid flowchart.
+    Mermaid,
+}
+
+impl FromStr for GraphFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "dot" => Ok(GraphFormat::Dot),
+            "mermaid" => Ok(GraphFormat::Mermaid),
+            _ => Err(format!(
+                "unknown graph format {s:?}, expected dot or mermaid"
+            )),
+        }
+    }
+}
+
+/// Writes the FX layers of the descriptor as a graph. Each layer becomes a cluster
+/// with `Entry` and `Exit` nodes, and dashed edges to the parameters its states drive.
+pub fn write_descriptor_graph<W: Write>(
+    writer: &mut W,
+    descriptor: &ValidatedDescriptor,
+    format: GraphFormat,
+) -> IoResult<()> {
+    let animator = Animator::new(descriptor);
+    let mut w = CodeWriter::new(writer, 4);
+    match format {
+        GraphFormat::Dot => {
+            w.write(format_args!("digraph {}", DotString(&descriptor.name)))?;
+            w.with_block(|mut b| {
+                b.write("node [shape=box, style=rounded];")?;
+                for (i, layer) in animator.layers.iter().enumerate() {
+                    b.write_empty()?;
+                    write_dot_layer(&mut b, i, layer)?;
+                }
+                Ok(())
+            })
+        }
+        GraphFormat::Mermaid => {
+            w.write("flowchart TB")?;
+            w.with_indent(|mut b| {
+                for (i, layer) in animator.layers.iter().enumerate() {
+                    write_mermaid_layer(&mut b, i, layer)?;
+                }
+                Ok(())
+            })
+        }
+    }
+}
+
+/// Edges of a layer with node identifiers and labels.
+struct LayerGraph {
+    states: Vec<(String, String)>,
+    transitions: Vec<(String, String, String)>,
+    parameters: Vec<(String, String)>,
+    drives: Vec<(String, String, String)>,
+}
+
+impl LayerGraph {
+    fn new(index: usize, layer: &Layer) -> LayerGraph {
+        let node = |var: &str| format!("l{index}_{var}");
+        let states = layer
+            .states
+            .iter()
+            .map(|s| (node(&s.var), s.name.clone()))
+            .collect();
+
+        let mut transitions = vec![];
+        if let Some(default_state) = layer.states.first() {
+            transitions.push((node("entry"), node(&default_state.var), String::new()));
+        }
+        for transition in &layer.transitions {
+            if !transition.condition.is_valid() {
+                continue;
+            }
+            let to = match &transition.to {
+                Some(to) => node(to),
+                None => node("exit"),
+            };
+            let label = CondLabel::new(layer, &transition.condition).to_string();
+            transitions.push((node(&transition.from), to, label));
+        }
+
+        let mut parameters: Vec<(String, String)> = vec![];
+        let mut drives = vec![];
+        for state in &layer.states {
+            for drive in &state.drives {
+                let (name, value) = match drive {
+                    ResolvedDrive::Integer { name, index } => (name, index.to_string()),
+                    ResolvedDrive::Bool { name, enabled } => (name, enabled.to_string()),
+                };
+                let parameter_node = match parameters.iter().find(|(_, n)| n == name) {
+                    Some((node, _)) => node.clone(),
+                    None => {
+                        let parameter_node = node(&format!("driven{}", parameters.len()));
+                        parameters.push((parameter_node.clone(), name.clone()));
+                        parameter_node
+                    }
+                };
+                drives.push((node(&state.var), parameter_node, format!("= {value}")));
+            }
+        }
+
+        LayerGraph {
+            states,
+            transitions,
+            parameters,
+            drives,
+        }
+    }
+}
+
+fn write_dot_layer<W: Write>(w: &mut CodeWriter<W>, index: usize, layer: &Layer) -> IoResult<()> {
+    let graph = LayerGraph::new(index, layer);
+
+    w.write(format_args!("subgraph cluster_{index}"))?;
+    w.with_block(|mut b| {
+        b.write(format_args!("label={};", DotString(&layer.name)))?;
+        b.write(format_args!(
+            r#"l{index}_entry [label="Entry", shape=circle];"#
+        ))?;
+        if layer.transitions.iter().any(|t| t.to.is_none()) {
+            b.write(format_args!(
+                r#"l{index}_exit [label="Exit", shape=doublecircle];"#
+            ))?;
+        }
+        for (node, label) in &graph.states {
+            b.write(format_args!("{node} [label={}];", DotString(label)))?;
+        }
+        for (node, name) in &graph.parameters {
+            b.write(format_args!(
+                "{node} [label={}, shape=ellipse, style=dashed];",
+                DotString(name)
+            ))?;
+        }
+        for (from, to, label) in &graph.transitions {
+            if label.is_empty() {
+                b.write(format_args!("{from} -> {to};"))?;
+            } else {
+                b.write(format_args!("{from} -> {to} [label={}];", DotString(label)))?;
+            }
+        }
+        for (from, to, label) in &graph.drives {
+            b.write(format_args!(
+                "{from} -> {to} [label={}, style=dashed];",
+                DotString(label)
+            ))?;
+        }
+        Ok(())
+    })
+}
+
+fn write_mermaid_layer<W: Write>(
+    w: &mut CodeWriter<W>,
+    index: usize,
+    layer: &Layer,
+) -> IoResult<()> {
+    let graph = LayerGraph::new(index, layer);
+
+    w.write(format_args!(
+        "subgraph l{index} [{}]",
+        MermaidString(&layer.name)
+    ))?;
+    w.with_indent(|mut b| {
+        b.write(format_args!(r#"l{index}_entry(("Entry"))"#))?;
+        if layer.transitions.iter().any(|t| t.to.is_none()) {
+            b.write(format_args!(r#"l{index}_exit((("Exit")))"#))?;
+        }
+        for (node, label) in &graph.states {
+            b.write(format_args!("{node}[{}]", MermaidString(label)))?;
+        }
+        for (node, name) in &graph.parameters {
+            b.write(format_args!("{node}[/{}/]", MermaidString(name)))?;
+        }
+        for (from, to, label) in &graph.transitions {
+            if label.is_empty() {
+                b.write(format_args!("{from} --> {to}"))?;
+            } else {
+                b.write(format_args!("{from} -->|{}| {to}", MermaidString(label)))?;
+            }
+        }
+        for (from, to, label) in &graph.drives {
+            b.write(format_args!("{from} -.->|{}| {to}", MermaidString(label)))?;
+        }
+        Ok(())
+    })?;
+    w.write("end")
+}
+
+/// Transition condition written with parameter names, e.g. `Eyelids == 2 || Cheek`.
+struct CondLabel<'a> {
+    layer: &'a Layer,
+    cond: &'a Cond,
+    nested: bool,
+}
+
+impl<'a> CondLabel<'a> {
+    fn new(layer: &'a Layer, cond: &'a Cond) -> Self {
+        CondLabel {
+            layer,
+            cond,
+            nested: false,
+        }
+    }
+
+    fn parameter(&self, var: &'a str) -> &'a str {
+        self.layer
+            .parameter(var)
+            .map(|p| p.name.as_str())
+            .unwrap_or(var)
+    }
+}
+
+impl Display for CondLabel<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.cond {
+            Cond::Or(clauses) => {
+                for (i, clause) in clauses.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" || ")?;
+                    }
+                    let clause = CondLabel {
+                        layer: self.layer,
+                        cond: clause,
+                        nested: clauses.len() > 1,
+                    };
+                    write!(f, "{clause}")?;
+                }
+                Ok(())
+            }
+            Cond::And(terms) => {
+                let parenthesized = self.nested && terms.len() > 1;
+                if parenthesized {
+                    f.write_str("(")?;
+                }
+                for (i, term) in terms.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(" && ")?;
+                    }
+                    write!(f, "{}", CondLabel::new(self.layer, term))?;
+                }
+                if parenthesized {
+                    f.write_str(")")?;
+                }
+                Ok(())
+            }
+            Cond::Term(expr) => match expr {
+                Expr::IntEqual(p, v) => write!(f, "{} == {v}", self.parameter(p)),
+                Expr::IntNotEqual(p, v) => write!(f, "{} != {v}", self.parameter(p)),
+                Expr::IsTrue(p) => write!(f, "{}", self.parameter(p)),
+                Expr::IsFalse(p) => write!(f, "!{}", self.parameter(p)),
+                Expr::FloatGreaterThan(p, v) => write!(f, "{} > {v}", self.parameter(p)),
+                Expr::FloatLessThan(p, v) => write!(f, "{} < {v}", self.parameter(p)),
+            },
+        }
+    }
+}
+
+/// Quoted DOT string.
+struct DotString<'a>(&'a str);
+
+impl Display for DotString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str(r#"\""#)?,
+                '\\' => f.write_str(r#"\\"#)?,
+                '\n' => f.write_str(r#"\n"#)?,
+                c if c.is_control() => (),
+                c => write!(f, "{c}")?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+/// Quoted Mermaid text. Special characters are written as entity codes.
+struct MermaidString<'a>(&'a str);
+
+impl Display for MermaidString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' | '#' | '|' | '<' | '>' => write!(f, "#{};", c as u32)?,
+                c if c.is_control() => f.write_str(" ")?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
@@ -0,0 +1,97 @@
+use std::fmt::{Display, Formatter, Result as FmtResult, Write};
+
+/// Quoted C# string literal, e.g. `"mouth \"o\""`.
+#[derive(Debug, Clone, Copy)]
+pub struct CsString<'a>(pub &'a str);
+
+impl Display for CsString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, r#""{}""#, CsEscaped::new(self.0))
+    }
+}
+
+/// Text escaped to be embedded in a C# string literal, without quotes.
+#[derive(Debug, Clone, Copy)]
+pub struct CsEscaped<'a> {
+    text: &'a str,
+    interpolated: bool,
+}
+
+impl<'a> CsEscaped<'a> {
+    /// Escapes for a regular string literal.
+    pub fn new(text: &'a str) -> Self {
+        CsEscaped {
+            text,
+            interpolated: false,
+        }
+    }
+
+    /// Escapes for an interpolated string literal (`$"..."`), where braces are also special.
+    pub fn interpolated(text: &'a str) -> Self {
+        CsEscaped {
+            text,
+            interpolated: true,
+        }
+    }
+}
+
+impl Display for CsEscaped<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        for c in self.text.chars() {
+            match c {
+                '"' => f.write_str(r#"\""#)?,
+                '\\' => f.write_str(r#"\\"#)?,
+                '\0' => f.write_str(r#"\0"#)?,
+                '\n' => f.write_str(r#"\n"#)?,
+                '\r' => f.write_str(r#"\r"#)?,
+                '\t' => f.write_str(r#"\t"#)?,
+                '{' | '}' if self.interpolated => {
+                    f.write_char(c)?;
+                    f.write_char(c)?;
+                }
+                // Line separators also terminate a literal in C#.
+                c if c.is_control() || c == '\u{2028}' || c == '\u{2029}' => {
+                    write!(f, r#"\u{:04X}"#, c as u32)?
+                }
+                c => f.write_char(c)?,
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CsEscaped, CsString};
+
+    #[test]
+    fn quotes_and_backslashes() {
+        assert_eq!(CsString(r#"mouth "o""#).to_string(), r#""mouth \"o\"""#);
+        assert_eq!(CsString(r"Assets\Face").to_string(), r#""Assets\\Face""#);
+        assert_eq!(CsString(r#"\""#).to_string(), r#""\\\"""#);
+    }
+
+    #[test]
+    fn line_breaks_and_controls() {
+        assert_eq!(CsString("a\nb\r\nc\td").to_string(), r#""a\nb\r\nc\td""#);
+        assert_eq!(CsString("a\0b").to_string(), r#""a\0b""#);
+        assert_eq!(CsString("a\u{1B}b").to_string(), r#""a\u001Bb""#);
+        assert_eq!(
+            CsString("a\u{2028}b\u{2029}").to_string(),
+            r#""a\u2028b\u2029""#
+        );
+    }
+
+    #[test]
+    fn braces_only_in_interpolated_strings() {
+        assert_eq!(CsEscaped::new("{Face}").to_string(), "{Face}");
+        assert_eq!(CsEscaped::interpolated("{Face}").to_string(), "{{Face}}");
+        assert_eq!(CsEscaped::interpolated(r#"}"{"#).to_string(), r#"}}\"{{"#);
+    }
+
+    #[test]
+    fn non_ascii_is_kept() {
+        assert_eq!(CsString("笑顔 é 😀").to_string(), r#""笑顔 é 😀""#);
+        assert_eq!(CsEscaped::interpolated("目{閉}").to_string(), "目{{閉}}");
+    }
+}
@@ -1,4 +1,36 @@
 mod aac;
+mod graph;
+mod literal;
+mod unity;
 mod writer;
 
-pub use self::{aac::write_descriptor_code, writer::CodeWriter};
+pub use self::{
+    aac::write_descriptor_code,
+    graph::{write_descriptor_graph, GraphFormat},
+    unity::{generate_unity_assets, validate_unity_assets, UnityAsset, UnityAssetError},
+    writer::CodeWriter,
+};
+
+use std::str::FromStr;
+
+/// Kind of the generated files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Animator As Code script, which builds the assets in the editor.
+    Aac,
+
+    /// AnimatorController and AnimationClip assets in Unity YAML.
+    Unity,
+}
+
+impl FromStr for Backend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "aac" => Ok(Backend::Aac),
+            "unity" => Ok(Backend::Unity),
+            _ => Err(format!("unknown backend {s:?}, expected aac or unity")),
+        }
+    }
+}
@@ -0,0 +1,1250 @@
+use crate::{
+    animator::{
+        AnimationTarget, Animator, BlendTree, BlendTreePosition, BlendTreeType, ClipItem, Cond,
+        Expr, ExpressionParameter, Layer, Material, Motion, ParameterKind, State, TrackingControl,
+    },
+    codegen::CodeWriter,
+    descriptor::{
+        to_identifier, Descriptor, Diagnostics, ResolvedDrive, ShapeKeyGroup, ValidatedDescriptor,
+        ValidationError,
+    },
+};
+
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fmt::{Display, Formatter, Result as FmtResult},
+    fs::read_to_string,
+    io::{prelude::*, Error as IoError, Result as IoResult},
+    iter::once,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error as ThisError;
+
+/// VRChat Avatars SDK assembly (`VRCSDK3A.dll`) which defines the scripts below.
+const SDK_GUID: &str = "67cc4cb7839cd3741b63733d5adf0442";
+
+/// File IDs of scripts in an assembly are the first 4 bytes of MD4(`"s\0\0\0"` + full class name).
+const PARAMETER_DRIVER_SCRIPT: i64 = -706344726;
+const TRACKING_CONTROL_SCRIPT: i64 = -646210727;
+const EXPRESSION_PARAMETERS_SCRIPT: i64 = -1506855854;
+
+/// File IDs of the main objects of native assets.
+const CONTROLLER_FILE_ID: i64 = 9100000;
+const CLIP_FILE_ID: i64 = 7400000;
+const MATERIAL_FILE_ID: i64 = 2100000;
+const MONO_BEHAVIOUR_FILE_ID: i64 = 11400000;
+
+/// State positions are aligned in this grid like Animator As Code.
+const GRID_X: i64 = 250;
+const GRID_Y: i64 = 70;
+
+const BASE_LAYER_NAME: &str = "Base Layer";
+
+/// Clips hold the value for a frame at 60 fps like Animator As Code.
+const CLIP_KEY_TIME: &str = "0.016666668";
+
+const FNV_OFFSET_BASIS: u64 = 0xcbf29ce484222325;
+const FNV_PRIME: u64 = 0x100000001b3;
+
+#[derive(Debug, ThisError)]
+pub enum UnityAssetError {
+    /// Swapped materials are referred to by the GUIDs in their `.meta` files.
+    #[error("failed to read {}: {1}", .0.display())]
+    MaterialMeta(PathBuf, IoError),
+
+    #[error("{} has no GUID", .0.display())]
+    MaterialGuid(PathBuf),
+
+    /// Swapped slots are restored to the original materials, which must be set in the descriptor.
+    #[error("original material of slot {1} in \"{0}\" is unknown")]
+    UnknownOriginalMaterial(String, usize),
+
+    #[error(transparent)]
+    Io(#[from] IoError),
+}
+
+/// Generated asset file.
+#[derive(Debug, Clone)]
+pub struct UnityAsset {
+    /// Path relative to the output directory.
+    pub path: PathBuf,
+
+    pub content: Vec<u8>,
+}
+
+/// Generates the FX layers as an AnimatorController with AnimationClips, and the
+/// Expression Parameters, in Unity YAML. The assets are named `SK2AAC_<AvatarName>`,
+/// and `.meta` files are also generated so that they refer to each other by stable GUIDs.
+/// GUIDs of swapped materials are read from their `.meta` files under `project_dir`, the Unity project.
+/// Expressions Menu is not generated.
+pub fn generate_unity_assets(
+    descriptor: &ValidatedDescriptor,
+    project_dir: &Path,
+) -> Result<Vec<UnityAsset>, UnityAssetError> {
+    let animator = Animator::new(descriptor);
+    let asset_name = format!("SK2AAC_{}", to_identifier(&descriptor.name));
+    let mut assets = vec![];
+
+    let mut original_materials = HashMap::new();
+    for group in &descriptor.shape_groups {
+        for (slot, original) in original_materials_of(group) {
+            let original = original.ok_or_else(|| {
+                UnityAssetError::UnknownOriginalMaterial(group.common.name.clone(), slot)
+            })?;
+            original_materials.insert((group.common.name.as_str(), slot), original);
+        }
+    }
+
+    let mut material_guids = HashMap::new();
+    let swapped = animator
+        .layers
+        .iter()
+        .flat_map(layer_clips)
+        .flat_map(|(_, items)| items)
+        .filter_map(|item| match item {
+            ClipItem::Material {
+                material: Material::Asset(path),
+                ..
+            } => Some(path.as_str()),
+            _ => None,
+        });
+    for path in swapped.chain(original_materials.values().copied()) {
+        if let Entry::Vacant(entry) = material_guids.entry(path.to_string()) {
+            entry.insert(material_guid(project_dir, path)?);
+        }
+    }
+
+    for layer in &animator.layers {
+        for (key, items) in layer_clips(layer) {
+            let path = clip_path(&asset_name, layer, &key);
+            let curves = clip_curves(layer, items, &original_materials, &material_guids);
+            let mut content = vec![];
+            write_clip(
+                &mut CodeWriter::new(&mut content, 2),
+                &clip_name(layer, &key),
+                &curves,
+            )?;
+            push_native_asset(&mut assets, path, CLIP_FILE_ID, content);
+        }
+    }
+
+    let mut content = vec![];
+    write_controller(
+        &mut CodeWriter::new(&mut content, 2),
+        &asset_name,
+        &animator.layers,
+    )?;
+    let path = format!("{asset_name}.controller");
+    push_native_asset(&mut assets, path, CONTROLLER_FILE_ID, content);
+
+    let mut content = vec![];
+    let name = format!("{asset_name}_ExpressionParameters");
+    write_expression_parameters(
+        &mut CodeWriter::new(&mut content, 2),
+        &name,
+        &animator.parameters,
+    )?;
+    push_native_asset(
+        &mut assets,
+        format!("{name}.asset"),
+        MONO_BEHAVIOUR_FILE_ID,
+        content,
+    );
+
+    Ok(assets)
+}
+
+/// Checks that the original materials of the swapped slots are set.
+/// The clips restore them, since the Unity assets cannot refer to the materials set in the avatar.
+pub fn validate_unity_assets(descriptor: &Descriptor, diagnostics: &mut Diagnostics) {
+    for (i, group) in descriptor.shape_groups.iter().enumerate() {
+        let source = descriptor.sources.shape_group(i);
+        let name = &group.common.name;
+        let originals = original_materials_of(group);
+        for (slot, original) in &originals {
+            if original.is_none() {
+                diagnostics.error(
+                    ValidationError::UnknownOriginalMaterial(name.clone(), *slot),
+                    source,
+                    group.common.span,
+                );
+            }
+        }
+        for option in &group.options {
+            for swap in &option.materials {
+                let first = originals
+                    .iter()
+                    .find_map(|(s, o)| (*s == swap.slot).then_some(*o).flatten());
+                if let (Some(original), Some(first)) = (&swap.original, first) {
+                    if original != first {
+                        diagnostics.error(
+                            ValidationError::ConflictingOriginalMaterial(name.clone(), swap.slot),
+                            source,
+                            option.span,
+                        );
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Original materials of the swapped slots, in the order of the first swap of each slot.
+/// The first `original` set to each slot is taken.
+fn original_materials_of(group: &ShapeKeyGroup) -> Vec<(usize, Option<&str>)> {
+    let mut originals: Vec<(usize, Option<&str>)> = vec![];
+    for swap in group.options.iter().flat_map(|o| &o.materials) {
+        match originals.iter_mut().find(|(s, _)| *s == swap.slot) {
+            Some((_, known @ None)) => *known = swap.original.as_deref(),
+            Some(_) => (),
+            None => originals.push((swap.slot, swap.original.as_deref())),
+        }
+    }
+    originals
+}
+
+/// Reads the GUID in the `.meta` file of the material asset.
+fn material_guid(project_dir: &Path, material: &str) -> Result<String, UnityAssetError> {
+    let path = project_dir.join(format!("{material}.meta"));
+    let meta = match read_to_string(&path) {
+        Ok(meta) => meta,
+        Err(e) => return Err(UnityAssetError::MaterialMeta(path, e)),
+    };
+    meta.lines()
+        .find_map(|l| l.strip_prefix("guid:"))
+        .map(|guid| guid.trim().to_string())
+        .filter(|guid| !guid.is_empty())
+        .ok_or(UnityAssetError::MaterialGuid(path))
+}
+
+/// Adds the asset and its `.meta` file.
+fn push_native_asset(
+    assets: &mut Vec<UnityAsset>,
+    path: String,
+    main_file_id: i64,
+    content: Vec<u8>,
+) {
+    let meta = format!(
+        "fileFormatVersion: 2\nguid: {}\nNativeFormatImporter:\n  externalObjects: {{}}\n  mainObjectFileID: {main_file_id}\n  userData: \"\"\n  assetBundleName: \"\"\n  assetBundleVariant: \"\"\n",
+        asset_guid(&path)
+    );
+    assets.push(UnityAsset {
+        path: PathBuf::from(&path),
+        content,
+    });
+    assets.push(UnityAsset {
+        path: PathBuf::from(format!("{path}.meta")),
+        content: meta.into_bytes(),
+    });
+}
+
+/// FNV-1a hash, stable across runs and platforms.
+fn fnv1a(key: &str, basis: u64) -> u64 {
+    key.bytes().fold(basis, |hash, b| {
+        (hash ^ u64::from(b)).wrapping_mul(FNV_PRIME)
+    })
+}
+
+/// File ID of an object in the controller derived from its key.
+fn file_id(key: &str) -> i64 {
+    fnv1a(key, FNV_OFFSET_BASIS) as i64
+}
+
+/// GUID of an asset derived from its path.
+fn asset_guid(path: &str) -> String {
+    format!(
+        "{:016x}{:016x}",
+        fnv1a(path, FNV_OFFSET_BASIS),
+        fnv1a(path, !FNV_OFFSET_BASIS)
+    )
+}
+
+/// Clips of a layer keyed by the state or the blend tree child.
+fn layer_clips(layer: &Layer) -> Vec<(String, &[ClipItem])> {
+    let mut clips = vec![];
+    for state in &layer.states {
+        match &state.motion {
+            Motion::Clip(items) => clips.push((state.var.clone(), items.as_slice())),
+            Motion::BlendTree(tree) => {
+                for (i, (_, items)) in tree.children.iter().enumerate() {
+                    clips.push((format!("{}_{i}", tree.var), items.as_slice()));
+                }
+            }
+        }
+    }
+    clips
+}
+
+fn clip_name(layer: &Layer, key: &str) -> String {
+    format!("{}_{key}", to_identifier(&layer.name))
+}
+
+fn clip_path(asset_name: &str, layer: &Layer, key: &str) -> String {
+    format!("{asset_name}/{}.anim", clip_name(layer, key))
+}
+
+/// Reference to the main object of another asset.
+fn asset_reference(path: &str, file_id: i64) -> String {
+    format!("{{fileID: {file_id}, guid: {}, type: 2}}", asset_guid(path))
+}
+
+/// Animated property.
+#[derive(Debug, Clone)]
+struct Curve {
+    path: String,
+    attribute: String,
+    class_id: u32,
+    value: CurveValue,
+}
+
+/// Value of a float curve, or a reference of an object reference curve.
+#[derive(Debug, Clone)]
+enum CurveValue {
+    Float(f64),
+    Object(String),
+}
+
+/// `original_materials` are keyed by the layer name and the slot.
+fn clip_curves(
+    layer: &Layer,
+    items: &[ClipItem],
+    original_materials: &HashMap<(&str, usize), &str>,
+    material_guids: &HashMap<String, String>,
+) -> Vec<Curve> {
+    let mut curves = vec![];
+    for item in items {
+        match item {
+            ClipItem::BlendShape {
+                renderer,
+                shape,
+                value,
+            } => curves.push(Curve {
+                path: layer.renderers[*renderer].clone(),
+                attribute: format!("blendShape.{shape}"),
+                class_id: 137,
+                value: CurveValue::Float(value * 100.0),
+            }),
+            ClipItem::ObjectActive(active) => {
+                curves.extend(layer.objects.iter().map(|object| Curve {
+                    path: object.clone(),
+                    attribute: "m_IsActive".into(),
+                    class_id: 1,
+                    value: CurveValue::Float(if *active { 1.0 } else { 0.0 }),
+                }))
+            }
+            ClipItem::Material {
+                renderer,
+                slot,
+                material,
+            } => {
+                let material = match material {
+                    Material::Original => original_materials[&(layer.name.as_str(), *slot)],
+                    Material::Asset(material) => material.as_str(),
+                };
+                curves.push(Curve {
+                    path: layer.renderers[*renderer].clone(),
+                    attribute: format!("m_Materials.Array.data[{slot}]"),
+                    class_id: 137,
+                    value: CurveValue::Object(format!(
+                        "{{fileID: {MATERIAL_FILE_ID}, guid: {}, type: 2}}",
+                        material_guids[material]
+                    )),
+                })
+            }
+        }
+    }
+    curves
+}
+
+fn write_header<W: Write>(w: &mut CodeWriter<W>) -> IoResult<()> {
+    w.write("%YAML 1.1")?;
+    w.write("%TAG !u! tag:unity3d.com,2011:")
+}
+
+/// Writes `--- !u!<class ID> &<file ID>` and the fields of the object.
+fn write_object<W, F>(
+    w: &mut CodeWriter<W>,
+    class_id: u32,
+    file_id: i64,
+    type_name: &str,
+    hide_flags: u32,
+    f: F,
+) -> IoResult<()>
+where
+    W: Write,
+    F: FnOnce(&mut CodeWriter<W>) -> IoResult<()>,
+{
+    w.write(format_args!("--- !u!{class_id} &{file_id}"))?;
+    w.write(format_args!("{type_name}:"))?;
+    w.with_indent(|mut b| {
+        b.write(format_args!("m_ObjectHideFlags: {hide_flags}"))?;
+        b.write("m_CorrespondingSourceObject: {fileID: 0}")?;
+        b.write("m_PrefabInstance: {fileID: 0}")?;
+        b.write("m_PrefabAsset: {fileID: 0}")?;
+        f(&mut b)
+    })
+}
+
+/// Writes a list of references, or `[]` if empty.
+fn write_references<W: Write>(w: &mut CodeWriter<W>, key: &str, ids: &[i64]) -> IoResult<()> {
+    if ids.is_empty() {
+        return w.write(format_args!("{key}: []"));
+    }
+    w.write(format_args!("{key}:"))?;
+    for id in ids {
+        w.write(format_args!("- {{fileID: {id}}}"))?;
+    }
+    Ok(())
+}
+
+fn write_clip<W: Write>(w: &mut CodeWriter<W>, name: &str, curves: &[Curve]) -> IoResult<()> {
+    let float_curves: Vec<_> = curves
+        .iter()
+        .filter_map(|c| match &c.value {
+            CurveValue::Float(value) => Some((c, *value)),
+            CurveValue::Object(_) => None,
+        })
+        .collect();
+    let object_curves: Vec<_> = curves
+        .iter()
+        .filter_map(|c| match &c.value {
+            CurveValue::Float(_) => None,
+            CurveValue::Object(reference) => Some((c, reference)),
+        })
+        .collect();
+
+    let write_binding = |b: &mut CodeWriter<W>, curve: &Curve| {
+        b.write(format_args!("attribute: {}", YamlString(&curve.attribute)))?;
+        b.write(format_args!("path: {}", YamlString(&curve.path)))?;
+        b.write(format_args!("classID: {}", curve.class_id))?;
+        b.write("script: {fileID: 0}")
+    };
+    let write_curves = |w: &mut CodeWriter<W>, key: &str| {
+        if float_curves.is_empty() {
+            return w.write(format_args!("{key}: []"));
+        }
+        w.write(format_args!("{key}:"))?;
+        for (curve, value) in &float_curves {
+            w.write("- curve:")?;
+            w.with_indent(|mut b| {
+                b.with_indent(|mut b| {
+                    b.write("serializedVersion: 2")?;
+                    b.write("m_Curve:")?;
+                    for time in ["0", CLIP_KEY_TIME] {
+                        b.write("- serializedVersion: 3")?;
+                        b.with_indent(|mut b| {
+                            b.write(format_args!("time: {time}"))?;
+                            b.write(format_args!("value: {value}"))?;
+                            b.write("inSlope: 0")?;
+                            b.write("outSlope: 0")?;
+                            b.write("tangentMode: 136")?;
+                            b.write("weightedMode: 0")?;
+                            b.write("inWeight: 0.33333334")?;
+                            b.write("outWeight: 0.33333334")
+                        })?;
+                    }
+                    b.write("m_PreInfinity: 2")?;
+                    b.write("m_PostInfinity: 2")?;
+                    b.write("m_RotationOrder: 4")
+                })?;
+                write_binding(&mut b, curve)
+            })?;
+        }
+        Ok(())
+    };
+    let write_object_curves = |w: &mut CodeWriter<W>| {
+        if object_curves.is_empty() {
+            return w.write("m_PPtrCurves: []");
+        }
+        w.write("m_PPtrCurves:")?;
+        for (curve, reference) in &object_curves {
+            w.write("- curve:")?;
+            w.with_indent(|mut b| {
+                for time in ["0", CLIP_KEY_TIME] {
+                    b.write(format_args!("- time: {time}"))?;
+                    b.with_indent(|mut b| b.write(format_args!("value: {reference}")))?;
+                }
+                write_binding(&mut b, curve)
+            })?;
+        }
+        Ok(())
+    };
+
+    // Empty clips last a second as Unity creates them.
+    let stop_time = if curves.is_empty() {
+        "1"
+    } else {
+        CLIP_KEY_TIME
+    };
+
+    write_header(w)?;
+    write_object(w, 74, CLIP_FILE_ID, "AnimationClip", 0, |b| {
+        b.write(format_args!("m_Name: {}", YamlString(name)))?;
+        b.write("serializedVersion: 7")?;
+        b.write("m_Legacy: 0")?;
+        b.write("m_Compressed: 0")?;
+        b.write("m_UseHighQualityCurve: 1")?;
+        b.write("m_RotationCurves: []")?;
+        b.write("m_CompressedRotationCurves: []")?;
+        b.write("m_EulerCurves: []")?;
+        b.write("m_PositionCurves: []")?;
+        b.write("m_ScaleCurves: []")?;
+        write_curves(b, "m_FloatCurves")?;
+        write_object_curves(b)?;
+        b.write("m_SampleRate: 60")?;
+        b.write("m_WrapMode: 0")?;
+        b.write("m_Bounds:")?;
+        b.with_indent(|mut b| {
+            b.write("m_Center: {x: 0, y: 0, z: 0}")?;
+            b.write("m_Extent: {x: 0, y: 0, z: 0}")
+        })?;
+        b.write("m_ClipBindingConstant:")?;
+        b.with_indent(|mut b| {
+            b.write("genericBindings: []")?;
+            b.write("pptrCurveMapping: []")
+        })?;
+        b.write("m_AnimationClipSettings:")?;
+        b.with_indent(|mut b| {
+            b.write("serializedVersion: 2")?;
+            b.write("m_AdditiveReferencePoseClip: {fileID: 0}")?;
+            b.write("m_AdditiveReferencePoseTime: 0")?;
+            b.write("m_StartTime: 0")?;
+            b.write(format_args!("m_StopTime: {stop_time}"))?;
+            b.write("m_OrientationOffsetY: 0")?;
+            b.write("m_Level: 0")?;
+            b.write("m_CycleOffset: 0")?;
+            b.write("m_HasAdditiveReferencePose: 0")?;
+            b.write("m_LoopTime: 0")?;
+            b.write("m_LoopBlend: 0")?;
+            b.write("m_LoopBlendOrientation: 0")?;
+            b.write("m_LoopBlendPositionY: 0")?;
+            b.write("m_LoopBlendPositionXZ: 0")?;
+            b.write("m_KeepOriginalOrientation: 0")?;
+            b.write("m_KeepOriginalPositionY: 1")?;
+            b.write("m_KeepOriginalPositionXZ: 0")?;
+            b.write("m_HeightFromFeet: 0")?;
+            b.write("m_Mirror: 0")
+        })?;
+        write_curves(b, "m_EditorCurves")?;
+        b.write("m_EulerEditorCurves: []")?;
+        b.write("m_HasGenericRootTransform: 0")?;
+        b.write("m_HasMotionFloatCurves: 0")?;
+        b.write("m_Events: []")
+    })
+}
+
+fn write_controller<W: Write>(
+    w: &mut CodeWriter<W>,
+    asset_name: &str,
+    layers: &[Layer],
+) -> IoResult<()> {
+    // Parameters used by conditions, blend trees and drives, without duplicates.
+    let mut parameters: Vec<(&str, ParameterKind)> = vec![];
+    for layer in layers {
+        let used = layer.parameters.iter().map(|p| (p.name.as_str(), p.kind));
+        let driven = layer
+            .states
+            .iter()
+            .flat_map(|s| s.drives.iter())
+            .map(|d| match d {
+                ResolvedDrive::Integer { name, .. } => (name.as_str(), ParameterKind::Int),
+                ResolvedDrive::Bool { name, .. } => (name.as_str(), ParameterKind::Bool),
+            });
+        for (name, kind) in used.chain(driven) {
+            if !parameters.iter().any(|(n, _)| *n == name) {
+                parameters.push((name, kind));
+            }
+        }
+    }
+
+    // The first layer is always fully weighted, so FX layers follow an empty base layer.
+    // Keys of layer objects contain a slash, so the base layer never collides with them.
+    let base_machine = file_id(BASE_LAYER_NAME);
+    let controller_layers: Vec<_> = layers
+        .iter()
+        .map(|layer| ControllerLayer { layer, asset_name })
+        .collect();
+    let layer_entries = once((BASE_LAYER_NAME, base_machine)).chain(
+        controller_layers
+            .iter()
+            .map(|l| (l.layer.name.as_str(), l.id("machine", ""))),
+    );
+
+    write_header(w)?;
+    write_object(w, 91, CONTROLLER_FILE_ID, "AnimatorController", 0, |b| {
+        b.write(format_args!("m_Name: {}", YamlString(asset_name)))?;
+        b.write("serializedVersion: 5")?;
+        if parameters.is_empty() {
+            b.write("m_AnimatorParameters: []")?;
+        } else {
+            b.write("m_AnimatorParameters:")?;
+        }
+        for (name, kind) in &parameters {
+            let type_id = match kind {
+                ParameterKind::Float => 1,
+                ParameterKind::Int => 3,
+                ParameterKind::Bool => 4,
+            };
+            b.write(format_args!("- m_Name: {}", YamlString(name)))?;
+            b.with_indent(|mut b| {
+                b.write(format_args!("m_Type: {type_id}"))?;
+                b.write("m_DefaultFloat: 0")?;
+                b.write("m_DefaultInt: 0")?;
+                b.write("m_DefaultBool: 0")?;
+                b.write(format_args!(
+                    "m_Controller: {{fileID: {CONTROLLER_FILE_ID}}}"
+                ))
+            })?;
+        }
+        b.write("m_AnimatorLayers:")?;
+        for (name, machine) in layer_entries {
+            b.write("- serializedVersion: 5")?;
+            b.with_indent(|mut b| {
+                b.write(format_args!("m_Name: {}", YamlString(name)))?;
+                b.write(format_args!("m_StateMachine: {{fileID: {machine}}}"))?;
+                b.write("m_Mask: {fileID: 0}")?;
+                b.write("m_Motions: []")?;
+                b.write("m_Behaviours: []")?;
+                b.write("m_BlendingMode: 0")?;
+                b.write("m_SyncedLayerIndex: -1")?;
+                b.write("m_DefaultWeight: 1")?;
+                b.write("m_IKPass: 0")?;
+                b.write("m_SyncedLayerAffectsTiming: 0")?;
+                b.write(format_args!(
+                    "m_Controller: {{fileID: {CONTROLLER_FILE_ID}}}"
+                ))
+            })?;
+        }
+        Ok(())
+    })?;
+
+    write_state_machine(w, base_machine, BASE_LAYER_NAME, &[], None)?;
+    for layer in controller_layers {
+        layer.write_into(w)?;
+    }
+    Ok(())
+}
+
+/// Writes an AnimatorStateMachine with the states and their positions in the grid.
+fn write_state_machine<W: Write>(
+    w: &mut CodeWriter<W>,
+    id: i64,
+    name: &str,
+    states: &[(i64, (i64, i64))],
+    default_state: Option<i64>,
+) -> IoResult<()> {
+    write_object(w, 1107, id, "AnimatorStateMachine", 1, |b| {
+        b.write(format_args!("m_Name: {}", YamlString(name)))?;
+        b.write("serializedVersion: 6")?;
+        if states.is_empty() {
+            b.write("m_ChildStates: []")?;
+        } else {
+            b.write("m_ChildStates:")?;
+        }
+        for (state, (x, y)) in states {
+            b.write("- serializedVersion: 1")?;
+            b.with_indent(|mut b| {
+                b.write(format_args!("m_State: {{fileID: {state}}}"))?;
+                b.write(format_args!(
+                    "m_Position: {{x: {}, y: {}, z: 0}}",
+                    (x + 1) * GRID_X,
+                    (y + 1) * GRID_Y
+                ))
+            })?;
+        }
+        b.write("m_ChildStateMachines: []")?;
+        b.write("m_AnyStateTransitions: []")?;
+        b.write("m_EntryTransitions: []")?;
+        b.write("m_StateMachineTransitions: {}")?;
+        b.write("m_StateMachineBehaviours: []")?;
+        b.write("m_AnyStatePosition: {x: 0, y: 0, z: 0}")?;
+        b.write(format_args!("m_EntryPosition: {{x: 0, y: {GRID_Y}, z: 0}}"))?;
+        b.write(format_args!("m_ExitPosition: {{x: {GRID_X}, y: 0, z: 0}}"))?;
+        b.write(format_args!(
+            "m_ParentStateMachinePosition: {{x: {}, y: 0, z: 0}}",
+            GRID_X * 2
+        ))?;
+        b.write(format_args!(
+            "m_DefaultState: {{fileID: {}}}",
+            default_state.unwrap_or(0)
+        ))
+    })
+}
+
+/// Objects of a layer in the controller.
+struct ControllerLayer<'a> {
+    layer: &'a Layer,
+    asset_name: &'a str,
+}
+
+/// One of the transitions which a condition in disjunctive normal form is split into.
+struct SplitTransition<'a> {
+    id: i64,
+    from: &'a str,
+    to: Option<&'a str>,
+    terms: Vec<&'a Expr>,
+}
+
+impl<'a> ControllerLayer<'a> {
+    /// File ID of an object keyed by its kind and variable name.
+    fn id(&self, kind: &str, var: &str) -> i64 {
+        file_id(&format!("{}/{kind}/{var}", self.layer.name))
+    }
+
+    fn clip_reference(&self, key: &str) -> String {
+        asset_reference(&clip_path(self.asset_name, self.layer, key), CLIP_FILE_ID)
+    }
+
+    /// Positions of the states in the grid. States are placed under the previous one
+    /// unless they start a new row.
+    fn state_positions(&self) -> Vec<(i64, i64)> {
+        let mut positions: Vec<(i64, i64)> = vec![];
+        for state in &self.layer.states {
+            let right_of = state.right_of.as_ref().and_then(|ro| {
+                let index = self.layer.states.iter().position(|s| &s.var == ro)?;
+                positions.get(index)
+            });
+            let position = match (right_of, positions.last()) {
+                (Some(&(x, y)), _) => (x + 1, y),
+                (None, Some(&(x, y))) => (x, y + 1),
+                (None, None) => (0, 0),
+            };
+            positions.push(position);
+        }
+        positions
+    }
+
+    /// Or-ed clauses become separate transitions.
+    fn transitions(&self) -> Vec<SplitTransition<'a>> {
+        let mut transitions = vec![];
+        let valid_transitions = self
+            .layer
+            .transitions
+            .iter()
+            .enumerate()
+            .filter(|(_, t)| t.condition.is_valid());
+        for (i, transition) in valid_transitions {
+            for (j, terms) in and_clauses(&transition.condition).into_iter().enumerate() {
+                transitions.push(SplitTransition {
+                    id: self.id("transition", &format!("{i}_{j}")),
+                    from: &transition.from,
+                    to: transition.to.as_deref(),
+                    terms,
+                });
+            }
+        }
+        transitions
+    }
+
+    fn parameter_name(&self, var: &'a str) -> &'a str {
+        self.layer
+            .parameter(var)
+            .map(|p| p.name.as_str())
+            .unwrap_or(var)
+    }
+
+    fn write_into<W: Write>(self, w: &mut CodeWriter<W>) -> IoResult<()> {
+        let positions = self.state_positions();
+        let transitions = self.transitions();
+
+        let states: Vec<_> = self
+            .layer
+            .states
+            .iter()
+            .map(|s| self.id("state", &s.var))
+            .zip(positions)
+            .collect();
+        write_state_machine(
+            w,
+            self.id("machine", ""),
+            &self.layer.name,
+            &states,
+            states.first().map(|(id, _)| *id),
+        )?;
+
+        for state in &self.layer.states {
+            let state_transitions: Vec<_> = transitions
+                .iter()
+                .filter(|t| t.from == state.var)
+                .map(|t| t.id)
+                .collect();
+            self.write_state(w, state, &state_transitions)?;
+        }
+        for transition in &transitions {
+            self.write_transition(w, transition)?;
+        }
+        for state in &self.layer.states {
+            self.write_behaviours(w, state)?;
+            if let Motion::BlendTree(tree) = &state.motion {
+                self.write_blend_tree(w, tree)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn write_state<W: Write>(
+        &self,
+        w: &mut CodeWriter<W>,
+        state: &State,
+        transitions: &[i64],
+    ) -> IoResult<()> {
+        let mut behaviours = vec![];
+        if !state.drives.is_empty() {
+            behaviours.push(self.id("driver", &state.var));
+        }
+        if state.tracking.is_some() {
+            behaviours.push(self.id("tracking", &state.var));
+        }
+        let motion = match &state.motion {
+            Motion::Clip(_) => self.clip_reference(&state.var),
+            Motion::BlendTree(tree) => format!("{{fileID: {}}}", self.id("tree", &tree.var)),
+        };
+
+        write_object(
+            w,
+            1102,
+            self.id("state", &state.var),
+            "AnimatorState",
+            1,
+            |b| {
+                b.write("serializedVersion: 6")?;
+                b.write(format_args!("m_Name: {}", YamlString(&state.name)))?;
+                b.write("m_Speed: 1")?;
+                b.write("m_CycleOffset: 0")?;
+                write_references(b, "m_Transitions", transitions)?;
+                write_references(b, "m_StateMachineBehaviours", &behaviours)?;
+                b.write("m_Position: {x: 0, y: 0, z: 0}")?;
+                b.write("m_IKOnFeet: 0")?;
+                // Same as `WriteDefaultsOff()` of Animator As Code.
+                b.write("m_WriteDefaultValues: 0")?;
+                b.write("m_Mirror: 0")?;
+                b.write("m_SpeedParameterActive: 0")?;
+                b.write("m_MirrorParameterActive: 0")?;
+                b.write("m_CycleOffsetParameterActive: 0")?;
+                b.write("m_TimeParameterActive: 0")?;
+                b.write(format_args!("m_Motion: {motion}"))?;
+                b.write(r#"m_Tag: """#)?;
+                b.write(r#"m_SpeedParameter: """#)?;
+                b.write(r#"m_MirrorParameter: """#)?;
+                b.write(r#"m_CycleOffsetParameter: """#)?;
+                b.write(r#"m_TimeParameter: """#)
+            },
+        )
+    }
+
+    fn write_transition<W: Write>(
+        &self,
+        w: &mut CodeWriter<W>,
+        transition: &SplitTransition,
+    ) -> IoResult<()> {
+        write_object(w, 1101, transition.id, "AnimatorStateTransition", 1, |b| {
+            b.write(r#"m_Name: """#)?;
+            if transition.terms.is_empty() {
+                b.write("m_Conditions: []")?;
+            } else {
+                b.write("m_Conditions:")?;
+            }
+            for term in &transition.terms {
+                let (mode, var, threshold) = match term {
+                    Expr::IsTrue(p) => (1, p, 0.0),
+                    Expr::IsFalse(p) => (2, p, 0.0),
+                    Expr::FloatGreaterThan(p, v) => (3, p, *v),
+                    Expr::FloatLessThan(p, v) => (4, p, *v),
+                    Expr::IntEqual(p, v) => (6, p, *v as f64),
+                    Expr::IntNotEqual(p, v) => (7, p, *v as f64),
+                };
+                b.write(format_args!("- m_ConditionMode: {mode}"))?;
+                b.with_indent(|mut b| {
+                    b.write(format_args!(
+                        "m_ConditionEvent: {}",
+                        YamlString(self.parameter_name(var))
+                    ))?;
+                    b.write(format_args!("m_EventTreshold: {threshold}"))
+                })?;
+            }
+            b.write("m_DstStateMachine: {fileID: 0}")?;
+            match transition.to {
+                Some(to) => {
+                    b.write(format_args!(
+                        "m_DstState: {{fileID: {}}}",
+                        self.id("state", to)
+                    ))?;
+                }
+                None => b.write("m_DstState: {fileID: 0}")?,
+            }
+            b.write("m_Solo: 0")?;
+            b.write("m_Mute: 0")?;
+            b.write(format_args!(
+                "m_IsExit: {}",
+                u8::from(transition.to.is_none())
+            ))?;
+            b.write("serializedVersion: 3")?;
+            b.write("m_TransitionDuration: 0")?;
+            b.write("m_TransitionOffset: 0")?;
+            b.write("m_ExitTime: 0")?;
+            b.write("m_HasExitTime: 0")?;
+            b.write("m_HasFixedDuration: 1")?;
+            b.write("m_InterruptionSource: 0")?;
+            b.write("m_OrderedInterruption: 1")?;
+            b.write("m_CanTransitionToSelf: 0")
+        })
+    }
+
+    /// Writes VRCAvatarParameterDriver and VRCAnimatorTrackingControl of the state.
+    fn write_behaviours<W: Write>(&self, w: &mut CodeWriter<W>, state: &State) -> IoResult<()> {
+        if !state.drives.is_empty() {
+            let id = self.id("driver", &state.var);
+            write_object(w, 114, id, "MonoBehaviour", 1, |b| {
+                write_script_header(b, PARAMETER_DRIVER_SCRIPT, "")?;
+                b.write("parameters:")?;
+                for drive in &state.drives {
+                    let (name, value) = match drive {
+                        ResolvedDrive::Integer { name, index } => (name, *index),
+                        ResolvedDrive::Bool { name, enabled } => (name, usize::from(*enabled)),
+                    };
+                    b.write("- type: 0")?;
+                    b.with_indent(|mut b| {
+                        b.write(r#"source: """#)?;
+                        b.write(format_args!("name: {}", YamlString(name)))?;
+                        b.write(format_args!("value: {value}"))?;
+                        b.write("valueMin: 0")?;
+                        b.write("valueMax: 1")?;
+                        b.write("chance: 1")
+                    })?;
+                }
+                b.write("localOnly: 1")?;
+                b.write(r#"debugString: """#)
+            })?;
+        }
+
+        if let Some(tracking) = state.tracking {
+            let (target, mode) = match tracking {
+                TrackingControl::Tracks(target) => (target, 1),
+                TrackingControl::Animates(target) => (target, 2),
+            };
+            let (eyes, mouth) = match target {
+                AnimationTarget::Eyelids => (mode, 0),
+                AnimationTarget::JawAndMouth => (0, mode),
+            };
+            let id = self.id("tracking", &state.var);
+            write_object(w, 114, id, "MonoBehaviour", 1, |b| {
+                write_script_header(b, TRACKING_CONTROL_SCRIPT, "")?;
+                for element in [
+                    "Head",
+                    "LeftHand",
+                    "RightHand",
+                    "Hip",
+                    "LeftFoot",
+                    "RightFoot",
+                    "LeftFingers",
+                    "RightFingers",
+                ] {
+                    b.write(format_args!("tracking{element}: 0"))?;
+                }
+                b.write(format_args!("trackingEyes: {eyes}"))?;
+                b.write(format_args!("trackingMouth: {mouth}"))?;
+                b.write(r#"debugString: """#)
+            })?;
+        }
+        Ok(())
+    }
+
+    fn write_blend_tree<W: Write>(&self, w: &mut CodeWriter<W>, tree: &BlendTree) -> IoResult<()> {
+        let thresholds = tree.children.iter().filter_map(|(p, _)| match p {
+            BlendTreePosition::Threshold(t) => Some(*t),
+            BlendTreePosition::Position(..) => None,
+        });
+        let min_threshold = thresholds.clone().fold(f64::INFINITY, f64::min);
+        let max_threshold = thresholds.fold(f64::NEG_INFINITY, f64::max);
+        let (min_threshold, max_threshold) = if min_threshold <= max_threshold {
+            (min_threshold, max_threshold)
+        } else {
+            (0.0, 1.0)
+        };
+        let blend_type = match tree.blend_type {
+            BlendTreeType::Simple1D => 0,
+            BlendTreeType::SimpleDirectional2D => 1,
+            BlendTreeType::FreeformDirectional2D => 2,
+            BlendTreeType::FreeformCartesian2D => 3,
+        };
+        let parameter = |i: usize| {
+            tree.parameters
+                .get(i)
+                .map_or("", |var| self.parameter_name(var))
+        };
+
+        write_object(w, 206, self.id("tree", &tree.var), "BlendTree", 1, |b| {
+            b.write(r#"m_Name: "Blend Tree""#)?;
+            b.write("m_Childs:")?;
+            for (i, (position, _)) in tree.children.iter().enumerate() {
+                let (threshold, (x, y)) = match *position {
+                    BlendTreePosition::Threshold(t) => (t, (0.0, 0.0)),
+                    BlendTreePosition::Position(x, y) => (0.0, (x, y)),
+                };
+                b.write("- serializedVersion: 2")?;
+                b.with_indent(|mut b| {
+                    let key = format!("{}_{i}", tree.var);
+                    b.write(format_args!("m_Motion: {}", self.clip_reference(&key)))?;
+                    b.write(format_args!("m_Threshold: {threshold}"))?;
+                    b.write(format_args!("m_Position: {{x: {x}, y: {y}}}"))?;
+                    b.write("m_TimeScale: 1")?;
+                    b.write("m_CycleOffset: 0")?;
+                    b.write(r#"m_DirectBlendParameter: """#)?;
+                    b.write("m_Mirror: 0")
+                })?;
+            }
+            b.write(format_args!(
+                "m_BlendParameter: {}",
+                YamlString(parameter(0))
+            ))?;
+            b.write(format_args!(
+                "m_BlendParameterY: {}",
+                YamlString(parameter(1))
+            ))?;
+            b.write(format_args!("m_MinThreshold: {min_threshold}"))?;
+            b.write(format_args!("m_MaxThreshold: {max_threshold}"))?;
+            b.write("m_UseAutomaticThresholds: 0")?;
+            b.write("m_NormalizedBlendValues: 0")?;
+            b.write(format_args!("m_BlendType: {blend_type}"))
+        })
+    }
+}
+
+/// Splits a valid condition into the terms of each clause.
+fn and_clauses(cond: &Cond) -> Vec<Vec<&Expr>> {
+    match cond {
+        Cond::Or(clauses) => clauses.iter().flat_map(and_clauses).collect(),
+        Cond::And(terms) => vec![terms
+            .iter()
+            .filter_map(|t| match t {
+                Cond::Term(expr) => Some(expr),
+                _ => None,
+            })
+            .collect()],
+        Cond::Term(expr) => vec![vec![expr]],
+    }
+}
+
+/// Writes the common fields of MonoBehaviours of the SDK scripts.
+fn write_script_header<W: Write>(w: &mut CodeWriter<W>, script: i64, name: &str) -> IoResult<()> {
+    w.write("m_GameObject: {fileID: 0}")?;
+    w.write("m_Enabled: 1")?;
+    w.write("m_EditorHideFlags: 0")?;
+    w.write(format_args!(
+        "m_Script: {{fileID: {script}, guid: {SDK_GUID}, type: 3}}"
+    ))?;
+    w.write(format_args!("m_Name: {}", YamlString(name)))?;
+    w.write(r#"m_EditorClassIdentifier: """#)
+}
+
+fn write_expression_parameters<W: Write>(
+    w: &mut CodeWriter<W>,
+    name: &str,
+    parameters: &[ExpressionParameter],
+) -> IoResult<()> {
+    write_header(w)?;
+    write_object(w, 114, MONO_BEHAVIOUR_FILE_ID, "MonoBehaviour", 0, |b| {
+        write_script_header(b, EXPRESSION_PARAMETERS_SCRIPT, name)?;
+        if parameters.is_empty() {
+            return b.write("parameters: []");
+        }
+        b.write("parameters:")?;
+        for parameter in parameters {
+            let value_type = match parameter.kind {
+                ParameterKind::Int => 0,
+                ParameterKind::Float => 1,
+                ParameterKind::Bool => 2,
+            };
+            let settings = &parameter.settings;
+            b.write(format_args!("- name: {}", YamlString(&parameter.name)))?;
+            b.with_indent(|mut b| {
+                b.write(format_args!("valueType: {value_type}"))?;
+                b.write(format_args!("saved: {}", u8::from(settings.saved)))?;
+                b.write(format_args!("defaultValue: {}", settings.default))?;
+                b.write(format_args!("networkSynced: {}", u8::from(settings.synced)))
+            })?;
+        }
+        Ok(())
+    })
+}
+
+/// YAML scalar, double-quoted unless it can be written plainly.
+struct YamlString<'a>(&'a str);
+
+impl YamlString<'_> {
+    fn is_plain(&self) -> bool {
+        let text = self.0;
+        !text.is_empty()
+            && text.trim() == text
+            && !text.starts_with(|c| "-?:,[]{}#&*!|>'\"%@`".contains(c))
+            && !text.ends_with(':')
+            && !text.contains(": ")
+            && !text.contains(" #")
+            && !text.chars().any(|c| c.is_control())
+    }
+}
+
+impl Display for YamlString<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        if self.is_plain() {
+            return f.write_str(self.0);
+        }
+        f.write_str("\"")?;
+        for c in self.0.chars() {
+            match c {
+                '"' => f.write_str(r#"\""#)?,
+                '\\' => f.write_str(r#"\\"#)?,
+                '\n' => f.write_str(r#"\n"#)?,
+                '\t' => f.write_str(r#"\t"#)?,
+                c if c.is_control() => write!(f, r#"\u{:04X}"#, c as u32)?,
+                c => write!(f, "{c}")?,
+            }
+        }
+        f.write_str("\"")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{generate_unity_assets, validate_unity_assets, UnityAsset, UnityAssetError};
+    use crate::descriptor::{
+        Descriptor, Diagnostics, MaterialSwap, ShapeKeyGroup, ShapeKeyOption, ValidatedDescriptor,
+    };
+
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        path::{Path, PathBuf},
+        process::id,
+    };
+
+    const TEAR_GUID: &str = "4b1c0b6f0e6c4a5c9e1b8a2d3f4e5a6b";
+    const EYE_GUID: &str = "7d2e1c3b4a5f4e6d8c9b0a1f2e3d4c5b";
+
+    fn example_descriptor() -> ValidatedDescriptor {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let descriptor = Descriptor::load(manifest_dir.join("../example.toml")).unwrap();
+        ValidatedDescriptor::new(descriptor).unwrap()
+    }
+
+    /// Unity project with the `.meta` files of the materials in `example.toml`.
+    fn create_project(name: &str) -> PathBuf {
+        let dir = temp_dir().join(format!("sk2aac-unity-{}-{name}", id()));
+        let materials = dir.join("Assets/Avatar/Materials");
+        create_dir_all(&materials).unwrap();
+        for (file, guid) in [("EyeTear.mat.meta", TEAR_GUID), ("Eye.mat.meta", EYE_GUID)] {
+            let meta = format!("fileFormatVersion: 2\nguid: {guid}\nNativeFormatImporter:\n");
+            write(materials.join(file), meta).unwrap();
+        }
+        dir
+    }
+
+    fn asset<'a>(assets: &'a [UnityAsset], path: &str) -> &'a str {
+        let asset = assets.iter().find(|a| a.path == Path::new(path)).unwrap();
+        std::str::from_utf8(&asset.content).unwrap()
+    }
+
+    #[test]
+    fn assets_are_stable() {
+        let descriptor = example_descriptor();
+        let dir = create_project("stable");
+        let first = generate_unity_assets(&descriptor, &dir);
+        let second = generate_unity_assets(&descriptor, &dir);
+        remove_dir_all(&dir).unwrap();
+
+        let (first, second) = (first.unwrap(), second.unwrap());
+        let contents = |assets: &[UnityAsset]| -> Vec<_> {
+            assets
+                .iter()
+                .map(|a| (a.path.clone(), a.content.clone()))
+                .collect()
+        };
+        assert_eq!(contents(&first), contents(&second));
+
+        // GUIDs are derived from the paths, so they must not change between versions either.
+        let meta = asset(&first, "SK2AAC_AvatarName.controller.meta");
+        assert!(meta.contains("guid: bda162e5df4135d503b43d3bc88cb67e\n"));
+        assert!(meta.contains("mainObjectFileID: 9100000\n"));
+        let clip = asset(&first, "SK2AAC_AvatarName/Eyelids_disabled.anim");
+        assert!(clip.starts_with("%YAML 1.1\n%TAG !u! tag:unity3d.com,2011:\n--- !u!74 &7400000\n"));
+    }
+
+    #[test]
+    fn materials_refer_to_meta_guids() {
+        let descriptor = example_descriptor();
+        let dir = create_project("materials");
+        let assets = generate_unity_assets(&descriptor, &dir);
+        remove_dir_all(&dir).unwrap();
+
+        let assets = assets.unwrap();
+        let swapped = asset(&assets, "SK2AAC_AvatarName/Eyelids_enabled5.anim");
+        let original = asset(&assets, "SK2AAC_AvatarName/Eyelids_disabled.anim");
+        let value = |guid| format!("value: {{fileID: 2100000, guid: {guid}, type: 2}}");
+        assert!(swapped.contains(&value(TEAR_GUID)));
+        assert!(original.contains(&value(EYE_GUID)));
+        assert!(original.contains("attribute: m_Materials.Array.data[1]"));
+    }
+
+    #[test]
+    fn missing_meta_is_an_error() {
+        let descriptor = example_descriptor();
+        let dir = temp_dir().join(format!("sk2aac-unity-{}-missing", id()));
+        let result = generate_unity_assets(&descriptor, &dir);
+        assert!(matches!(result, Err(UnityAssetError::MaterialMeta(..))));
+    }
+
+    #[test]
+    fn unknown_original_material_is_an_error() {
+        let mut descriptor = example_descriptor().into_inner();
+        for swap in descriptor
+            .shape_groups
+            .iter_mut()
+            .flat_map(|g| &mut g.options)
+            .flat_map(|o| &mut o.materials)
+        {
+            swap.original = None;
+        }
+        let descriptor = ValidatedDescriptor::new(descriptor).unwrap();
+        let dir = create_project("original");
+        let result = generate_unity_assets(&descriptor, &dir);
+        remove_dir_all(&dir).unwrap();
+        assert!(matches!(
+            result,
+            Err(UnityAssetError::UnknownOriginalMaterial(name, 1)) if name == "Eyelids"
+        ));
+    }
+
+    #[test]
+    fn original_materials_are_validated() {
+        let swap = |slot, original: Option<&str>| MaterialSwap {
+            slot,
+            material: "Assets/Tear.mat".into(),
+            original: original.map(|o| o.into()),
+        };
+        let option = |label, materials| ShapeKeyOption {
+            materials,
+            ..ShapeKeyOption::new(label)
+        };
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .option(option("tear", vec![swap(1, None), swap(2, None)]))
+                    .option(option("cry", vec![swap(1, Some("Assets/Eye.mat"))]))
+                    .option(option("sob", vec![swap(1, Some("Assets/Face.mat"))]))
+                    .build(),
+            )
+            .build();
+        let mut diagnostics = Diagnostics::new();
+        validate_unity_assets(&descriptor, &mut diagnostics);
+        let messages: Vec<_> = diagnostics.iter().map(|d| d.message.as_str()).collect();
+        assert_eq!(
+            messages,
+            [
+                "original material of slot 2 in \"Eyelids\" is unknown; set `original` of a material swap",
+                "different original materials are set to slot 1 in \"Eyelids\"",
+            ]
+        );
+    }
+}
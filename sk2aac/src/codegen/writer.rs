@@ -16,7 +16,7 @@ where
 
 impl<'a, W: Write> CodeWriter<'a, W> {
     /// Wraps writer for code generation.
-    pub fn new(writer: &mut W, indent_width: usize) -> CodeWriter<W> {
+    pub fn new(writer: &mut W, indent_width: usize) -> CodeWriter<'_, W> {
         CodeWriter {
             writer,
             indent_width,
@@ -0,0 +1,282 @@
+use crate::descriptor::{
+    Descriptor, DescriptorSources, Drive, Driver, DriverOption, GestureMap, MenuSettings,
+    NormalizedF64, ObjectToggle, ParameterSettings, ShapeKeyCommon, ShapeKeyDrive, ShapeKeyGroup,
+    ShapeKeyOption, ShapeKeyPuppet, ShapeKeySlider, ShapeKeySwitch, Span,
+};
+
+impl Descriptor {
+    /// Starts building a descriptor programmatically.
+    pub fn builder(name: impl Into<String>) -> DescriptorBuilder {
+        DescriptorBuilder(Descriptor {
+            name: name.into(),
+            span: Span::default(),
+            includes: vec![],
+            sync_budget: None,
+            shape_groups: vec![],
+            shape_switches: vec![],
+            shape_sliders: vec![],
+            shape_puppets: vec![],
+            object_toggles: vec![],
+            drivers: vec![],
+            gesture_maps: vec![],
+            sources: DescriptorSources::default(),
+        })
+    }
+}
+
+/// Builds a `Descriptor`. Built descriptors are checked by `ValidatedDescriptor::new` before generation.
+#[derive(Debug, Clone)]
+pub struct DescriptorBuilder(Descriptor);
+
+impl DescriptorBuilder {
+    /// Sets the maximum bits of synced Expression Parameters.
+    pub fn sync_budget(mut self, bits: usize) -> Self {
+        self.0.sync_budget = Some(bits);
+        self
+    }
+
+    /// Adds a shape key switch.
+    pub fn shape_switch(mut self, switch: ShapeKeySwitch) -> Self {
+        self.0.shape_switches.push(switch);
+        self
+    }
+
+    /// Adds a shape key group.
+    pub fn shape_group(mut self, group: ShapeKeyGroup) -> Self {
+        self.0.shape_groups.push(group);
+        self
+    }
+
+    /// Adds a shape key slider.
+    pub fn shape_slider(mut self, slider: ShapeKeySlider) -> Self {
+        self.0.shape_sliders.push(slider);
+        self
+    }
+
+    /// Adds a shape key puppet.
+    pub fn shape_puppet(mut self, puppet: ShapeKeyPuppet) -> Self {
+        self.0.shape_puppets.push(puppet);
+        self
+    }
+
+    /// Adds a GameObject toggle.
+    pub fn object_toggle(mut self, toggle: ObjectToggle) -> Self {
+        self.0.object_toggles.push(toggle);
+        self
+    }
+
+    /// Adds a parameter driver layer.
+    pub fn driver(mut self, driver: Driver) -> Self {
+        self.0.drivers.push(driver);
+        self
+    }
+
+    /// Adds a hand gesture layer.
+    pub fn gesture_map(mut self, gesture_map: GestureMap) -> Self {
+        self.0.gesture_maps.push(gesture_map);
+        self
+    }
+
+    pub fn build(self) -> Descriptor {
+        self.0
+    }
+}
+
+impl ShapeKeyCommon {
+    fn new(name: String) -> ShapeKeyCommon {
+        ShapeKeyCommon {
+            name,
+            span: Span::default(),
+            mesh: vec![],
+            prevent_eyelids: false,
+            prevent_mouth: false,
+            menu: MenuSettings::default(),
+            parameter: ParameterSettings::default(),
+        }
+    }
+}
+
+/// Implements setters of `ShapeKeyCommon` for layer builders.
+macro_rules! impl_common_setters {
+    ($builder:ty) => {
+        impl $builder {
+            /// Adds a target SkinnedMeshRenderer. The first one is the primary mesh.
+            pub fn mesh(mut self, mesh: impl Into<String>) -> Self {
+                self.0.common.mesh.push(mesh.into());
+                self
+            }
+
+            /// Sets whether this layer prevents the eyelids animation.
+            pub fn prevent_eyelids(mut self, prevent: bool) -> Self {
+                self.0.common.prevent_eyelids = prevent;
+                self
+            }
+
+            /// Sets whether this layer prevents the mouth animation.
+            pub fn prevent_mouth(mut self, prevent: bool) -> Self {
+                self.0.common.prevent_mouth = prevent;
+                self
+            }
+
+            /// Sets the Expressions Menu settings.
+            pub fn menu(mut self, menu: MenuSettings) -> Self {
+                self.0.common.menu = menu;
+                self
+            }
+
+            /// Sets the Expression Parameter settings.
+            pub fn parameter(mut self, parameter: ParameterSettings) -> Self {
+                self.0.common.parameter = parameter;
+                self
+            }
+        }
+    };
+}
+
+impl ShapeKeySwitch {
+    /// Starts building a shape key switch.
+    pub fn builder(name: impl Into<String>, shape: impl Into<String>) -> ShapeKeySwitchBuilder {
+        ShapeKeySwitchBuilder(ShapeKeySwitch {
+            common: ShapeKeyCommon::new(name.into()),
+            shape: shape.into(),
+            enabled_value: NormalizedF64(1.0),
+            disabled_value: NormalizedF64(0.0),
+            enabled_value_span: None,
+            disabled_value_span: None,
+        })
+    }
+}
+
+/// Builds a `ShapeKeySwitch`.
+#[derive(Debug, Clone)]
+pub struct ShapeKeySwitchBuilder(ShapeKeySwitch);
+
+impl_common_setters!(ShapeKeySwitchBuilder);
+
+impl ShapeKeySwitchBuilder {
+    /// Sets the value on enabled.
+    pub fn enabled_value(mut self, value: f64) -> Self {
+        self.0.enabled_value = NormalizedF64(value);
+        self
+    }
+
+    /// Sets the value on disabled.
+    pub fn disabled_value(mut self, value: f64) -> Self {
+        self.0.disabled_value = NormalizedF64(value);
+        self
+    }
+
+    pub fn build(self) -> ShapeKeySwitch {
+        self.0
+    }
+}
+
+impl ShapeKeyGroup {
+    /// Starts building a shape key group.
+    pub fn builder(name: impl Into<String>) -> ShapeKeyGroupBuilder {
+        ShapeKeyGroupBuilder(ShapeKeyGroup {
+            common: ShapeKeyCommon::new(name.into()),
+            bit_packed: false,
+            defaults: vec![],
+            options: vec![],
+        })
+    }
+}
+
+/// Builds a `ShapeKeyGroup`.
+#[derive(Debug, Clone)]
+pub struct ShapeKeyGroupBuilder(ShapeKeyGroup);
+
+impl_common_setters!(ShapeKeyGroupBuilder);
+
+impl ShapeKeyGroupBuilder {
+    /// Syncs the index as Bool parameters, one for each bit.
+    pub fn bit_packed(mut self) -> Self {
+        self.0.bit_packed = true;
+        self
+    }
+
+    /// Adds a default shape key value.
+    pub fn default_shape(mut self, drive: ShapeKeyDrive) -> Self {
+        self.0.defaults.push(drive);
+        self
+    }
+
+    /// Adds an option.
+    pub fn option(mut self, option: ShapeKeyOption) -> Self {
+        self.0.options.push(option);
+        self
+    }
+
+    pub fn build(self) -> ShapeKeyGroup {
+        self.0
+    }
+}
+
+impl ShapeKeyOption {
+    /// Creates an option which moves the shape key of the same name to 1.0.
+    pub fn new(label: impl Into<String>) -> ShapeKeyOption {
+        let label = label.into();
+        ShapeKeyOption {
+            shapes: vec![ShapeKeyDrive::new(label.clone())],
+            label,
+            index: None,
+            icon: None,
+            materials: vec![],
+            span: Span::default(),
+        }
+    }
+}
+
+impl DriverOption {
+    /// Creates an option which drives the parameters.
+    pub fn new(label: impl Into<String>, drives: impl IntoIterator<Item = Drive>) -> DriverOption {
+        DriverOption {
+            label: label.into(),
+            icon: None,
+            drives: drives.into_iter().collect(),
+            span: Span::default(),
+        }
+    }
+}
+
+impl Driver {
+    /// Starts building a parameter driver layer.
+    pub fn builder(name: impl Into<String>) -> DriverBuilder {
+        DriverBuilder(Driver {
+            name: name.into(),
+            span: Span::default(),
+            options: vec![],
+            menu: MenuSettings::default(),
+            parameter: ParameterSettings::default(),
+        })
+    }
+}
+
+/// Builds a `Driver`.
+#[derive(Debug, Clone)]
+pub struct DriverBuilder(Driver);
+
+impl DriverBuilder {
+    /// Adds an option.
+    pub fn option(mut self, option: DriverOption) -> Self {
+        self.0.options.push(option);
+        self
+    }
+
+    /// Sets the Expressions Menu settings.
+    pub fn menu(mut self, menu: MenuSettings) -> Self {
+        self.0.menu = menu;
+        self
+    }
+
+    /// Sets the Expression Parameter settings.
+    pub fn parameter(mut self, parameter: ParameterSettings) -> Self {
+        self.0.parameter = parameter;
+        self
+    }
+
+    pub fn build(self) -> Driver {
+        self.0
+    }
+}
@@ -0,0 +1,234 @@
+use std::{
+    fmt::Display,
+    io::{prelude::*, Result as IoResult},
+    path::{Path, PathBuf},
+};
+
+use serde::Serialize;
+use toml::Spanned;
+
+/// Byte range in a descriptor file.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    /// Takes the span of a deserialized TOML value.
+    pub fn of<T>(spanned: &Spanned<T>) -> Span {
+        Span {
+            start: spanned.start(),
+            end: spanned.end(),
+        }
+    }
+
+    /// Makes a span of the character at zero-based line and byte column, as reported by toml errors.
+    pub fn from_line_col(text: &str, line: usize, col: usize) -> Span {
+        let line_start: usize = text.split_inclusive('\n').take(line).map(|l| l.len()).sum();
+        let start = (line_start + col).min(text.len());
+        let len = text
+            .get(start..)
+            .and_then(|t| t.chars().next())
+            .map_or(1, char::len_utf8);
+        Span {
+            start,
+            end: start + len,
+        }
+    }
+
+    /// Calculates one-based line and column of the start.
+    pub fn line_col(&self, text: &str) -> (usize, usize) {
+        let before = &text[..self.start.min(text.len())];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map(|i| i + 1).unwrap_or(0);
+        let col = before[line_start..].chars().count() + 1;
+        (line, col)
+    }
+}
+
+/// Severity of a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// An error or warning with its location in a descriptor file.
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+
+    /// Descriptor file. `None` if the descriptor was not loaded from a file.
+    pub source: Option<PathBuf>,
+    pub span: Option<Span>,
+}
+
+impl Diagnostic {
+    /// Writes the diagnostic with a source snippet and a caret.
+    pub fn write_report<W: Write>(&self, w: &mut W, text: Option<&str>) -> IoResult<()> {
+        let severity = match self.severity {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        };
+        writeln!(w, "{severity}: {}", self.message)?;
+
+        let source = self
+            .source
+            .as_deref()
+            .map(|p| p.display().to_string())
+            .unwrap_or_else(|| "<descriptor>".to_string());
+        // Spans not on character boundaries are reported without the snippet.
+        let (text, span) = match (text, self.span) {
+            (Some(text), Some(span))
+                if text.get(span.start..span.end.min(text.len())).is_some() =>
+            {
+                (text, span)
+            }
+            _ => {
+                if self.source.is_some() {
+                    writeln!(w, "  --> {source}")?;
+                }
+                return Ok(());
+            }
+        };
+
+        let (line, col) = span.line_col(text);
+        let line_text = text.lines().nth(line - 1).unwrap_or("");
+        let line_number = line.to_string();
+        let gutter = " ".repeat(line_number.len());
+        let caret_width = text
+            .get(span.start..span.end.min(text.len()))
+            .and_then(|t| t.lines().next())
+            .map(|l| l.chars().count())
+            .unwrap_or(0)
+            .max(1);
+
+        writeln!(w, "{gutter}--> {source}:{line}:{col}")?;
+        writeln!(w, "{gutter} |")?;
+        writeln!(w, "{line_number} | {line_text}")?;
+        writeln!(
+            w,
+            "{gutter} | {}{}",
+            " ".repeat(col - 1),
+            "^".repeat(caret_width)
+        )?;
+        Ok(())
+    }
+}
+
+/// Collects diagnostics of a descriptor.
+#[derive(Debug, Clone, Default)]
+pub struct Diagnostics(Vec<Diagnostic>);
+
+impl Diagnostics {
+    pub fn new() -> Diagnostics {
+        Diagnostics::default()
+    }
+
+    /// Adds an error.
+    pub fn error(&mut self, message: impl Display, source: Option<&Path>, span: Span) {
+        self.push(Severity::Error, message, source, span);
+    }
+
+    /// Adds a warning.
+    pub fn warning(&mut self, message: impl Display, source: Option<&Path>, span: Span) {
+        self.push(Severity::Warning, message, source, span);
+    }
+
+    fn push(
+        &mut self,
+        severity: Severity,
+        message: impl Display,
+        source: Option<&Path>,
+        span: Span,
+    ) {
+        self.0.push(Diagnostic {
+            severity,
+            message: message.to_string(),
+            source: source.map(|p| p.to_path_buf()),
+            span: Some(span),
+        });
+    }
+
+    /// Number of errors.
+    pub fn error_count(&self) -> usize {
+        self.0
+            .iter()
+            .filter(|d| d.severity == Severity::Error)
+            .count()
+    }
+
+    /// Checks whether any error is collected.
+    pub fn has_errors(&self) -> bool {
+        self.error_count() > 0
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Diagnostic> {
+        self.0.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostic, Severity, Span};
+    use crate::descriptor::Descriptor;
+
+    use std::{
+        env::temp_dir,
+        fs::{remove_file, write},
+        path::PathBuf,
+        process::id,
+    };
+
+    fn report(text: &str, span: Span) -> String {
+        let diagnostic = Diagnostic {
+            severity: Severity::Error,
+            message: "error".into(),
+            source: Some(PathBuf::from("avatar.toml")),
+            span: Some(span),
+        };
+        let mut report = vec![];
+        diagnostic.write_report(&mut report, Some(text)).unwrap();
+        String::from_utf8(report).unwrap()
+    }
+
+    #[test]
+    fn toml_error_at_non_ascii_character() {
+        let text = "name = \"Avatar\"\nmesh = あ\n";
+        let error = toml::from_str::<toml::Value>(text).unwrap_err();
+        let (line, col) = error.line_col().unwrap();
+        let span = Span::from_line_col(text, line, col);
+        assert_eq!(&text[span.start..span.end], "あ");
+        assert_eq!(
+            report(text, span),
+            "error: error\n --> avatar.toml:2:8\n  |\n2 | mesh = あ\n  |        ^\n"
+        );
+    }
+
+    #[test]
+    fn span_inside_character_has_no_snippet() {
+        let text = "mesh = あ\n";
+        let span = Span { start: 8, end: 9 };
+        assert_eq!(report(text, span), "error: error\n  --> avatar.toml\n");
+    }
+
+    #[test]
+    fn non_ascii_name_next_to_syntax_error() {
+        let text = "name = \"アバター\"\n\n[[shape_switches]]\nname = \"ほっぺ\"ほっぺ\n";
+        let path = temp_dir().join(format!("sk2aac-diagnostic-{}.toml", id()));
+        write(&path, text).unwrap();
+        let error = Descriptor::load(&path).unwrap_err();
+        remove_file(&path).unwrap();
+
+        let mut report = vec![];
+        error
+            .to_diagnostic()
+            .write_report(&mut report, Some(text))
+            .unwrap();
+        let report = String::from_utf8(report).unwrap();
+        assert!(report.contains(":4:"), "{report}");
+        assert!(report.contains("4 | name = \"ほっぺ\"ほっぺ\n"), "{report}");
+    }
+}
@@ -0,0 +1,367 @@
+use crate::descriptor::{
+    include::{parse_file, IncludedDescriptor},
+    Descriptor, LoadError,
+};
+
+use std::{
+    fs::read_to_string,
+    path::{Path, PathBuf},
+};
+
+use thiserror::Error as ThisError;
+use toml_edit::{
+    ser::{to_document, Error as SerializeError},
+    Array, ArrayOfTables, Document, Item, Table, TomlError, Value,
+};
+
+/// Layer arrays written as arrays of tables.
+const TABLE_ARRAYS: &[&str] = &[
+    "shape_switches",
+    "shape_groups",
+    "shape_sliders",
+    "shape_puppets",
+    "object_toggles",
+    "drivers",
+    "gesture_maps",
+];
+
+/// Layers which have Bool parameters.
+const BOOL_LAYERS: &[&str] = &["shape_switches", "object_toggles"];
+
+const INDENT: &str = "    ";
+
+/// Arrays in the elements longer than this are written in multiple lines.
+const MAX_WIDTH: usize = 100;
+
+#[derive(Debug, ThisError)]
+pub enum FormatError {
+    /// Failed to load the descriptor.
+    #[error(transparent)]
+    Load(#[from] LoadError),
+
+    /// Failed to parse the descriptor for its comments.
+    #[error("failed to parse {}", .0.display())]
+    Parse(PathBuf, #[source] TomlError),
+
+    /// Failed to serialize the descriptor.
+    #[error("failed to format {}", .0.display())]
+    Serialize(PathBuf, #[source] SerializeError),
+}
+
+/// Formats a descriptor file in the canonical form, keeping its comments where possible.
+/// Included descriptor files are also accepted. Included files are not followed.
+pub fn format_file(path: impl AsRef<Path>) -> Result<String, FormatError> {
+    let path = path.as_ref();
+    let document = match parse_file::<Descriptor>(path) {
+        Ok(descriptor) => to_document(&descriptor),
+        Err(e) => match parse_file::<IncludedDescriptor>(path) {
+            Ok(included) => to_document(&included),
+            Err(_) => return Err(e.into()),
+        },
+    };
+    let mut document = document.map_err(|e| FormatError::Serialize(path.to_path_buf(), e))?;
+
+    let source = read_to_string(path).map_err(|e| LoadError::Io(path.to_path_buf(), e))?;
+    let original: Document = source
+        .parse()
+        .map_err(|e| FormatError::Parse(path.to_path_buf(), e))?;
+
+    layout_document(&mut document);
+    let mut trailing = copy_table_comments(original.as_table(), document.as_table_mut());
+    if let Some(comments) = raw_str(original.trailing()).and_then(|t| comment_lines(t, "")) {
+        trailing.push_str(&comments);
+    }
+    document.set_trailing(trailing);
+
+    Ok(document.to_string())
+}
+
+/// Arranges serialized values in the canonical layout.
+fn layout_document(document: &mut Document) {
+    for (key, item) in document.iter_mut() {
+        if !TABLE_ARRAYS.contains(&key.get()) {
+            continue;
+        }
+        let mut tables = to_array_of_tables(item);
+        for table in tables.iter_mut() {
+            if key == "drivers" {
+                if let Some(options) = table.get_mut("options") {
+                    let mut options_tables = to_array_of_tables(options);
+                    options_tables.iter_mut().for_each(layout_table);
+                    *options = Item::ArrayOfTables(options_tables);
+                }
+            }
+            if BOOL_LAYERS.contains(&key.get()) {
+                if let Some(default) = table.get_mut("default") {
+                    if let Some(value) = default.as_integer() {
+                        *default = toml_edit::value(value != 0);
+                    }
+                }
+            }
+            layout_table(table);
+        }
+        *item = Item::ArrayOfTables(tables);
+    }
+}
+
+fn to_array_of_tables(item: &mut Item) -> ArrayOfTables {
+    let mut tables = ArrayOfTables::new();
+    if let Some(array) = item.as_array() {
+        for value in array {
+            if let Some(inline_table) = value.as_inline_table() {
+                tables.push(inline_table.clone().into_table());
+            }
+        }
+    }
+    tables
+}
+
+fn layout_table(table: &mut Table) {
+    for (_, item) in table.iter_mut() {
+        if let Some(array) = item.as_array_mut() {
+            layout_array(array, 1);
+        }
+    }
+}
+
+/// Writes an array of inline tables in multiple lines.
+/// Arrays in too long elements are also broken.
+fn layout_array(array: &mut Array, depth: usize) {
+    if !array.iter().any(|v| v.is_inline_table()) {
+        return;
+    }
+
+    let indent = INDENT.repeat(depth);
+    for value in array.iter_mut() {
+        value.decor_mut().set_prefix(format!("\n{indent}"));
+        let width = indent.len() + value.to_string().trim().chars().count() + 1;
+        if let (true, Some(inline_table)) = (width > MAX_WIDTH, value.as_inline_table_mut()) {
+            for (_, nested) in inline_table.iter_mut() {
+                if let Some(nested) = nested.as_array_mut() {
+                    layout_array(nested, depth + 1);
+                }
+            }
+        }
+    }
+    array.set_trailing_comma(true);
+    array.set_trailing(format!("\n{}", INDENT.repeat(depth - 1)));
+}
+
+/// Copies the comments of the original table into the formatted one.
+/// Comments of the omitted keys are moved to the next key, and the rest are returned.
+fn copy_table_comments(original: &Table, formatted: &mut Table) -> String {
+    let mut pending = String::new();
+    for (key, original_item) in original.iter() {
+        let key_prefix = original
+            .key_decor(key)
+            .and_then(|d| d.prefix())
+            .and_then(raw_str);
+        let item_prefix = match original_item {
+            Item::Table(t) => t.decor().prefix().and_then(raw_str),
+            _ => None,
+        };
+        if let Some(comments) = key_prefix
+            .or(item_prefix)
+            .and_then(|p| comment_lines(p, ""))
+        {
+            pending.push_str(&comments);
+        }
+
+        let formatted_item = match formatted.get_mut(key) {
+            Some(item) => item,
+            None => {
+                let suffix = original_item
+                    .as_value()
+                    .and_then(|v| v.decor().suffix())
+                    .and_then(raw_str);
+                if let Some(comment) = suffix.filter(|s| s.contains('#')) {
+                    pending.push_str(comment.trim());
+                    pending.push('\n');
+                }
+                continue;
+            }
+        };
+        match (original_item, formatted_item) {
+            (Item::ArrayOfTables(original_tables), Item::ArrayOfTables(formatted_tables)) => {
+                let pairs = original_tables.iter().zip(formatted_tables.iter_mut());
+                for (original_table, formatted_table) in pairs {
+                    let comments = original_table
+                        .decor()
+                        .prefix()
+                        .and_then(raw_str)
+                        .and_then(|p| comment_lines(p, ""));
+                    if let Some(comments) = comments {
+                        pending.push_str(&comments);
+                    }
+                    if !pending.is_empty() {
+                        let comments = pending.trim_start_matches('\n');
+                        formatted_table
+                            .decor_mut()
+                            .set_prefix(format!("\n{comments}"));
+                        pending.clear();
+                    }
+                    pending = copy_table_comments(original_table, formatted_table);
+                }
+                continue;
+            }
+            (Item::Value(original_value), Item::Value(formatted_value)) => {
+                copy_value_comments(original_value, formatted_value);
+            }
+            _ => (),
+        }
+
+        if !pending.is_empty() {
+            if let Some(decor) = formatted.key_decor_mut(key) {
+                decor.set_prefix(pending.clone());
+            }
+            pending.clear();
+        }
+    }
+    pending
+}
+
+/// Copies the trailing comment of a value and the comments in an array.
+fn copy_value_comments(original: &Value, formatted: &mut Value) {
+    if let Some(suffix) = original.decor().suffix().and_then(raw_str) {
+        if suffix.contains('#') {
+            formatted
+                .decor_mut()
+                .set_suffix(format!(" {}", suffix.trim()));
+        }
+    }
+
+    let (original, formatted) = match (original.as_array(), formatted.as_array_mut()) {
+        (Some(o), Some(f)) => (o, f),
+        _ => return,
+    };
+    if !formatted.trailing_comma() {
+        // Comments cannot be placed in single-line arrays.
+        return;
+    }
+    copy_array_comments(original, formatted);
+}
+
+fn copy_array_comments(original: &Array, formatted: &mut Array) {
+    for (original_value, formatted_value) in original.iter().zip(formatted.iter_mut()) {
+        let prefix = original_value.decor().prefix().and_then(raw_str);
+        if let Some(prefix) = prefix.and_then(|p| element_prefix(p, INDENT)) {
+            formatted_value.decor_mut().set_prefix(prefix);
+        }
+    }
+    if let Some(trailing) = raw_str(original.trailing()).and_then(|t| element_prefix(t, "")) {
+        formatted.set_trailing(trailing);
+    }
+}
+
+/// Normalizes the comment lines in a key or table prefix.
+/// Returns `None` if it has no comment.
+fn comment_lines(prefix: &str, indent: &str) -> Option<String> {
+    if !prefix.contains('#') {
+        return None;
+    }
+
+    let mut lines = String::new();
+    let mut blank = false;
+    for line in prefix.lines().map(|l| l.trim()) {
+        if line.is_empty() {
+            blank = true;
+            continue;
+        }
+        if blank {
+            lines.push('\n');
+            blank = false;
+        }
+        lines.push_str(indent);
+        lines.push_str(line);
+        lines.push('\n');
+    }
+    Some(lines)
+}
+
+/// Normalizes the prefix of an array element which has comments.
+/// The first line is the rest of the line of the previous element.
+fn element_prefix(prefix: &str, indent: &str) -> Option<String> {
+    if !prefix.contains('#') {
+        return None;
+    }
+
+    let (first, rest) = prefix.split_once('\n').unwrap_or((prefix, ""));
+    let mut normalized = String::new();
+    if first.contains('#') {
+        normalized.push(' ');
+        normalized.push_str(first.trim());
+    }
+    normalized.push('\n');
+    if let Some(lines) = comment_lines(rest, INDENT) {
+        normalized.push_str(&lines);
+    }
+    normalized.push_str(indent);
+    Some(normalized)
+}
+
+fn raw_str(raw: &toml_edit::RawString) -> Option<&str> {
+    raw.as_str()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::format_file;
+
+    use std::{env::temp_dir, fs::write, path::PathBuf, process::id};
+
+    /// Formats the source written into a temporary file.
+    fn format_source(name: &str, source: &str) -> String {
+        let path = temp_dir().join(format!("sk2aac-format-{}-{name}.toml", id()));
+        write(&path, source).unwrap();
+        let formatted = format_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        formatted
+    }
+
+    #[test]
+    fn formatting_is_idempotent() {
+        let example = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("../example.toml");
+        let formatted = format_file(example).unwrap();
+        assert_eq!(format_source("idempotent", &formatted), formatted);
+    }
+
+    #[test]
+    fn comments_are_carried_over() {
+        let source = r#"# Avatar name.
+name = "AvatarName"
+
+# Eyelids group.
+[[shape_groups]]
+name = "Eyelids"
+mesh = ["Face"] # primary mesh
+# Omitted default.
+saved = true
+options = [
+    # Smile.
+    "eyelids_smile",
+    { label = "eyelids_close", icon = "close.png", shapes = ["eyelids_close"] }, # close
+]
+
+# Trailing comment.
+"#;
+        let expected = r#"# Avatar name.
+name = "AvatarName"
+
+# Eyelids group.
+[[shape_groups]]
+name = "Eyelids"
+mesh = "Face" # primary mesh
+# Omitted default.
+options = [
+    # Smile.
+    "eyelids_smile",
+    { label = "eyelids_close", icon = "close.png" }, # close
+]
+
+# Trailing comment.
+"#;
+        let formatted = format_source("comments", source);
+        assert_eq!(formatted, expected);
+        assert_eq!(format_source("comments-again", &formatted), formatted);
+    }
+}
@@ -0,0 +1,26 @@
+use std::fmt::Write;
+
+/// Whether the character can be used in layer and parameter names.
+/// Names are used as they are for Expression Parameters, so Unicode letters are allowed.
+pub fn is_name_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+/// Derives a C# identifier from a name.
+/// ASCII alphanumerics and underscores are kept, and other characters are replaced with
+/// `_XXXX` of their code points, e.g. `笑顔` becomes `_7B11_9854`.
+/// Different names may result in the same identifier, which `validate_descriptor` reports.
+pub fn to_identifier(name: &str) -> String {
+    let mut identifier = String::with_capacity(name.len());
+    if name.is_empty() || name.starts_with(|c: char| c.is_ascii_digit()) {
+        identifier.push('_');
+    }
+    for c in name.chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            identifier.push(c);
+        } else {
+            write!(identifier, "_{:04X}", c as u32).expect("Writing to String never fails");
+        }
+    }
+    identifier
+}
@@ -0,0 +1,296 @@
+use crate::descriptor::{
+    raw::RawIncludedDescriptor, Descriptor, Diagnostic, Driver, Severity, ShapeKeyGroup,
+    ShapeKeySwitch, Span,
+};
+
+use std::{
+    collections::HashSet,
+    fs::read_to_string,
+    io::Error as IoError,
+    iter::repeat,
+    path::{Path, PathBuf},
+};
+
+use serde::{de::DeserializeOwned, Deserialize, Deserializer, Serialize};
+use thiserror::Error as ThisError;
+use toml::{de::Error as TomlError, from_str as toml_from_str};
+
+#[derive(Debug, ThisError)]
+pub enum LoadError {
+    /// Failed to read a descriptor file.
+    #[error("failed to read {}", .0.display())]
+    Io(PathBuf, #[source] IoError),
+
+    /// Failed to parse a descriptor file.
+    #[error("failed to parse {}", .0.display())]
+    Toml(PathBuf, #[source] TomlError, Option<Span>),
+
+    /// A file includes itself directly or indirectly.
+    #[error("circular include: {}", .0.display())]
+    CircularInclude(PathBuf),
+}
+
+impl LoadError {
+    /// The file which failed to load.
+    pub fn path(&self) -> &Path {
+        match self {
+            LoadError::Io(path, _)
+            | LoadError::Toml(path, _, _)
+            | LoadError::CircularInclude(path) => path,
+        }
+    }
+
+    /// Converts into a diagnostic located in the failed file.
+    pub fn to_diagnostic(&self) -> Diagnostic {
+        let (source, message, span) = match self {
+            LoadError::Io(path, e) => (path, format!("{self}: {e}"), None),
+            LoadError::Toml(path, e, span) => (path, e.to_string(), *span),
+            LoadError::CircularInclude(path) => (path, self.to_string(), None),
+        };
+        Diagnostic {
+            severity: Severity::Error,
+            message,
+            source: Some(source.clone()),
+            span,
+        }
+    }
+}
+
+/// Source files of the layers merged by `includes`.
+#[derive(Debug, Clone, Default)]
+pub struct DescriptorSources {
+    /// The descriptor file itself.
+    pub root: Option<PathBuf>,
+
+    /// All included files in the loaded order.
+    pub included: Vec<PathBuf>,
+
+    /// Source file of each shape key switch.
+    pub shape_switches: Vec<PathBuf>,
+
+    /// Source file of each shape key group.
+    pub shape_groups: Vec<PathBuf>,
+
+    /// Source file of each driver.
+    pub drivers: Vec<PathBuf>,
+}
+
+impl DescriptorSources {
+    /// The descriptor file and all included files.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.root
+            .iter()
+            .chain(self.included.iter())
+            .map(|p| p.as_path())
+    }
+
+    /// Source file of the shape key switch.
+    pub fn shape_switch(&self, index: usize) -> Option<&Path> {
+        self.shape_switches
+            .get(index)
+            .or(self.root.as_ref())
+            .map(|p| p.as_path())
+    }
+
+    /// Source file of the shape key group.
+    pub fn shape_group(&self, index: usize) -> Option<&Path> {
+        self.shape_groups
+            .get(index)
+            .or(self.root.as_ref())
+            .map(|p| p.as_path())
+    }
+
+    /// Source file of the driver.
+    pub fn driver(&self, index: usize) -> Option<&Path> {
+        self.drivers
+            .get(index)
+            .or(self.root.as_ref())
+            .map(|p| p.as_path())
+    }
+}
+
+/// Part of a descriptor loaded from an included file.
+#[derive(Debug, Clone, Serialize)]
+pub(super) struct IncludedDescriptor {
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    includes: Vec<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shape_groups: Vec<ShapeKeyGroup>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    shape_switches: Vec<ShapeKeySwitch>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    drivers: Vec<Driver>,
+}
+
+impl IncludedDescriptor {
+    fn from_raw<'de, D>(raw: RawIncludedDescriptor) -> Result<IncludedDescriptor, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let shape_switches = raw
+            .shape_switches
+            .into_iter()
+            .flatten()
+            .map(|s| ShapeKeySwitch::from_raw::<'de, D>(s))
+            .collect::<Result<_, _>>()?;
+        let shape_groups = raw
+            .shape_groups
+            .into_iter()
+            .flatten()
+            .map(|s| ShapeKeyGroup::from_raw::<'de, D>(s))
+            .collect::<Result<_, _>>()?;
+        let drivers = raw
+            .drivers
+            .into_iter()
+            .flatten()
+            .map(|s| Driver::from_raw::<'de, D>(s))
+            .collect::<Result<_, _>>()?;
+
+        Ok(IncludedDescriptor {
+            includes: raw.includes.unwrap_or_default(),
+            shape_switches,
+            shape_groups,
+            drivers,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for IncludedDescriptor {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawIncludedDescriptor::deserialize(deserializer)?;
+        IncludedDescriptor::from_raw::<'de, D>(raw)
+    }
+}
+
+impl Descriptor {
+    /// Loads a descriptor file and merges the layers of its included files.
+    /// Included paths are resolved relative to the including file.
+    pub fn load(path: impl AsRef<Path>) -> Result<Descriptor, LoadError> {
+        let path = path.as_ref();
+        let mut descriptor: Descriptor = parse_file(path)?;
+        descriptor.sources = DescriptorSources {
+            root: Some(path.to_path_buf()),
+            included: vec![],
+            shape_switches: vec![path.to_path_buf(); descriptor.shape_switches.len()],
+            shape_groups: vec![path.to_path_buf(); descriptor.shape_groups.len()],
+            drivers: vec![path.to_path_buf(); descriptor.drivers.len()],
+        };
+
+        let includes = descriptor.includes.clone();
+        let canonical_path = canonicalize(path)?;
+        let mut merged = HashSet::from([canonical_path.clone()]);
+        let mut include_stack = vec![canonical_path];
+        merge_includes(
+            &mut descriptor,
+            path,
+            &includes,
+            &mut merged,
+            &mut include_stack,
+        )?;
+        Ok(descriptor)
+    }
+}
+
+/// Merges the included files recursively.
+/// Files already merged through another path are skipped, and `include_stack` detects cycles.
+fn merge_includes(
+    descriptor: &mut Descriptor,
+    including_path: &Path,
+    includes: &[String],
+    merged: &mut HashSet<PathBuf>,
+    include_stack: &mut Vec<PathBuf>,
+) -> Result<(), LoadError> {
+    let base_dir = including_path.parent().unwrap_or_else(|| Path::new(""));
+    for include in includes {
+        let path = base_dir.join(include);
+        let canonical_path = canonicalize(&path)?;
+        if include_stack.contains(&canonical_path) {
+            return Err(LoadError::CircularInclude(path));
+        }
+        if !merged.insert(canonical_path.clone()) {
+            continue;
+        }
+
+        let included: IncludedDescriptor = parse_file(&path)?;
+        let sources = &mut descriptor.sources;
+        sources.included.push(path.clone());
+        let source = repeat(path.clone());
+        sources
+            .shape_switches
+            .extend(source.clone().take(included.shape_switches.len()));
+        sources
+            .shape_groups
+            .extend(source.clone().take(included.shape_groups.len()));
+        sources.drivers.extend(source.take(included.drivers.len()));
+        descriptor.shape_switches.extend(included.shape_switches);
+        descriptor.shape_groups.extend(included.shape_groups);
+        descriptor.drivers.extend(included.drivers);
+
+        include_stack.push(canonical_path);
+        merge_includes(descriptor, &path, &included.includes, merged, include_stack)?;
+        include_stack.pop();
+    }
+
+    Ok(())
+}
+
+fn canonicalize(path: &Path) -> Result<PathBuf, LoadError> {
+    path.canonicalize()
+        .map_err(|e| LoadError::Io(path.to_path_buf(), e))
+}
+
+pub(crate) fn parse_file<T: DeserializeOwned>(path: &Path) -> Result<T, LoadError> {
+    let source = read_to_string(path).map_err(|e| LoadError::Io(path.to_path_buf(), e))?;
+    toml_from_str(&source).map_err(|e| {
+        let span = e
+            .line_col()
+            .map(|(line, col)| Span::from_line_col(&source, line, col));
+        LoadError::Toml(path.to_path_buf(), e, span)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::descriptor::{Descriptor, LoadError};
+
+    use std::{
+        env::temp_dir,
+        fs::{create_dir_all, remove_dir_all, write},
+        process::id,
+    };
+
+    #[test]
+    fn diamond_includes_are_merged_once() {
+        let dir = temp_dir().join(format!("sk2aac-include-{}", id()));
+        create_dir_all(&dir).unwrap();
+        let files = [
+            (
+                "a.toml",
+                "name = \"A\"\nincludes = [\"b.toml\", \"c.toml\"]\n",
+            ),
+            ("b.toml", "includes = [\"d.toml\"]\n"),
+            ("c.toml", "includes = [\"d.toml\"]\n"),
+            (
+                "d.toml",
+                "[[shape_switches]]\nname = \"Cheek\"\nmesh = \"Face\"\nshape = \"cheek\"\n",
+            ),
+        ];
+        for (name, source) in files {
+            write(dir.join(name), source).unwrap();
+        }
+        let descriptor = Descriptor::load(dir.join("a.toml"));
+
+        // d.toml including a.toml makes a cycle.
+        write(dir.join("d.toml"), "includes = [\"a.toml\"]\n").unwrap();
+        let circular = Descriptor::load(dir.join("a.toml"));
+        remove_dir_all(&dir).unwrap();
+
+        let descriptor = descriptor.unwrap();
+        assert_eq!(descriptor.shape_switches.len(), 1);
+        assert_eq!(descriptor.sources.shape_switches.len(), 1);
+        assert!(matches!(circular, Err(LoadError::CircularInclude(_))));
+    }
+}
@@ -0,0 +1,103 @@
+use crate::descriptor::{
+    identifier::is_name_char,
+    include::{parse_file, LoadError},
+    raw::{RawInventory, RawInventoryMesh},
+    Descriptor, ShapeKeyGroup, ShapeKeyOption,
+};
+
+use std::path::Path;
+
+use serde::{Deserialize, Deserializer, Serialize};
+
+/// List of shape keys of meshes, as exported by the Blender plugin.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShapeKeyInventory {
+    /// Avatar name.
+    pub name: String,
+
+    /// Meshes.
+    pub meshes: Vec<InventoryMesh>,
+}
+
+impl ShapeKeyInventory {
+    fn from_raw<'de, D>(raw: RawInventory) -> Result<ShapeKeyInventory, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let meshes = raw
+            .meshes
+            .into_iter()
+            .flatten()
+            .map(|m| InventoryMesh::from_raw::<'de, D>(m))
+            .collect::<Result<_, _>>()?;
+        Ok(ShapeKeyInventory {
+            name: raw.name,
+            meshes,
+        })
+    }
+
+    /// Loads an inventory file.
+    pub fn load(path: impl AsRef<Path>) -> Result<ShapeKeyInventory, LoadError> {
+        parse_file(path.as_ref())
+    }
+
+    /// Makes a starter descriptor which has a shape key group for each mesh.
+    pub fn into_descriptor(self) -> Descriptor {
+        self.meshes
+            .into_iter()
+            .enumerate()
+            .fold(Descriptor::builder(self.name), |descriptor, (i, mesh)| {
+                descriptor.shape_group(mesh.into_group(i))
+            })
+            .build()
+    }
+}
+
+impl<'de> Deserialize<'de> for ShapeKeyInventory {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawInventory::deserialize(deserializer)?;
+        ShapeKeyInventory::from_raw::<'de, D>(raw)
+    }
+}
+
+/// Shape keys of a mesh.
+#[derive(Debug, Clone, Serialize)]
+pub struct InventoryMesh {
+    /// SkinnedMeshRenderer name.
+    pub name: String,
+
+    /// Shape key names except the basis.
+    pub shapes: Vec<String>,
+}
+
+impl InventoryMesh {
+    fn from_raw<'de, D>(raw: RawInventoryMesh) -> Result<InventoryMesh, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(InventoryMesh {
+            name: raw.name,
+            shapes: raw.shapes,
+        })
+    }
+
+    /// Makes a group whose options select each shape key.
+    fn into_group(self, index: usize) -> ShapeKeyGroup {
+        // Mesh names like "Body.001" cannot be used for parameter names.
+        let mut group_name: String = self.name.chars().filter(|&c| is_name_char(c)).collect();
+        if group_name.is_empty() {
+            group_name = format!("Mesh{index}");
+        }
+
+        self.shapes
+            .into_iter()
+            .fold(
+                ShapeKeyGroup::builder(group_name).mesh(self.name),
+                |group, shape| group.option(ShapeKeyOption::new(shape)),
+            )
+            .build()
+    }
+}
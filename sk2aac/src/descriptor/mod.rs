@@ -1,33 +1,69 @@
+mod builder;
+mod diagnostic;
+mod format;
+mod identifier;
+mod include;
+mod inventory;
 mod raw;
 mod validation;
 
-pub use self::validation::{validate_descriptor, ValidationError, ValidationResult};
+pub use self::{
+    builder::{DescriptorBuilder, DriverBuilder, ShapeKeyGroupBuilder, ShapeKeySwitchBuilder},
+    diagnostic::{Diagnostic, Diagnostics, Severity, Span},
+    format::{format_file, FormatError},
+    identifier::{is_name_char, to_identifier},
+    include::{DescriptorSources, LoadError},
+    inventory::{InventoryMesh, ShapeKeyInventory},
+    validation::{
+        validate_descriptor, ValidatedDescriptor, ValidationError, ValidationWarning,
+        MENU_MAX_CONTROLS,
+    },
+};
+
+pub(crate) use self::include::parse_file;
+
+use toml::Spanned;
 
 use crate::descriptor::raw::{
-    RawDescriptor, RawDrive, RawDriver, RawDriverOption, RawShapeKeyCommon, RawShapeKeyDrive,
-    RawShapeKeyGroup, RawShapeKeyOption, RawShapeKeySwitch,
+    RawDescriptor, RawDrive, RawDriver, RawDriverOption, RawGesture, RawGestureHand, RawGestureMap,
+    RawGestureMapping, RawGesturePriority, RawMaterialSwap, RawMenuSettings, RawMeshes,
+    RawObjectToggle, RawParameterDefault, RawParameterSettings, RawPuppetBlendType,
+    RawPuppetSample, RawShapeKeyCommon, RawShapeKeyDrive, RawShapeKeyDriveTable, RawShapeKeyGroup,
+    RawShapeKeyOption, RawShapeKeyOptionTable, RawShapeKeyPuppet, RawShapeKeySlider,
+    RawShapeKeySwitch,
 };
 
-use std::num::NonZeroUsize;
-
-use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+use serde::{ser::SerializeMap, Deserialize, Deserializer, Serialize, Serializer};
 
-/// Normalized float value in [0, 1].
+/// Float value expected in [0, 1]. The range is checked by validation.
 #[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
 pub struct NormalizedF64(pub f64);
 
 impl NormalizedF64 {
-    pub fn new(v: f64) -> Option<NormalizedF64> {
-        if v >= 0.0 && v <= 1.0 {
-            Some(NormalizedF64(v as f64))
-        } else {
-            None
-        }
+    /// Checks whether the value is in [0, 1].
+    pub fn is_valid(&self) -> bool {
+        (0.0..=1.0).contains(&self.0)
     }
 
     pub const fn get(&self) -> f64 {
         self.0
     }
+
+    /// Takes a value with its span, or the default value without span.
+    fn with_span(value: Option<(f64, Span)>, default: f64) -> (NormalizedF64, Option<Span>) {
+        match value {
+            Some((value, span)) => (NormalizedF64(value), Some(span)),
+            None => (NormalizedF64(default), None),
+        }
+    }
+
+    fn is_zero(&self) -> bool {
+        self.0 == 0.0
+    }
+
+    fn is_one(&self) -> bool {
+        self.0 == 1.0
+    }
 }
 
 impl Serialize for NormalizedF64 {
@@ -39,20 +75,102 @@ impl Serialize for NormalizedF64 {
     }
 }
 
+/// Value and span of a deserialized number.
+fn spanned_f64(value: &Spanned<f64>) -> (f64, Span) {
+    (*value.get_ref(), Span::of(value))
+}
+
+fn is_false(value: &bool) -> bool {
+    !value
+}
+
+fn is_true(value: &bool) -> bool {
+    *value
+}
+
+fn is_zero(value: &f64) -> bool {
+    *value == 0.0
+}
+
+/// Writes a single mesh as a string.
+fn serialize_meshes<S>(meshes: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    match meshes {
+        [mesh] => serializer.serialize_str(mesh),
+        _ => meshes.serialize(serializer),
+    }
+}
+
+/// Writes integral values as integers.
+fn serialize_parameter_default<S>(value: &f64, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    if value.fract() == 0.0 {
+        serializer.serialize_i64(*value as i64)
+    } else {
+        serializer.serialize_f64(*value)
+    }
+}
+
+fn serialize_menu_path<S>(path: &[String], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.serialize_str(&path.join("/"))
+}
+
 /// Represents a whole descriptor object.
 #[derive(Debug, Clone, Serialize)]
 pub struct Descriptor {
     /// Avatar name.
     pub name: String,
 
-    /// Shape key switces.
-    pub shape_switches: Vec<ShapeKeySwitch>,
+    /// Span of the avatar name.
+    #[serde(skip)]
+    pub span: Span,
+
+    /// Included descriptor files, relative to this file.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub includes: Vec<String>,
+
+    /// Maximum bits of synced Expression Parameters. The VRChat limit is used if omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sync_budget: Option<usize>,
 
     /// Shape key groups.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub shape_groups: Vec<ShapeKeyGroup>,
 
+    /// Shape key switces.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shape_switches: Vec<ShapeKeySwitch>,
+
+    /// Shape key sliders.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shape_sliders: Vec<ShapeKeySlider>,
+
+    /// Shape key puppets.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub shape_puppets: Vec<ShapeKeyPuppet>,
+
+    /// GameObject toggles.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub object_toggles: Vec<ObjectToggle>,
+
     /// Parameter driver layers.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub drivers: Vec<Driver>,
+
+    /// Hand gesture layers.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub gesture_maps: Vec<GestureMap>,
+
+    /// Source files of the layers, filled by `Descriptor::load`.
+    #[serde(skip)]
+    pub sources: DescriptorSources,
 }
 
 impl Descriptor {
@@ -72,18 +190,50 @@ impl Descriptor {
             .flatten()
             .map(|s| ShapeKeyGroup::from_raw::<'de, D>(s))
             .collect::<Result<_, _>>()?;
+        let shape_sliders = raw
+            .shape_sliders
+            .into_iter()
+            .flatten()
+            .map(|s| ShapeKeySlider::from_raw::<'de, D>(s))
+            .collect::<Result<_, _>>()?;
+        let shape_puppets = raw
+            .shape_puppets
+            .into_iter()
+            .flatten()
+            .map(|s| ShapeKeyPuppet::from_raw::<'de, D>(s))
+            .collect::<Result<_, _>>()?;
+        let object_toggles = raw
+            .object_toggles
+            .into_iter()
+            .flatten()
+            .map(|t| ObjectToggle::from_raw::<'de, D>(t))
+            .collect::<Result<_, _>>()?;
         let drivers = raw
             .drivers
             .into_iter()
             .flatten()
             .map(|s| Driver::from_raw::<'de, D>(s))
             .collect::<Result<_, _>>()?;
+        let gesture_maps = raw
+            .gesture_maps
+            .into_iter()
+            .flatten()
+            .map(|g| GestureMap::from_raw::<'de, D>(g))
+            .collect::<Result<_, _>>()?;
 
         Ok(Descriptor {
-            name: raw.name,
+            span: Span::of(&raw.name),
+            name: raw.name.into_inner(),
+            includes: raw.includes.unwrap_or_default(),
+            sync_budget: raw.sync_budget,
             shape_switches,
             shape_groups,
+            shape_sliders,
+            shape_puppets,
+            object_toggles,
             drivers,
+            gesture_maps,
+            sources: DescriptorSources::default(),
         })
     }
 }
@@ -105,14 +255,29 @@ pub struct ShapeKeyCommon {
     /// Name used for both the layer and its Expression Parameter.
     pub name: String,
 
-    /// Referencing SkinnedMeshRenderer name.
-    pub mesh: String,
+    /// Span of the name.
+    #[serde(skip)]
+    pub span: Span,
+
+    /// Referencing SkinnedMeshRenderer names. The first one is the primary mesh.
+    #[serde(serialize_with = "serialize_meshes")]
+    pub mesh: Vec<String>,
 
     /// Decides whether this layer prevents the eyelids animation.
+    #[serde(skip_serializing_if = "is_false")]
     pub prevent_eyelids: bool,
 
     /// Decides whether this layer prevents the mouth animation.
+    #[serde(skip_serializing_if = "is_false")]
     pub prevent_mouth: bool,
+
+    /// Expressions Menu settings.
+    #[serde(skip_serializing_if = "MenuSettings::is_empty")]
+    pub menu: MenuSettings,
+
+    /// Expression Parameter settings.
+    #[serde(flatten)]
+    pub parameter: ParameterSettings,
 }
 
 impl ShapeKeyCommon {
@@ -120,23 +285,127 @@ impl ShapeKeyCommon {
     where
         D: Deserializer<'de>,
     {
+        let mesh = match raw.mesh {
+            RawMeshes::Single(mesh) => vec![mesh],
+            RawMeshes::Multiple(meshes) => meshes,
+        };
         Ok(ShapeKeyCommon {
-            name: raw.name,
-            mesh: raw.mesh,
+            span: Span::of(&raw.name),
+            name: raw.name.into_inner(),
+            mesh,
             prevent_eyelids: raw.prevent_eyelids.unwrap_or(false),
             prevent_mouth: raw.prevent_mouth.unwrap_or(false),
+            menu: MenuSettings::from_raw::<'de, D>(raw.menu)?,
+            parameter: ParameterSettings::from_raw::<'de, D>(raw.parameter)?,
         })
     }
 }
 
-impl<'de> Deserialize<'de> for ShapeKeyCommon {
-    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+/// Expression Parameter settings of a layer.
+#[derive(Debug, Clone, Copy, Serialize)]
+pub struct ParameterSettings {
+    /// Decides whether the value is kept between worlds.
+    #[serde(skip_serializing_if = "is_true")]
+    pub saved: bool,
+
+    /// Decides whether the value is synced over the network.
+    #[serde(skip_serializing_if = "is_true")]
+    pub synced: bool,
+
+    /// Default value. Bool values are represented as 0.0 or 1.0.
+    #[serde(
+        skip_serializing_if = "is_zero",
+        serialize_with = "serialize_parameter_default"
+    )]
+    pub default: f64,
+
+    /// Span of the default value if specified.
+    #[serde(skip)]
+    pub span: Option<Span>,
+}
+
+impl Default for ParameterSettings {
+    fn default() -> ParameterSettings {
+        ParameterSettings {
+            saved: true,
+            synced: true,
+            default: 0.0,
+            span: None,
+        }
+    }
+}
+
+impl ParameterSettings {
+    fn from_raw<'de, D>(raw: RawParameterSettings) -> Result<ParameterSettings, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let span = raw.default.as_ref().map(Span::of);
+        let default = match raw.default.map(|d| d.into_inner()) {
+            Some(RawParameterDefault::Bool(true)) => 1.0,
+            Some(RawParameterDefault::Bool(false)) => 0.0,
+            Some(RawParameterDefault::Number(v)) => v,
+            None => 0.0,
+        };
+        Ok(ParameterSettings {
+            saved: raw.saved.unwrap_or(true),
+            synced: raw.synced.unwrap_or(true),
+            default,
+            span,
+        })
+    }
+}
+
+/// Expressions Menu settings of a layer.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MenuSettings {
+    /// Control name. The layer name is used if omitted.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+
+    /// Asset path of the icon texture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
+    /// Sub-menu names from the root menu.
+    #[serde(
+        skip_serializing_if = "Vec::is_empty",
+        serialize_with = "serialize_menu_path"
+    )]
+    pub path: Vec<String>,
+}
+
+impl MenuSettings {
+    fn from_raw<'de, D>(raw: Option<RawMenuSettings>) -> Result<MenuSettings, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let raw = RawShapeKeyCommon::deserialize(deserializer)?;
-        let skc = ShapeKeyCommon::from_raw::<'de, D>(raw)?;
-        Ok(skc)
+        let raw = match raw {
+            Some(raw) => raw,
+            None => return Ok(MenuSettings::default()),
+        };
+        let path = raw
+            .path
+            .iter()
+            .flat_map(|p| p.split('/'))
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_string())
+            .collect();
+        Ok(MenuSettings {
+            label: raw.label,
+            icon: raw.icon,
+            path,
+        })
+    }
+
+    /// Checks whether all settings are omitted.
+    pub fn is_empty(&self) -> bool {
+        self.label.is_none() && self.icon.is_none() && self.path.is_empty()
+    }
+
+    /// Control name of the layer.
+    pub fn label_or<'a>(&'a self, name: &'a str) -> &'a str {
+        self.label.as_deref().unwrap_or(name)
     }
 }
 
@@ -151,10 +420,20 @@ pub struct ShapeKeySwitch {
     pub shape: String,
 
     /// The value on enabled.
+    #[serde(skip_serializing_if = "NormalizedF64::is_one")]
     pub enabled_value: NormalizedF64,
 
     /// The value on disabled.
+    #[serde(skip_serializing_if = "NormalizedF64::is_zero")]
     pub disabled_value: NormalizedF64,
+
+    /// Span of `enabled_value` if specified.
+    #[serde(skip)]
+    pub enabled_value_span: Option<Span>,
+
+    /// Span of `disabled_value` if specified.
+    #[serde(skip)]
+    pub disabled_value_span: Option<Span>,
 }
 
 impl ShapeKeySwitch {
@@ -162,26 +441,19 @@ impl ShapeKeySwitch {
     where
         D: Deserializer<'de>,
     {
-        let common = ShapeKeyCommon {
-            name: raw.common.name,
-            mesh: raw.common.mesh,
-            prevent_eyelids: raw.common.prevent_eyelids.unwrap_or(false),
-            prevent_mouth: raw.common.prevent_mouth.unwrap_or(false),
-        };
-        let enabled_value = match NormalizedF64::new(raw.enabled_value.unwrap_or(1.0)) {
-            Some(v) => v,
-            None => return Err(D::Error::custom("enabled_value out of range")),
-        };
-        let disabled_value = match NormalizedF64::new(raw.disabled_value.unwrap_or(0.0)) {
-            Some(v) => v,
-            None => return Err(D::Error::custom("disabled_value out of range")),
-        };
-
+        let (common, raw) = raw.split();
+        let common = ShapeKeyCommon::from_raw::<'de, D>(common)?;
+        let (enabled_value, enabled_value_span) =
+            NormalizedF64::with_span(raw.enabled_value.as_ref().map(spanned_f64), 1.0);
+        let (disabled_value, disabled_value_span) =
+            NormalizedF64::with_span(raw.disabled_value.as_ref().map(spanned_f64), 0.0);
         Ok(ShapeKeySwitch {
             common,
             shape: raw.shape,
             enabled_value,
             disabled_value,
+            enabled_value_span,
+            disabled_value_span,
         })
     }
 }
@@ -204,7 +476,13 @@ pub struct ShapeKeyGroup {
     #[serde(flatten)]
     pub common: ShapeKeyCommon,
 
+    /// Syncs the index as Bool parameters, one for each bit, instead of an Int parameter.
+    /// The Int parameter is kept local for the menu.
+    #[serde(skip_serializing_if = "is_false")]
+    pub bit_packed: bool,
+
     /// Default shape key values.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
     pub defaults: Vec<ShapeKeyDrive>,
 
     /// Group options.
@@ -216,30 +494,82 @@ impl ShapeKeyGroup {
     where
         D: Deserializer<'de>,
     {
-        let common = ShapeKeyCommon {
-            name: raw.common.name,
-            mesh: raw.common.mesh,
-            prevent_eyelids: raw.common.prevent_eyelids.unwrap_or(false),
-            prevent_mouth: raw.common.prevent_mouth.unwrap_or(false),
-        };
+        let (common, raw) = raw.split();
+        let common = ShapeKeyCommon::from_raw::<'de, D>(common)?;
         let defaults = raw
             .defaults
             .into_iter()
             .flatten()
-            .map(|d| ShapeKeyDrive::from_raw::<'de, D>(d, 1.0))
+            .map(|d| ShapeKeyDrive::from_raw::<'de, D>(d, None))
             .collect::<Result<_, _>>()?;
         let options = raw
             .options
             .into_iter()
             .flatten()
-            .map(|o| ShapeKeyOption::from_raw::<'de, D>(o))
+            .map(|o| {
+                let span = Span::of(&o);
+                ShapeKeyOption::from_raw::<'de, D>(o.into_inner(), span)
+            })
             .collect::<Result<_, _>>()?;
         Ok(ShapeKeyGroup {
             common,
+            bit_packed: raw.bit_packed.unwrap_or(false),
             defaults,
             options,
         })
     }
+
+    /// Index of each option.
+    pub fn option_indices(&self) -> impl Iterator<Item = usize> + '_ {
+        self.options
+            .iter()
+            .enumerate()
+            .map(|(i, o)| o.index.unwrap_or(i + 1))
+    }
+
+    /// Bool parameters of the index from the least significant bit, e.g. `Eyelids_Bit0`.
+    /// Empty unless `bit_packed`.
+    pub fn bit_parameters(&self) -> Vec<String> {
+        if !self.bit_packed {
+            return vec![];
+        }
+        let max_index = self.option_indices().max().unwrap_or(0);
+        let bits = (usize::BITS - max_index.leading_zeros()).max(1);
+        (0..bits)
+            .map(|i| format!("{}_Bit{i}", self.common.name))
+            .collect()
+    }
+
+    /// Name of the layer which encodes the index into the bits, if `bit_packed`.
+    pub fn encoder_layer_name(&self) -> Option<String> {
+        self.bit_packed
+            .then(|| format!("{}_Encoder", self.common.name))
+    }
+
+    /// Drives which select the option of the index.
+    /// Bit-packed groups also drive the bits, so the local index stays in sync with them.
+    pub fn index_drives(&self, index: usize) -> Vec<ResolvedDrive> {
+        let integer = ResolvedDrive::Integer {
+            name: self.common.name.clone(),
+            index,
+        };
+        [integer]
+            .into_iter()
+            .chain(self.bit_drives(index))
+            .collect()
+    }
+
+    /// Drives of the bits for the index. Empty unless `bit_packed`.
+    pub fn bit_drives(&self, index: usize) -> Vec<ResolvedDrive> {
+        self.bit_parameters()
+            .into_iter()
+            .enumerate()
+            .map(|(i, name)| ResolvedDrive::Bool {
+                name,
+                enabled: (index >> i) & 1 == 1,
+            })
+            .collect()
+    }
 }
 
 impl<'de> Deserialize<'de> for ShapeKeyGroup {
@@ -253,65 +583,300 @@ impl<'de> Deserialize<'de> for ShapeKeyGroup {
     }
 }
 
-/// A option in `ShapeKeyGroup`.
+/// Represents a shape key slider layer driven by a Float parameter.
+#[derive(Debug, Clone, Serialize)]
+pub struct ShapeKeySlider {
+    /// Common part.
+    #[serde(flatten)]
+    pub common: ShapeKeyCommon,
+
+    /// Shape key values at the minimum of the parameter.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub defaults: Vec<ShapeKeyDrive>,
+
+    /// Shape key values at the maximum of the parameter.
+    pub shapes: Vec<ShapeKeyDrive>,
+}
+
+impl ShapeKeySlider {
+    fn from_raw<'de, D>(raw: RawShapeKeySlider) -> Result<ShapeKeySlider, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (common, raw) = raw.split();
+        let common = ShapeKeyCommon::from_raw::<'de, D>(common)?;
+        let defaults = raw
+            .defaults
+            .into_iter()
+            .flatten()
+            .map(|d| ShapeKeyDrive::from_raw::<'de, D>(d, None))
+            .collect::<Result<_, _>>()?;
+        let shapes = raw
+            .shapes
+            .into_iter()
+            .map(|d| ShapeKeyDrive::from_raw::<'de, D>(d, None))
+            .collect::<Result<_, _>>()?;
+        Ok(ShapeKeySlider {
+            common,
+            defaults,
+            shapes,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ShapeKeySlider {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawShapeKeySlider::deserialize(deserializer)?;
+        let sks = ShapeKeySlider::from_raw::<'de, D>(raw)?;
+        Ok(sks)
+    }
+}
+
+/// Represents a shape key puppet layer driven by two Float parameters.
 #[derive(Debug, Clone, Serialize)]
+pub struct ShapeKeyPuppet {
+    /// Common part. The name is used only for the layer.
+    #[serde(flatten)]
+    pub common: ShapeKeyCommon,
+
+    /// Float parameter name for X axis.
+    pub parameter_x: String,
+
+    /// Float parameter name for Y axis.
+    pub parameter_y: String,
+
+    /// Blend type of the 2D blend tree.
+    #[serde(skip_serializing_if = "PuppetBlendType::is_default")]
+    pub blend_type: PuppetBlendType,
+
+    /// Default shape key values.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub defaults: Vec<ShapeKeyDrive>,
+
+    /// Sample points.
+    pub samples: Vec<PuppetSample>,
+}
+
+impl ShapeKeyPuppet {
+    fn from_raw<'de, D>(raw: RawShapeKeyPuppet) -> Result<ShapeKeyPuppet, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (common, raw) = raw.split();
+        let common = ShapeKeyCommon::from_raw::<'de, D>(common)?;
+        let blend_type = match raw.blend_type {
+            Some(RawPuppetBlendType::SimpleDirectional) => PuppetBlendType::SimpleDirectional,
+            Some(RawPuppetBlendType::FreeformDirectional) | None => {
+                PuppetBlendType::FreeformDirectional
+            }
+            Some(RawPuppetBlendType::FreeformCartesian) => PuppetBlendType::FreeformCartesian,
+        };
+        let defaults = raw
+            .defaults
+            .into_iter()
+            .flatten()
+            .map(|d| ShapeKeyDrive::from_raw::<'de, D>(d, None))
+            .collect::<Result<_, _>>()?;
+        let samples = raw
+            .samples
+            .into_iter()
+            .map(|s| {
+                let span = Span::of(&s);
+                PuppetSample::from_raw::<'de, D>(s.into_inner(), span)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(ShapeKeyPuppet {
+            common,
+            parameter_x: raw.parameter_x,
+            parameter_y: raw.parameter_y,
+            blend_type,
+            defaults,
+            samples,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ShapeKeyPuppet {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawShapeKeyPuppet::deserialize(deserializer)?;
+        let skp = ShapeKeyPuppet::from_raw::<'de, D>(raw)?;
+        Ok(skp)
+    }
+}
+
+/// Blend type of `ShapeKeyPuppet`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PuppetBlendType {
+    /// 2D Simple Directional.
+    SimpleDirectional,
+
+    /// 2D Freeform Directional.
+    FreeformDirectional,
+
+    /// 2D Freeform Cartesian.
+    FreeformCartesian,
+}
+
+impl PuppetBlendType {
+    fn is_default(&self) -> bool {
+        *self == PuppetBlendType::FreeformDirectional
+    }
+}
+
+/// A sample point in `ShapeKeyPuppet`.
+#[derive(Debug, Clone, Serialize)]
+pub struct PuppetSample {
+    /// X position.
+    pub x: f64,
+
+    /// Y position.
+    pub y: f64,
+
+    /// Shape keys to move.
+    pub shapes: Vec<ShapeKeyDrive>,
+
+    /// Span of the sample.
+    #[serde(skip)]
+    pub span: Span,
+}
+
+impl PuppetSample {
+    fn from_raw<'de, D>(raw: RawPuppetSample, span: Span) -> Result<PuppetSample, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let shapes = raw
+            .shapes
+            .into_iter()
+            .map(|s| ShapeKeyDrive::from_raw::<'de, D>(s, None))
+            .collect::<Result<_, _>>()?;
+        Ok(PuppetSample {
+            x: raw.x,
+            y: raw.y,
+            shapes,
+            span,
+        })
+    }
+}
+
+/// A option in `ShapeKeyGroup`.
+#[derive(Debug, Clone)]
 pub struct ShapeKeyOption {
     /// Option label.
     pub label: String,
 
     /// Index value for Unity AnimatorController State.
-    pub index: Option<NonZeroUsize>,
+    pub index: Option<usize>,
+
+    /// Asset path of the menu icon texture.
+    pub icon: Option<String>,
 
     /// Shape keys to move.
     pub shapes: Vec<ShapeKeyDrive>,
+
+    /// Materials to swap.
+    pub materials: Vec<MaterialSwap>,
+
+    /// Span of the option.
+    pub span: Span,
 }
 
 impl ShapeKeyOption {
-    fn from_raw<'de, D>(raw: RawShapeKeyOption) -> Result<ShapeKeyOption, D::Error>
+    fn from_raw<'de, D>(raw: RawShapeKeyOption, span: Span) -> Result<ShapeKeyOption, D::Error>
     where
         D: Deserializer<'de>,
     {
         let sko = match raw {
-            RawShapeKeyOption::Simple(label) => {
-                let shapes = vec![ShapeKeyDrive::new(&label)];
-                ShapeKeyOption {
-                    label,
-                    index: None,
-                    shapes,
-                }
-            }
-            RawShapeKeyOption::Complex {
+            RawShapeKeyOption::Simple(label) => ShapeKeyOption {
+                span,
+                ..ShapeKeyOption::new(label)
+            },
+            RawShapeKeyOption::Complex(RawShapeKeyOptionTable {
                 label,
                 value,
                 index,
+                icon,
                 shapes,
-            } => {
-                let default_value = value.unwrap_or(1.0);
-                let index = match index {
-                    Some(i) => {
-                        let inner = NonZeroUsize::new(i)
-                            .ok_or(D::Error::custom("Index must be non-zero"))?;
-                        Some(inner)
-                    }
-                    None => None,
-                };
-                let shapes = match shapes {
-                    Some(sv) => sv
+                materials,
+            }) => {
+                let default_value = value.as_ref().map(spanned_f64);
+                let shapes = match (shapes, &materials) {
+                    (Some(sv), _) => sv
                         .into_iter()
                         .map(|s| ShapeKeyDrive::from_raw::<'de, D>(s, default_value))
                         .collect::<Result<_, _>>()?,
-                    None => vec![ShapeKeyDrive::new(&label)],
+                    // Material-only option
+                    (None, Some(_)) => vec![],
+                    (None, None) => vec![ShapeKeyDrive::new(&label)],
                 };
+                let materials = materials
+                    .into_iter()
+                    .flatten()
+                    .map(|m| MaterialSwap::from_raw::<'de, D>(m))
+                    .collect::<Result<_, _>>()?;
 
                 ShapeKeyOption {
                     label,
                     index,
+                    icon,
                     shapes,
+                    materials,
+                    span,
                 }
             }
         };
         Ok(sko)
     }
+
+    /// Returns whether `shapes` can be omitted.
+    fn has_implicit_shapes(&self) -> bool {
+        match &self.shapes[..] {
+            [] => !self.materials.is_empty(),
+            [drive] if self.materials.is_empty() && drive.shape == self.label => {
+                drive.mesh.is_none() && drive.value.is_one()
+            }
+            _ => false,
+        }
+    }
+}
+
+impl Serialize for ShapeKeyOption {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let implicit_shapes = self.has_implicit_shapes();
+        let is_simple = implicit_shapes
+            && self.index.is_none()
+            && self.icon.is_none()
+            && self.materials.is_empty();
+        if is_simple {
+            return serializer.serialize_str(&self.label);
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("label", &self.label)?;
+        if let Some(index) = self.index {
+            map.serialize_entry("index", &index)?;
+        }
+        if let Some(icon) = &self.icon {
+            map.serialize_entry("icon", icon)?;
+        }
+        if !implicit_shapes {
+            map.serialize_entry("shapes", &self.shapes)?;
+        }
+        if !self.materials.is_empty() {
+            map.serialize_entry("materials", &self.materials)?;
+        }
+        map.end()
+    }
 }
 
 impl<'de> Deserialize<'de> for ShapeKeyOption {
@@ -319,50 +884,100 @@ impl<'de> Deserialize<'de> for ShapeKeyOption {
     where
         D: Deserializer<'de>,
     {
-        let raw = RawShapeKeyOption::deserialize(deserializer)?;
-        let option = ShapeKeyOption::from_raw::<'de, D>(raw)?;
+        let raw = Spanned::<RawShapeKeyOption>::deserialize(deserializer)?;
+        let span = Span::of(&raw);
+        let option = ShapeKeyOption::from_raw::<'de, D>(raw.into_inner(), span)?;
         Ok(option)
     }
 }
 
-/// Drive information of a shape key.
+/// Material swap information of a renderer slot.
 #[derive(Debug, Clone, Serialize)]
+pub struct MaterialSwap {
+    /// Material slot index.
+    pub slot: usize,
+
+    /// Asset path of the material.
+    pub material: String,
+
+    /// Asset path of the material originally set to the slot. Required by the Unity backend.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub original: Option<String>,
+}
+
+impl MaterialSwap {
+    fn from_raw<'de, D>(raw: RawMaterialSwap) -> Result<MaterialSwap, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        Ok(MaterialSwap {
+            slot: raw.slot,
+            material: raw.material,
+            original: raw.original,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for MaterialSwap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawMaterialSwap::deserialize(deserializer)?;
+        MaterialSwap::from_raw::<'de, D>(raw)
+    }
+}
+
+/// Drive information of a shape key.
+#[derive(Debug, Clone)]
 pub struct ShapeKeyDrive {
     /// Shape key name.
     pub shape: String,
 
     /// Shape key value.
     pub value: NormalizedF64,
+
+    /// Target mesh. If omitted, all meshes of the layer are driven.
+    pub mesh: Option<String>,
+
+    /// Span of the value if specified, which may be the value of the option.
+    pub span: Option<Span>,
 }
 
 impl ShapeKeyDrive {
     /// Creates new instance with default options.
-    fn new(label: &str) -> ShapeKeyDrive {
+    pub fn new(shape: impl Into<String>) -> ShapeKeyDrive {
         ShapeKeyDrive {
-            shape: label.to_string(),
-            value: NormalizedF64::new(1.0).expect("Should be valid"),
+            shape: shape.into(),
+            value: NormalizedF64(1.0),
+            mesh: None,
+            span: None,
         }
     }
 
+    /// Uses `default_value` if the value is omitted, and 1.0 if both are omitted.
     fn with_default_value<'de, D>(
         shape: String,
-        value: Option<f64>,
-        default_value: f64,
+        value: Option<Spanned<f64>>,
+        default_value: Option<(f64, Span)>,
+        mesh: Option<String>,
     ) -> Result<ShapeKeyDrive, D::Error>
     where
         D: Deserializer<'de>,
     {
-        let value = NormalizedF64::new(value.unwrap_or(default_value))
-            .ok_or(D::Error::custom("Drive value out of range"))?;
+        let value = value.as_ref().map(spanned_f64).or(default_value);
+        let (value, span) = NormalizedF64::with_span(value, 1.0);
         Ok(ShapeKeyDrive {
             shape: shape.to_string(),
             value,
+            mesh,
+            span,
         })
     }
 
     fn from_raw<'de, D>(
         raw: RawShapeKeyDrive,
-        default_value: f64,
+        default_value: Option<(f64, Span)>,
     ) -> Result<ShapeKeyDrive, D::Error>
     where
         D: Deserializer<'de>,
@@ -372,33 +987,115 @@ impl ShapeKeyDrive {
                 let skd = ShapeKeyDrive::new(&shape);
                 Ok(skd)
             }
-            RawShapeKeyDrive::Complex { shape, value } => {
-                let skd = ShapeKeyDrive::with_default_value::<'de, D>(shape, value, default_value)?;
+            RawShapeKeyDrive::Complex(RawShapeKeyDriveTable { shape, value, mesh }) => {
+                let skd =
+                    ShapeKeyDrive::with_default_value::<'de, D>(shape, value, default_value, mesh)?;
                 Ok(skd)
             }
         }
     }
 }
 
+impl Serialize for ShapeKeyDrive {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        if self.value.is_one() && self.mesh.is_none() {
+            return serializer.serialize_str(&self.shape);
+        }
+
+        let mut map = serializer.serialize_map(None)?;
+        map.serialize_entry("shape", &self.shape)?;
+        if !self.value.is_one() {
+            map.serialize_entry("value", &self.value)?;
+        }
+        if let Some(mesh) = &self.mesh {
+            map.serialize_entry("mesh", mesh)?;
+        }
+        map.end()
+    }
+}
+
 impl<'de> Deserialize<'de> for ShapeKeyDrive {
     fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
     where
         D: Deserializer<'de>,
     {
         let raw = RawShapeKeyDrive::deserialize(deserializer)?;
-        let drive = ShapeKeyDrive::from_raw::<'de, D>(raw, 1.0)?;
+        let drive = ShapeKeyDrive::from_raw::<'de, D>(raw, None)?;
         Ok(drive)
     }
 }
 
+/// Represents a GameObject toggle layer.
+#[derive(Debug, Clone, Serialize)]
+pub struct ObjectToggle {
+    /// Name used for both the layer and its Expression Parameter.
+    pub name: String,
+
+    /// Span of the name.
+    #[serde(skip)]
+    pub span: Span,
+
+    /// Transform paths of target GameObjects.
+    pub objects: Vec<String>,
+
+    /// Expressions Menu settings.
+    #[serde(skip_serializing_if = "MenuSettings::is_empty")]
+    pub menu: MenuSettings,
+
+    /// Expression Parameter settings.
+    #[serde(flatten)]
+    pub parameter: ParameterSettings,
+}
+
+impl ObjectToggle {
+    fn from_raw<'de, D>(raw: RawObjectToggle) -> Result<ObjectToggle, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let (parameter, raw) = raw.split();
+        Ok(ObjectToggle {
+            span: Span::of(&raw.name),
+            name: raw.name.into_inner(),
+            objects: raw.objects,
+            menu: MenuSettings::from_raw::<'de, D>(raw.menu)?,
+            parameter: ParameterSettings::from_raw::<'de, D>(parameter)?,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for ObjectToggle {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawObjectToggle::deserialize(deserializer)?;
+        ObjectToggle::from_raw::<'de, D>(raw)
+    }
+}
+
 /// Represents a parameter driver layer.
 #[derive(Debug, Clone, Serialize)]
 pub struct Driver {
     /// Layer name.
     pub name: String,
 
+    /// Span of the name.
+    #[serde(skip)]
+    pub span: Span,
+
     /// Driver options.
     pub options: Vec<DriverOption>,
+
+    /// Expressions Menu settings.
+    #[serde(skip_serializing_if = "MenuSettings::is_empty")]
+    pub menu: MenuSettings,
+
+    /// Expression Parameter settings.
+    #[serde(flatten)]
+    pub parameter: ParameterSettings,
 }
 
 impl Driver {
@@ -406,14 +1103,21 @@ impl Driver {
     where
         D: Deserializer<'de>,
     {
+        let (parameter, raw) = raw.split();
         let options = raw
             .options
             .into_iter()
-            .map(|o| DriverOption::from_raw::<'de, D>(o))
+            .map(|o| {
+                let span = Span::of(&o);
+                DriverOption::from_raw::<'de, D>(o.into_inner(), span)
+            })
             .collect::<Result<_, _>>()?;
         Ok(Driver {
-            name: raw.name,
+            span: Span::of(&raw.name),
+            name: raw.name.into_inner(),
             options,
+            menu: MenuSettings::from_raw::<'de, D>(raw.menu)?,
+            parameter: ParameterSettings::from_raw::<'de, D>(parameter)?,
         })
     }
 }
@@ -433,11 +1137,20 @@ impl<'de> Deserialize<'de> for Driver {
 pub struct DriverOption {
     /// Option label.
     pub label: String,
+
+    /// Asset path of the menu icon texture.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub icon: Option<String>,
+
     pub drives: Vec<Drive>,
+
+    /// Span of the option.
+    #[serde(skip)]
+    pub span: Span,
 }
 
 impl DriverOption {
-    fn from_raw<'de, D>(raw: RawDriverOption) -> Result<DriverOption, D::Error>
+    fn from_raw<'de, D>(raw: RawDriverOption, span: Span) -> Result<DriverOption, D::Error>
     where
         D: Deserializer<'de>,
     {
@@ -448,7 +1161,9 @@ impl DriverOption {
             .collect::<Result<_, _>>()?;
         let option = DriverOption {
             label: raw.label,
+            icon: raw.icon,
             drives,
+            span,
         };
         Ok(option)
     }
@@ -459,8 +1174,9 @@ impl<'de> Deserialize<'de> for DriverOption {
     where
         D: Deserializer<'de>,
     {
-        let raw = RawDriverOption::deserialize(deserializer)?;
-        DriverOption::from_raw::<'de, D>(raw)
+        let raw = Spanned::<RawDriverOption>::deserialize(deserializer)?;
+        let span = Span::of(&raw);
+        DriverOption::from_raw::<'de, D>(raw.into_inner(), span)
     }
 }
 
@@ -498,6 +1214,286 @@ impl<'de> Deserialize<'de> for Drive {
     }
 }
 
+/// Represents a layer mapping hand gestures to a shape key group.
+#[derive(Debug, Clone, Serialize)]
+pub struct GestureMap {
+    /// Layer name.
+    pub name: String,
+
+    /// Span of the name.
+    #[serde(skip)]
+    pub span: Span,
+
+    /// Target shape key group name.
+    pub group: String,
+
+    /// The hand preferred when both hands make mapped gestures.
+    #[serde(skip_serializing_if = "GesturePriority::is_default")]
+    pub priority: GesturePriority,
+
+    /// Gesture mappings.
+    pub mappings: Vec<GestureMapping>,
+}
+
+impl GestureMap {
+    fn from_raw<'de, D>(raw: RawGestureMap) -> Result<GestureMap, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let priority = match raw.priority {
+            Some(RawGesturePriority::Left) | None => GesturePriority::Left,
+            Some(RawGesturePriority::Right) => GesturePriority::Right,
+        };
+        let mappings = raw
+            .mappings
+            .into_iter()
+            .map(|m| {
+                let span = Span::of(&m);
+                GestureMapping::from_raw::<'de, D>(m.into_inner(), span)
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(GestureMap {
+            span: Span::of(&raw.name),
+            name: raw.name.into_inner(),
+            group: raw.group,
+            priority,
+            mappings,
+        })
+    }
+}
+
+impl<'de> Deserialize<'de> for GestureMap {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let raw = RawGestureMap::deserialize(deserializer)?;
+        GestureMap::from_raw::<'de, D>(raw)
+    }
+}
+
+/// A mapping from a gesture to a group option.
+#[derive(Debug, Clone, Serialize)]
+pub struct GestureMapping {
+    /// Gesture.
+    pub gesture: Gesture,
+
+    /// Hands which trigger this mapping.
+    #[serde(skip_serializing_if = "GestureHand::is_default")]
+    pub hand: GestureHand,
+
+    /// Target option label.
+    pub label: String,
+
+    /// Decides whether the option is blended by the gesture weight of the hand.
+    #[serde(skip_serializing_if = "is_false")]
+    pub weighted: bool,
+
+    /// Span of the mapping.
+    #[serde(skip)]
+    pub span: Span,
+}
+
+impl GestureMapping {
+    fn from_raw<'de, D>(raw: RawGestureMapping, span: Span) -> Result<GestureMapping, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let gesture = match raw.gesture {
+            RawGesture::Neutral => Gesture::Neutral,
+            RawGesture::Fist => Gesture::Fist,
+            RawGesture::HandOpen => Gesture::HandOpen,
+            RawGesture::FingerPoint => Gesture::FingerPoint,
+            RawGesture::Victory => Gesture::Victory,
+            RawGesture::RockNRoll => Gesture::RockNRoll,
+            RawGesture::HandGun => Gesture::HandGun,
+            RawGesture::ThumbsUp => Gesture::ThumbsUp,
+        };
+        let hand = match raw.hand {
+            Some(RawGestureHand::Left) => GestureHand::Left,
+            Some(RawGestureHand::Right) => GestureHand::Right,
+            Some(RawGestureHand::Both) | None => GestureHand::Both,
+        };
+        Ok(GestureMapping {
+            gesture,
+            hand,
+            label: raw.label,
+            weighted: raw.weighted.unwrap_or(false),
+            span,
+        })
+    }
+}
+
+/// VRChat hand gesture.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum Gesture {
+    Neutral,
+    Fist,
+    HandOpen,
+    FingerPoint,
+    Victory,
+    RockNRoll,
+    HandGun,
+    ThumbsUp,
+}
+
+impl Gesture {
+    /// Value of `GestureLeft` / `GestureRight` parameter.
+    pub const fn value(&self) -> usize {
+        match self {
+            Gesture::Neutral => 0,
+            Gesture::Fist => 1,
+            Gesture::HandOpen => 2,
+            Gesture::FingerPoint => 3,
+            Gesture::Victory => 4,
+            Gesture::RockNRoll => 5,
+            Gesture::HandGun => 6,
+            Gesture::ThumbsUp => 7,
+        }
+    }
+}
+
+/// Hands which trigger a gesture mapping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GestureHand {
+    Left,
+    Right,
+    Both,
+}
+
+impl GestureHand {
+    fn is_default(&self) -> bool {
+        *self == GestureHand::Both
+    }
+
+    /// Gesture weight parameter name of the hand.
+    pub const fn weight_parameter_name(&self) -> Option<&'static str> {
+        match self {
+            GestureHand::Left => Some("GestureLeftWeight"),
+            GestureHand::Right => Some("GestureRightWeight"),
+            GestureHand::Both => None,
+        }
+    }
+
+    /// Checks whether this includes the hand.
+    pub fn includes(&self, priority: GesturePriority) -> bool {
+        matches!(
+            (self, priority),
+            (GestureHand::Both, _)
+                | (GestureHand::Left, GesturePriority::Left)
+                | (GestureHand::Right, GesturePriority::Right)
+        )
+    }
+}
+
+/// Preferred hand of `GestureMap`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GesturePriority {
+    Left,
+    Right,
+}
+
+impl GesturePriority {
+    fn is_default(&self) -> bool {
+        *self == GesturePriority::Left
+    }
+
+    /// Returns the other hand.
+    pub const fn other(&self) -> GesturePriority {
+        match self {
+            GesturePriority::Left => GesturePriority::Right,
+            GesturePriority::Right => GesturePriority::Left,
+        }
+    }
+
+    /// Gesture parameter name of the hand.
+    pub const fn parameter_name(&self) -> &'static str {
+        match self {
+            GesturePriority::Left => "GestureLeft",
+            GesturePriority::Right => "GestureRight",
+        }
+    }
+}
+
+/// Resolved gesture map, which overlays gestures on the group.
+#[derive(Debug, Clone)]
+pub struct ResolvedGestureMap {
+    pub name: String,
+    pub group: String,
+    pub priority: GesturePriority,
+    pub states: Vec<ResolvedGestureState>,
+}
+
+impl ResolvedGestureMap {
+    /// Resolves the layer.
+    pub fn resolve(
+        descriptor: &ValidatedDescriptor,
+        gesture_map: &GestureMap,
+    ) -> ResolvedGestureMap {
+        let primary = gesture_map.priority;
+        let secondary = primary.other();
+
+        // Group name is already validated.
+        let group = descriptor
+            .shape_groups
+            .iter()
+            .find(|g| g.common.name == gesture_map.group)
+            .expect("Group not found");
+
+        let mut states: Vec<ResolvedGestureState> = vec![];
+        for mapping in &gesture_map.mappings {
+            let state = match states.iter_mut().find(|s| s.label == mapping.label) {
+                Some(s) => s,
+                None => {
+                    let index = group
+                        .options
+                        .iter()
+                        .zip(group.option_indices())
+                        .find_map(|(o, index)| (o.label == mapping.label).then_some(index))
+                        .expect("Label not found");
+                    states.push(ResolvedGestureState {
+                        label: mapping.label.clone(),
+                        index,
+                        primary: vec![],
+                        secondary: vec![],
+                    });
+                    states.last_mut().expect("Just pushed")
+                }
+            };
+            if mapping.hand.includes(primary) {
+                state.primary.push(mapping.gesture);
+            }
+            if mapping.hand.includes(secondary) {
+                state.secondary.push(mapping.gesture);
+            }
+        }
+
+        ResolvedGestureMap {
+            name: gesture_map.name.clone(),
+            group: gesture_map.group.clone(),
+            priority: primary,
+            states,
+        }
+    }
+}
+
+/// A state of resolved gesture map, which selects an option of the group.
+#[derive(Debug, Clone)]
+pub struct ResolvedGestureState {
+    pub label: String,
+
+    /// Index of the group option.
+    pub index: usize,
+
+    /// Gestures of the preferred hand.
+    pub primary: Vec<Gesture>,
+
+    /// Gestures of the other hand.
+    pub secondary: Vec<Gesture>,
+}
+
 /// Resolved driver layer.
 #[derive(Debug, Clone)]
 pub struct ResolvedDriver {
@@ -507,7 +1503,7 @@ pub struct ResolvedDriver {
 
 impl ResolvedDriver {
     /// Resolves the layer.
-    pub fn resolve(descriptor: &Descriptor, driver: &Driver) -> ResolvedDriver {
+    pub fn resolve(descriptor: &ValidatedDescriptor, driver: &Driver) -> ResolvedDriver {
         let options = driver
             .options
             .iter()
@@ -527,11 +1523,11 @@ pub struct ResolvedDriverOption {
 }
 
 impl ResolvedDriverOption {
-    fn resolve(descriptor: &Descriptor, option: &DriverOption) -> ResolvedDriverOption {
+    fn resolve(descriptor: &ValidatedDescriptor, option: &DriverOption) -> ResolvedDriverOption {
         let drives = option
             .drives
             .iter()
-            .map(|d| ResolvedDrive::resolve(descriptor, d))
+            .flat_map(|d| ResolvedDrive::resolve(descriptor, d))
             .collect();
         ResolvedDriverOption {
             label: option.label.clone(),
@@ -547,14 +1543,15 @@ pub enum ResolvedDrive {
 }
 
 impl ResolvedDrive {
-    fn resolve(descriptor: &Descriptor, drive: &Drive) -> ResolvedDrive {
+    /// Resolves the drive into parameter drives.
+    fn resolve(descriptor: &ValidatedDescriptor, drive: &Drive) -> Vec<ResolvedDrive> {
         match drive {
             Drive::Switch { name, enabled } => {
-                // Group name is already validated.
-                ResolvedDrive::Bool {
+                // Switch or toggle name is already validated.
+                vec![ResolvedDrive::Bool {
                     name: name.clone(),
                     enabled: *enabled,
-                }
+                }]
             }
             Drive::Group { name, label } => {
                 let group = descriptor
@@ -566,19 +1563,10 @@ impl ResolvedDrive {
                 let resolved_index = group
                     .options
                     .iter()
-                    .enumerate()
-                    .find_map(|(i, o)| {
-                        if &o.label == label {
-                            Some(o.index.map(|x| x.get()).unwrap_or(i + 1))
-                        } else {
-                            None
-                        }
-                    })
+                    .zip(group.option_indices())
+                    .find_map(|(o, index)| (&o.label == label).then_some(index))
                     .expect("Label not found");
-                ResolvedDrive::Integer {
-                    name: name.clone(),
-                    index: resolved_index,
-                }
+                group.index_drives(resolved_index)
             }
         }
     }
@@ -1,68 +1,275 @@
-use serde::Deserialize;
+use std::fmt::{Formatter, Result as FmtResult};
+
+use serde::{
+    de::{value::MapAccessDeserializer, Error as DeError, MapAccess, Visitor},
+    Deserialize, Deserializer,
+};
+use toml::Spanned;
+
+// Fields are not flattened because `Spanned` cannot be deserialized through
+// `#[serde(flatten)]` or `#[serde(untagged)]` containers.
+// Values written as a string or a table are deserialized by `StringOrTable` for the same reason.
+
+/// Declares a raw layer with the Expression Parameter fields, and also the fields of
+/// `RawShapeKeyCommon` for `shape_key` layers. `split` separates them from the other fields.
+macro_rules! raw_layer {
+    (shape_key $name:ident => $fields:ident { $(pub $field:ident: $ty:ty,)* }) => {
+        #[derive(Debug, Deserialize)]
+        pub struct $name {
+            pub name: Spanned<String>,
+            pub mesh: RawMeshes,
+            pub prevent_eyelids: Option<bool>,
+            pub prevent_mouth: Option<bool>,
+            pub menu: Option<RawMenuSettings>,
+            pub saved: Option<bool>,
+            pub synced: Option<bool>,
+            pub default: Option<Spanned<RawParameterDefault>>,
+            $(pub $field: $ty,)*
+        }
+
+        #[derive(Debug)]
+        pub struct $fields {
+            $(pub $field: $ty,)*
+        }
+
+        impl $name {
+            pub fn split(self) -> (RawShapeKeyCommon, $fields) {
+                let common = RawShapeKeyCommon {
+                    name: self.name,
+                    mesh: self.mesh,
+                    prevent_eyelids: self.prevent_eyelids,
+                    prevent_mouth: self.prevent_mouth,
+                    menu: self.menu,
+                    parameter: RawParameterSettings {
+                        saved: self.saved,
+                        synced: self.synced,
+                        default: self.default,
+                    },
+                };
+                (common, $fields { $($field: self.$field,)* })
+            }
+        }
+    };
+    (parameter $name:ident => $fields:ident { $(pub $field:ident: $ty:ty,)* }) => {
+        #[derive(Debug, Deserialize)]
+        pub struct $name {
+            pub saved: Option<bool>,
+            pub synced: Option<bool>,
+            pub default: Option<Spanned<RawParameterDefault>>,
+            $(pub $field: $ty,)*
+        }
+
+        #[derive(Debug)]
+        pub struct $fields {
+            $(pub $field: $ty,)*
+        }
+
+        impl $name {
+            pub fn split(self) -> (RawParameterSettings, $fields) {
+                let parameter = RawParameterSettings {
+                    saved: self.saved,
+                    synced: self.synced,
+                    default: self.default,
+                };
+                (parameter, $fields { $($field: self.$field,)* })
+            }
+        }
+    };
+}
 
 #[derive(Debug, Deserialize)]
 pub struct RawDescriptor {
-    pub name: String,
+    pub name: Spanned<String>,
+    pub includes: Option<Vec<String>>,
+    pub sync_budget: Option<usize>,
     pub shape_switches: Option<Vec<RawShapeKeySwitch>>,
     pub shape_groups: Option<Vec<RawShapeKeyGroup>>,
+    pub shape_sliders: Option<Vec<RawShapeKeySlider>>,
+    pub shape_puppets: Option<Vec<RawShapeKeyPuppet>>,
+    pub object_toggles: Option<Vec<RawObjectToggle>>,
     pub drivers: Option<Vec<RawDriver>>,
+    pub gesture_maps: Option<Vec<RawGestureMap>>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct RawShapeKeyCommon {
+#[serde(deny_unknown_fields)]
+pub struct RawIncludedDescriptor {
+    pub includes: Option<Vec<String>>,
+    pub shape_switches: Option<Vec<RawShapeKeySwitch>>,
+    pub shape_groups: Option<Vec<RawShapeKeyGroup>>,
+    pub drivers: Option<Vec<RawDriver>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawInventory {
     pub name: String,
-    pub mesh: String,
+    pub meshes: Option<Vec<RawInventoryMesh>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawInventoryMesh {
+    pub name: String,
+    pub shapes: Vec<String>,
+}
+
+/// Common part of shape key layers, assembled from the fields of each layer.
+#[derive(Debug)]
+pub struct RawShapeKeyCommon {
+    pub name: Spanned<String>,
+    pub mesh: RawMeshes,
     pub prevent_eyelids: Option<bool>,
     pub prevent_mouth: Option<bool>,
+    pub menu: Option<RawMenuSettings>,
+    pub parameter: RawParameterSettings,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct RawShapeKeySwitch {
-    #[serde(flatten)]
-    pub common: RawShapeKeyCommon,
+pub struct RawMenuSettings {
+    pub label: Option<String>,
+    pub icon: Option<String>,
+    pub path: Option<String>,
+}
 
-    pub shape: String,
-    pub enabled_value: Option<f64>,
-    pub disabled_value: Option<f64>,
+/// Expression Parameter part of layers, assembled from the fields of each layer.
+#[derive(Debug)]
+pub struct RawParameterSettings {
+    pub saved: Option<bool>,
+    pub synced: Option<bool>,
+    pub default: Option<Spanned<RawParameterDefault>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum RawParameterDefault {
+    Bool(bool),
+    Number(f64),
 }
 
 #[derive(Debug, Deserialize)]
-pub struct RawShapeKeyGroup {
-    #[serde(flatten)]
-    pub common: RawShapeKeyCommon,
+#[serde(untagged)]
+pub enum RawMeshes {
+    Single(String),
+    Multiple(Vec<String>),
+}
 
+raw_layer! { shape_key RawShapeKeySwitch => RawShapeKeySwitchFields {
+    pub shape: String,
+    pub enabled_value: Option<Spanned<f64>>,
+    pub disabled_value: Option<Spanned<f64>>,
+} }
+
+raw_layer! { shape_key RawShapeKeyGroup => RawShapeKeyGroupFields {
+    pub bit_packed: Option<bool>,
+    pub defaults: Option<Vec<RawShapeKeyDrive>>,
+    pub options: Option<Vec<Spanned<RawShapeKeyOption>>>,
+} }
+
+raw_layer! { shape_key RawShapeKeySlider => RawShapeKeySliderFields {
+    pub defaults: Option<Vec<RawShapeKeyDrive>>,
+    pub shapes: Vec<RawShapeKeyDrive>,
+} }
+
+raw_layer! { shape_key RawShapeKeyPuppet => RawShapeKeyPuppetFields {
+    pub parameter_x: String,
+    pub parameter_y: String,
+    pub blend_type: Option<RawPuppetBlendType>,
     pub defaults: Option<Vec<RawShapeKeyDrive>>,
-    pub options: Option<Vec<RawShapeKeyOption>>,
+    pub samples: Vec<Spanned<RawPuppetSample>>,
+} }
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawPuppetBlendType {
+    SimpleDirectional,
+    FreeformDirectional,
+    FreeformCartesian,
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(untagged)]
+pub struct RawPuppetSample {
+    pub x: f64,
+    pub y: f64,
+    pub shapes: Vec<RawShapeKeyDrive>,
+}
+
+#[derive(Debug)]
 pub enum RawShapeKeyOption {
     Simple(String),
-    Complex {
-        label: String,
-        value: Option<f64>,
-        index: Option<usize>,
-        shapes: Option<Vec<RawShapeKeyDrive>>,
-    },
+    Complex(RawShapeKeyOptionTable),
 }
 
 #[derive(Debug, Deserialize)]
-#[serde(untagged)]
+pub struct RawShapeKeyOptionTable {
+    pub label: String,
+    pub value: Option<Spanned<f64>>,
+    pub index: Option<usize>,
+    pub icon: Option<String>,
+    pub shapes: Option<Vec<RawShapeKeyDrive>>,
+    pub materials: Option<Vec<RawMaterialSwap>>,
+}
+
+impl<'de> Deserialize<'de> for RawShapeKeyOption {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(StringOrTable::new(
+            "an option label or table",
+            RawShapeKeyOption::Simple,
+            RawShapeKeyOption::Complex,
+        ))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawMaterialSwap {
+    pub slot: usize,
+    pub material: String,
+    pub original: Option<String>,
+}
+
+#[derive(Debug)]
 pub enum RawShapeKeyDrive {
     Simple(String),
-    Complex { shape: String, value: Option<f64> },
+    Complex(RawShapeKeyDriveTable),
 }
 
 #[derive(Debug, Deserialize)]
-pub struct RawDriver {
-    pub name: String,
-    pub options: Vec<RawDriverOption>,
+pub struct RawShapeKeyDriveTable {
+    pub shape: String,
+    pub value: Option<Spanned<f64>>,
+    pub mesh: Option<String>,
+}
+
+impl<'de> Deserialize<'de> for RawShapeKeyDrive {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        deserializer.deserialize_any(StringOrTable::new(
+            "a shape key name or table",
+            RawShapeKeyDrive::Simple,
+            RawShapeKeyDrive::Complex,
+        ))
+    }
 }
 
+raw_layer! { parameter RawObjectToggle => RawObjectToggleFields {
+    pub name: Spanned<String>,
+    pub objects: Vec<String>,
+    pub menu: Option<RawMenuSettings>,
+} }
+
+raw_layer! { parameter RawDriver => RawDriverFields {
+    pub name: Spanned<String>,
+    pub options: Vec<Spanned<RawDriverOption>>,
+    pub menu: Option<RawMenuSettings>,
+} }
+
 #[derive(Debug, Deserialize)]
 pub struct RawDriverOption {
     pub label: String,
+    pub icon: Option<String>,
     pub drives: Vec<RawDrive>,
 }
 
@@ -72,3 +279,81 @@ pub enum RawDrive {
     Switch { name: String, enabled: bool },
     Group { name: String, label: String },
 }
+
+#[derive(Debug, Deserialize)]
+pub struct RawGestureMap {
+    pub name: Spanned<String>,
+    pub group: String,
+    pub priority: Option<RawGesturePriority>,
+    pub mappings: Vec<Spanned<RawGestureMapping>>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawGesturePriority {
+    Left,
+    Right,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RawGestureMapping {
+    pub gesture: RawGesture,
+    pub hand: Option<RawGestureHand>,
+    pub label: String,
+    pub weighted: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub enum RawGesture {
+    Neutral,
+    Fist,
+    HandOpen,
+    FingerPoint,
+    Victory,
+    RockNRoll,
+    HandGun,
+    ThumbsUp,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RawGestureHand {
+    Left,
+    Right,
+    Both,
+}
+
+/// Visits a string or a table like `#[serde(untagged)]`.
+/// The table is deserialized directly from the map, so its `Spanned` fields keep the positions.
+struct StringOrTable<T, V> {
+    expecting: &'static str,
+    string: fn(String) -> V,
+    table: fn(T) -> V,
+}
+
+impl<T, V> StringOrTable<T, V> {
+    fn new(expecting: &'static str, string: fn(String) -> V, table: fn(T) -> V) -> Self {
+        StringOrTable {
+            expecting,
+            string,
+            table,
+        }
+    }
+}
+
+impl<'de, T: Deserialize<'de>, V> Visitor<'de> for StringOrTable<T, V> {
+    type Value = V;
+
+    fn expecting(&self, formatter: &mut Formatter) -> FmtResult {
+        formatter.write_str(self.expecting)
+    }
+
+    fn visit_str<E: DeError>(self, v: &str) -> Result<V, E> {
+        Ok((self.string)(v.to_string()))
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<V, A::Error> {
+        let table = T::deserialize(MapAccessDeserializer::new(map))?;
+        Ok((self.table)(table))
+    }
+}
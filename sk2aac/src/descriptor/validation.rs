@@ -1,86 +1,1079 @@
-use crate::descriptor::{Descriptor, Drive, Driver, ShapeKeyCommon, ShapeKeyGroup, ShapeKeySwitch};
+use crate::{
+    animator::SyncUsage,
+    descriptor::{
+        identifier::{is_name_char, to_identifier},
+        Descriptor, Diagnostics, Drive, Driver, Gesture, GestureHand, GestureMap, MenuSettings,
+        ObjectToggle, ParameterSettings, ShapeKeyCommon, ShapeKeyDrive, ShapeKeyGroup,
+        ShapeKeyPuppet, ShapeKeySlider, ShapeKeySwitch, Span,
+    },
+};
+
+use std::{
+    collections::HashMap,
+    ops::{Deref, RangeInclusive},
+    path::{Path, PathBuf},
+};
 
 use thiserror::Error as ThisError;
 
 #[non_exhaustive]
 #[derive(Debug, Clone, ThisError)]
 pub enum ValidationError {
-    /// Name is invalid for identifier or Expression Parameter name.
-    #[error("invalid name for an identifier: \"{0}\"")]
+    /// Name is invalid for an Expression Parameter name.
+    #[error("invalid name for a parameter: \"{0}\"")]
     InvalidName(String),
 
+    /// Different names result in the same C# identifier.
+    #[error("\"{0}\" and \"{1}\" result in the same identifier \"{2}\"")]
+    IdentifierCollision(String, String, String),
+
+    /// No mesh is specified for a layer.
+    #[error("no mesh specified for \"{0}\"")]
+    NoMesh(String),
+
     /// Name not found.
-    #[error("No group or switch found: \"{0}\"")]
+    #[error("No group, switch or toggle found: \"{0}\"")]
     NameNotExist(String),
+
+    /// Same gesture of the same hand is mapped twice.
+    #[error("gesture {1:?} is mapped twice in \"{0}\"")]
+    DuplicateGesture(String, Gesture),
+
+    /// Weighted gesture mapping does not decide a single hand.
+    #[error("weighted gesture for \"{1}\" in \"{0}\" must be mapped to one hand")]
+    AmbiguousGestureWeight(String, String),
+
+    /// Default value is invalid for the parameter type.
+    #[error("invalid default value for \"{0}\": {1}")]
+    InvalidParameterDefault(String, f64),
+
+    /// Two or more samples share the same position.
+    #[error("duplicate sample position in \"{0}\": ({1}, {2})")]
+    DuplicateSamplePosition(String, f64, f64),
+
+    /// Same name is defined in two descriptor files.
+    #[error("\"{0}\" is defined in both {} and {}", .1.display(), .2.display())]
+    DuplicateIncludedName(String, PathBuf, PathBuf),
+
+    /// Shape key value is out of [0, 1].
+    #[error("value out of range in \"{0}\": {1}")]
+    ValueOutOfRange(String, f64),
+
+    /// Option index is zero, which is used for the disabled state.
+    #[error("index of \"{1}\" in \"{0}\" must be non-zero")]
+    ZeroIndex(String, String),
+
+    /// Same layer or parameter name is defined twice.
+    #[error("\"{0}\" is defined twice")]
+    DuplicateName(String),
+
+    /// Same option label is defined twice in a layer.
+    #[error("option \"{1}\" is defined twice in \"{0}\"")]
+    DuplicateLabel(String, String),
+
+    /// Same index is used by two options.
+    #[error("index {1} is used twice in \"{0}\"")]
+    DuplicateIndex(String, usize),
+
+    /// Option index exceeds the range of Int parameters.
+    #[error("index {1} in \"{0}\" is out of range (1-255)")]
+    IndexOutOfRange(String, usize),
+
+    /// Synced Expression Parameters use more bits than the budget.
+    #[error("synced parameters use {0} bits, exceeding the budget of {1} bits")]
+    SyncBudgetExceeded(usize, usize),
+
+    /// Root Expressions Menu has more controls than VRChat allows.
+    #[error("root menu has {0} controls, exceeding the limit of {MENU_MAX_CONTROLS}; use `path` of `menu` to move them into sub-menus")]
+    RootMenuOverflow(usize),
+
+    /// Unity assets cannot refer to the material originally set in the avatar.
+    #[error(
+        "original material of slot {1} in \"{0}\" is unknown; set `original` of a material swap"
+    )]
+    UnknownOriginalMaterial(String, usize),
+
+    /// Material swaps of the same slot set different original materials.
+    #[error("different original materials are set to slot {1} in \"{0}\"")]
+    ConflictingOriginalMaterial(String, usize),
+}
+
+#[non_exhaustive]
+#[derive(Debug, Clone, ThisError)]
+pub enum ValidationWarning {
+    /// Group has no option.
+    #[error("group \"{0}\" has no options")]
+    EmptyGroup(String),
+
+    /// Driver option drives nothing.
+    #[error("option \"{1}\" of \"{0}\" drives nothing")]
+    EmptyDriverOption(String, String),
+
+    /// Name cannot be kept as is in Unity.
+    #[error("{0:?} may not work in Unity: {1}")]
+    UnityIncompatibleName(String, &'static str),
+
+    /// Configured sync budget is larger than VRChat allows.
+    #[error("sync budget {0} exceeds the VRChat limit of {1} bits")]
+    SyncBudgetOverLimit(usize, usize),
+
+    /// Synced Expression Parameters leave little of the budget.
+    #[error("synced parameters use {0} of {1} bits")]
+    SyncBudgetNearLimit(usize, usize),
+}
+
+/// An Expressions Menu can hold up to this number of controls in VRChat.
+pub const MENU_MAX_CONTROLS: usize = 8;
+
+/// Characters not allowed in asset file names.
+const INVALID_FILE_NAME_CHARS: &[char] = &['/', '\\', ':', '*', '?', '"', '<', '>', '|'];
+
+/// Usage of a name in Unity.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum UnityName {
+    /// Shape key name or transform path of a GameObject.
+    Plain,
+
+    /// Animator state name.
+    State,
+
+    /// Sub-menu name, which is also a part of the menu asset file name.
+    SubMenu,
+
+    /// Asset path of a texture or material.
+    AssetPath,
+}
+
+/// Descriptor in which `validate_descriptor` found no errors.
+/// Layers are built only from validated descriptors, since they look up groups and labels by name.
+#[derive(Debug, Clone)]
+pub struct ValidatedDescriptor {
+    descriptor: Descriptor,
+    warnings: Diagnostics,
+}
+
+impl ValidatedDescriptor {
+    /// Validates the descriptor. Returns the diagnostics if any error is found.
+    pub fn new(descriptor: Descriptor) -> Result<ValidatedDescriptor, Diagnostics> {
+        let diagnostics = validate_descriptor(&descriptor);
+        if diagnostics.has_errors() {
+            return Err(diagnostics);
+        }
+        Ok(ValidatedDescriptor {
+            descriptor,
+            warnings: diagnostics,
+        })
+    }
+
+    /// Warnings found by the validation.
+    pub fn warnings(&self) -> &Diagnostics {
+        &self.warnings
+    }
+
+    pub fn into_inner(self) -> Descriptor {
+        self.descriptor
+    }
+}
+
+impl Deref for ValidatedDescriptor {
+    type Target = Descriptor;
+
+    fn deref(&self) -> &Descriptor {
+        &self.descriptor
+    }
+}
+
+/// Validates the descriptor and collects all errors and warnings, including the sync budget.
+pub fn validate_descriptor(descriptor: &Descriptor) -> Diagnostics {
+    let mut diagnostics = Diagnostics::new();
+    let root = descriptor.sources.root.as_deref();
+
+    if !descriptor.name.chars().all(is_name_char) {
+        diagnostics.error(
+            ValidationError::InvalidName(descriptor.name.clone()),
+            root,
+            descriptor.span,
+        );
+    }
+    validate_names(descriptor, &mut diagnostics);
+    for (i, switch) in descriptor.shape_switches.iter().enumerate() {
+        let source = descriptor.sources.shape_switch(i);
+        validate_shape_key_switch(switch, source, &mut diagnostics);
+    }
+    for (i, group) in descriptor.shape_groups.iter().enumerate() {
+        let source = descriptor.sources.shape_group(i);
+        validate_shape_key_group(group, source, &mut diagnostics);
+    }
+    for slider in &descriptor.shape_sliders {
+        validate_shape_key_slider(slider, root, &mut diagnostics);
+    }
+    for puppet in &descriptor.shape_puppets {
+        validate_shape_key_puppet(puppet, root, &mut diagnostics);
+    }
+    for toggle in &descriptor.object_toggles {
+        validate_object_toggle(toggle, root, &mut diagnostics);
+    }
+    for (i, driver) in descriptor.drivers.iter().enumerate() {
+        let source = descriptor.sources.driver(i);
+        validate_driver(driver, descriptor, source, &mut diagnostics);
+    }
+    for gesture_map in &descriptor.gesture_maps {
+        validate_gesture_map(gesture_map, descriptor, root, &mut diagnostics);
+    }
+    validate_root_menu(descriptor, &mut diagnostics);
+    SyncUsage::new(descriptor).validate(descriptor, &mut diagnostics);
+
+    diagnostics
 }
 
-/// Shorthand for `Result<(), ValidationError>`.
-pub type ValidationResult = Result<(), ValidationError>;
+/// Counts the controls in the root menu, where the generated controls are added to the existing ones.
+/// Sub-menus are paginated by the generator, but the root menu is not.
+fn validate_root_menu(descriptor: &Descriptor, diagnostics: &mut Diagnostics) {
+    let switches = descriptor
+        .shape_switches
+        .iter()
+        .map(|s| (&s.common.menu, &s.common.name, false));
+    let groups = descriptor
+        .shape_groups
+        .iter()
+        .map(|g| (&g.common.menu, &g.common.name, true));
+    let sliders = descriptor
+        .shape_sliders
+        .iter()
+        .map(|s| (&s.common.menu, &s.common.name, false));
+    let puppets = descriptor
+        .shape_puppets
+        .iter()
+        .map(|p| (&p.common.menu, &p.common.name, false));
+    let toggles = descriptor
+        .object_toggles
+        .iter()
+        .map(|t| (&t.menu, &t.name, false));
+    let drivers = descriptor.drivers.iter().map(|d| (&d.menu, &d.name, true));
 
-pub fn validate_descriptor(descriptor: &Descriptor) -> ValidationResult {
-    if descriptor.name.chars().any(|c| !c.is_ascii_alphanumeric()) {
-        return Err(ValidationError::InvalidName(descriptor.name.clone()));
+    // Controls with the same first path segment share a sub-menu, which may be a group or a driver.
+    let mut root_controls: Vec<(&str, bool)> = vec![];
+    let layers = switches
+        .chain(groups)
+        .chain(sliders)
+        .chain(puppets)
+        .chain(toggles)
+        .chain(drivers);
+    for (menu, name, is_sub_menu) in layers {
+        match menu.path.first() {
+            None => root_controls.push((menu.label_or(name), is_sub_menu)),
+            Some(head) => {
+                if !root_controls.contains(&(head.as_str(), true)) {
+                    root_controls.push((head.as_str(), true));
+                }
+            }
+        }
     }
-    for switch in &descriptor.shape_switches {
-        validate_shape_key_switch(switch)?;
+
+    if root_controls.len() > MENU_MAX_CONTROLS {
+        diagnostics.error(
+            ValidationError::RootMenuOverflow(root_controls.len()),
+            descriptor.sources.root.as_deref(),
+            descriptor.span,
+        );
     }
-    for group in &descriptor.shape_groups {
-        validate_shape_key_group(group)?;
+}
+
+fn validate_names(descriptor: &Descriptor, diagnostics: &mut Diagnostics) {
+    let sources = &descriptor.sources;
+    let root = sources.root.as_deref();
+    let switches = descriptor
+        .shape_switches
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (&s.common.name, s.common.span, sources.shape_switch(i)));
+    let groups = descriptor
+        .shape_groups
+        .iter()
+        .enumerate()
+        .map(|(i, g)| (&g.common.name, g.common.span, sources.shape_group(i)));
+    // Bit-packed groups also define the bit parameters and the encoder layer.
+    let group_generated: Vec<_> = descriptor
+        .shape_groups
+        .iter()
+        .enumerate()
+        .flat_map(|(i, g)| {
+            let source = sources.shape_group(i);
+            g.bit_parameters()
+                .into_iter()
+                .chain(g.encoder_layer_name())
+                .map(move |name| (name, g.common.span, source))
+        })
+        .collect();
+    let group_generated = group_generated
+        .iter()
+        .map(|(name, span, source)| (name, *span, *source));
+    let sliders = descriptor
+        .shape_sliders
+        .iter()
+        .map(|s| (&s.common.name, s.common.span, root));
+    let puppets = descriptor.shape_puppets.iter().flat_map(|p| {
+        [&p.common.name, &p.parameter_x, &p.parameter_y]
+            .into_iter()
+            .map(move |name| (name, p.common.span, root))
+    });
+    let toggles = descriptor
+        .object_toggles
+        .iter()
+        .map(|t| (&t.name, t.span, root));
+    let drivers = descriptor
+        .drivers
+        .iter()
+        .enumerate()
+        .map(|(i, d)| (&d.name, d.span, sources.driver(i)));
+    let gesture_maps = descriptor
+        .gesture_maps
+        .iter()
+        .map(|g| (&g.name, g.span, root));
+
+    // Layers and parameters share the names and the identifiers derived from them.
+    let mut defined: HashMap<&str, Option<&Path>> = HashMap::new();
+    let mut identifiers: HashMap<String, &str> = HashMap::new();
+    let names = switches
+        .chain(groups)
+        .chain(group_generated)
+        .chain(sliders)
+        .chain(puppets)
+        .chain(toggles)
+        .chain(drivers)
+        .chain(gesture_maps);
+    for (name, span, source) in names {
+        match identifiers.insert(to_identifier(name), name) {
+            Some(other) if other != name => {
+                diagnostics.error(
+                    ValidationError::IdentifierCollision(
+                        other.to_string(),
+                        name.clone(),
+                        to_identifier(name),
+                    ),
+                    source,
+                    span,
+                );
+            }
+            _ => (),
+        }
+        let previous = match defined.insert(name, source) {
+            Some(previous) => previous,
+            None => continue,
+        };
+        let error = match (previous, source) {
+            (Some(previous), Some(source)) if previous != source => {
+                ValidationError::DuplicateIncludedName(
+                    name.clone(),
+                    previous.to_path_buf(),
+                    source.to_path_buf(),
+                )
+            }
+            _ => ValidationError::DuplicateName(name.clone()),
+        };
+        diagnostics.error(error, source, span);
+    }
+}
+
+fn validate_shape_key_switch(
+    switch: &ShapeKeySwitch,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    validate_shape_key_common(&switch.common, source, diagnostics);
+    validate_unity_name(
+        &switch.shape,
+        UnityName::Plain,
+        (source, switch.common.span),
+        diagnostics,
+    );
+    validate_parameter_default(
+        &switch.common.name,
+        &switch.common.parameter,
+        0.0..=1.0,
+        true,
+        (source, switch.common.span),
+        diagnostics,
+    );
+    let values = [
+        (switch.enabled_value, switch.enabled_value_span),
+        (switch.disabled_value, switch.disabled_value_span),
+    ];
+    for (value, span) in values {
+        if !value.is_valid() {
+            diagnostics.error(
+                ValidationError::ValueOutOfRange(switch.common.name.clone(), value.get()),
+                source,
+                span.unwrap_or(switch.common.span),
+            );
+        }
+    }
+}
+
+fn validate_shape_key_group(
+    group: &ShapeKeyGroup,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    let name = &group.common.name;
+    validate_shape_key_common(&group.common, source, diagnostics);
+    validate_parameter_default(
+        name,
+        &group.common.parameter,
+        0.0..=255.0,
+        true,
+        (source, group.common.span),
+        diagnostics,
+    );
+    validate_drives(
+        name,
+        &group.defaults,
+        (source, group.common.span),
+        diagnostics,
+    );
+
+    if group.options.is_empty() {
+        diagnostics.warning(
+            ValidationWarning::EmptyGroup(name.clone()),
+            source,
+            group.common.span,
+        );
     }
-    for driver in &descriptor.drivers {
-        validate_driver(driver, descriptor)?;
+    for (i, option) in group.options.iter().enumerate() {
+        let index = option.index.unwrap_or(i + 1);
+        if index == 0 {
+            diagnostics.error(
+                ValidationError::ZeroIndex(name.clone(), option.label.clone()),
+                source,
+                option.span,
+            );
+        } else if index > 255 {
+            diagnostics.error(
+                ValidationError::IndexOutOfRange(name.clone(), index),
+                source,
+                option.span,
+            );
+        }
+
+        let previous_options = group.options[..i].iter().enumerate();
+        if previous_options
+            .clone()
+            .any(|(_, o)| o.label == option.label)
+        {
+            diagnostics.error(
+                ValidationError::DuplicateLabel(name.clone(), option.label.clone()),
+                source,
+                option.span,
+            );
+        }
+        if previous_options
+            .into_iter()
+            .any(|(j, o)| o.index.unwrap_or(j + 1) == index)
+        {
+            diagnostics.error(
+                ValidationError::DuplicateIndex(name.clone(), index),
+                source,
+                option.span,
+            );
+        }
+
+        validate_unity_name(
+            &option.label,
+            UnityName::State,
+            (source, option.span),
+            diagnostics,
+        );
+        if let Some(icon) = &option.icon {
+            validate_unity_name(
+                icon,
+                UnityName::AssetPath,
+                (source, option.span),
+                diagnostics,
+            );
+        }
+        for swap in &option.materials {
+            validate_unity_name(
+                &swap.material,
+                UnityName::AssetPath,
+                (source, option.span),
+                diagnostics,
+            );
+        }
+        validate_drives(name, &option.shapes, (source, option.span), diagnostics);
     }
+}
 
-    Ok(())
+fn validate_shape_key_slider(
+    slider: &ShapeKeySlider,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    let name = &slider.common.name;
+    validate_shape_key_common(&slider.common, source, diagnostics);
+    validate_parameter_default(
+        name,
+        &slider.common.parameter,
+        0.0..=1.0,
+        false,
+        (source, slider.common.span),
+        diagnostics,
+    );
+    validate_drives(
+        name,
+        &slider.defaults,
+        (source, slider.common.span),
+        diagnostics,
+    );
+    validate_drives(
+        name,
+        &slider.shapes,
+        (source, slider.common.span),
+        diagnostics,
+    );
 }
 
-fn validate_shape_key_switch(switch: &ShapeKeySwitch) -> ValidationResult {
-    validate_shape_key_common(&switch.common)?;
+fn validate_shape_key_puppet(
+    puppet: &ShapeKeyPuppet,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    let name = &puppet.common.name;
+    validate_shape_key_common(&puppet.common, source, diagnostics);
+    validate_parameter_default(
+        name,
+        &puppet.common.parameter,
+        -1.0..=1.0,
+        false,
+        (source, puppet.common.span),
+        diagnostics,
+    );
+    for parameter in [&puppet.parameter_x, &puppet.parameter_y] {
+        if !parameter.chars().all(is_name_char) {
+            diagnostics.error(
+                ValidationError::InvalidName(parameter.clone()),
+                source,
+                puppet.common.span,
+            );
+        }
+    }
+    validate_drives(
+        name,
+        &puppet.defaults,
+        (source, puppet.common.span),
+        diagnostics,
+    );
+    for (i, sample) in puppet.samples.iter().enumerate() {
+        let duplicate = puppet.samples[..i]
+            .iter()
+            .any(|s| s.x == sample.x && s.y == sample.y);
+        if duplicate {
+            diagnostics.error(
+                ValidationError::DuplicateSamplePosition(name.clone(), sample.x, sample.y),
+                source,
+                sample.span,
+            );
+        }
+        validate_drives(name, &sample.shapes, (source, sample.span), diagnostics);
+    }
+}
+
+fn validate_shape_key_common(
+    common: &ShapeKeyCommon,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    if !common.name.chars().all(is_name_char) {
+        diagnostics.error(
+            ValidationError::InvalidName(common.name.clone()),
+            source,
+            common.span,
+        );
+    }
+    if common.mesh.is_empty() {
+        diagnostics.error(
+            ValidationError::NoMesh(common.name.clone()),
+            source,
+            common.span,
+        );
+    }
+    for mesh in &common.mesh {
+        validate_unity_name(mesh, UnityName::Plain, (source, common.span), diagnostics);
+    }
+    validate_menu(&common.menu, (source, common.span), diagnostics);
+}
 
-    Ok(())
+fn validate_menu(
+    menu: &MenuSettings,
+    (source, span): (Option<&Path>, Span),
+    diagnostics: &mut Diagnostics,
+) {
+    if let Some(label) = &menu.label {
+        validate_unity_name(label, UnityName::Plain, (source, span), diagnostics);
+    }
+    if let Some(icon) = &menu.icon {
+        validate_unity_name(icon, UnityName::AssetPath, (source, span), diagnostics);
+    }
+    for name in &menu.path {
+        validate_unity_name(name, UnityName::SubMenu, (source, span), diagnostics);
+    }
 }
 
-fn validate_shape_key_group(group: &ShapeKeyGroup) -> ValidationResult {
-    validate_shape_key_common(&group.common)?;
+/// Warns if Unity would alter the name or fail to use it.
+fn validate_unity_name(
+    name: &str,
+    usage: UnityName,
+    (source, span): (Option<&Path>, Span),
+    diagnostics: &mut Diagnostics,
+) {
+    let reason = if name.chars().any(char::is_control) {
+        "control characters are not kept"
+    } else {
+        match usage {
+            UnityName::State if name.contains('.') => "'.' is not allowed in state names",
+            UnityName::SubMenu if name.contains(INVALID_FILE_NAME_CHARS) => {
+                "not allowed in the menu asset file name"
+            }
+            UnityName::AssetPath if name.contains('\\') => "asset paths must be separated by '/'",
+            _ => return,
+        }
+    };
+    diagnostics.warning(
+        ValidationWarning::UnityIncompatibleName(name.to_string(), reason),
+        source,
+        span,
+    );
+}
 
-    Ok(())
+fn validate_parameter_default(
+    name: &str,
+    settings: &ParameterSettings,
+    range: RangeInclusive<f64>,
+    integral: bool,
+    (source, layer_span): (Option<&Path>, Span),
+    diagnostics: &mut Diagnostics,
+) {
+    let value = settings.default;
+    if !range.contains(&value) || (integral && value.fract() != 0.0) {
+        diagnostics.error(
+            ValidationError::InvalidParameterDefault(name.to_string(), value),
+            source,
+            settings.span.unwrap_or(layer_span),
+        );
+    }
 }
 
-fn validate_shape_key_common(common: &ShapeKeyCommon) -> ValidationResult {
-    if common.name.chars().any(|c| !c.is_ascii_alphanumeric()) {
-        return Err(ValidationError::InvalidName(common.name.clone()));
+fn validate_drives(
+    name: &str,
+    drives: &[ShapeKeyDrive],
+    (source, span): (Option<&Path>, Span),
+    diagnostics: &mut Diagnostics,
+) {
+    for drive in drives {
+        validate_unity_name(&drive.shape, UnityName::Plain, (source, span), diagnostics);
+        if let Some(mesh) = &drive.mesh {
+            validate_unity_name(mesh, UnityName::Plain, (source, span), diagnostics);
+        }
+        if !drive.value.is_valid() {
+            diagnostics.error(
+                ValidationError::ValueOutOfRange(name.to_string(), drive.value.get()),
+                source,
+                drive.span.unwrap_or(span),
+            );
+        }
     }
+}
 
-    Ok(())
+fn validate_object_toggle(
+    toggle: &ObjectToggle,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    if !toggle.name.chars().all(is_name_char) {
+        diagnostics.error(
+            ValidationError::InvalidName(toggle.name.clone()),
+            source,
+            toggle.span,
+        );
+    }
+    validate_parameter_default(
+        &toggle.name,
+        &toggle.parameter,
+        0.0..=1.0,
+        true,
+        (source, toggle.span),
+        diagnostics,
+    );
+    for object in &toggle.objects {
+        validate_unity_name(object, UnityName::Plain, (source, toggle.span), diagnostics);
+    }
+    validate_menu(&toggle.menu, (source, toggle.span), diagnostics);
 }
 
-fn validate_driver(driver: &Driver, descriptor: &Descriptor) -> ValidationResult {
-    if driver.name.chars().any(|c| !c.is_ascii_alphanumeric()) {
-        return Err(ValidationError::InvalidName(driver.name.clone()));
+fn validate_driver(
+    driver: &Driver,
+    descriptor: &Descriptor,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    if !driver.name.chars().all(is_name_char) {
+        diagnostics.error(
+            ValidationError::InvalidName(driver.name.clone()),
+            source,
+            driver.span,
+        );
     }
-    for option in &driver.options {
+    validate_parameter_default(
+        &driver.name,
+        &driver.parameter,
+        0.0..=255.0,
+        true,
+        (source, driver.span),
+        diagnostics,
+    );
+    validate_menu(&driver.menu, (source, driver.span), diagnostics);
+    for (i, option) in driver.options.iter().enumerate() {
+        validate_unity_name(
+            &option.label,
+            UnityName::State,
+            (source, option.span),
+            diagnostics,
+        );
+        if let Some(icon) = &option.icon {
+            validate_unity_name(
+                icon,
+                UnityName::AssetPath,
+                (source, option.span),
+                diagnostics,
+            );
+        }
+        if i + 1 > 255 {
+            diagnostics.error(
+                ValidationError::IndexOutOfRange(driver.name.clone(), i + 1),
+                source,
+                option.span,
+            );
+        }
+        if driver.options[..i].iter().any(|o| o.label == option.label) {
+            diagnostics.error(
+                ValidationError::DuplicateLabel(driver.name.clone(), option.label.clone()),
+                source,
+                option.span,
+            );
+        }
+        if option.drives.is_empty() {
+            diagnostics.warning(
+                ValidationWarning::EmptyDriverOption(driver.name.clone(), option.label.clone()),
+                source,
+                option.span,
+            );
+        }
         for drive in &option.drives {
-            match drive {
+            let (name, exists) = match drive {
                 Drive::Switch { name, .. } => {
                     let exists_shape_switch = descriptor
                         .shape_switches
                         .iter()
                         .any(|s| name == &s.common.name);
-                    if !exists_shape_switch {
-                        return Err(ValidationError::NameNotExist(name.clone()));
-                    }
+                    let exists_object_toggle =
+                        descriptor.object_toggles.iter().any(|t| name == &t.name);
+                    (name, exists_shape_switch || exists_object_toggle)
                 }
                 Drive::Group { name, label } => {
                     let exists_shape_group = descriptor.shape_groups.iter().any(|g| {
                         name == &g.common.name && g.options.iter().any(|o| &o.label == label)
                     });
-                    if !exists_shape_group {
-                        return Err(ValidationError::NameNotExist(name.clone()));
-                    }
+                    (name, exists_shape_group)
                 }
+            };
+            if !exists {
+                diagnostics.error(
+                    ValidationError::NameNotExist(name.clone()),
+                    source,
+                    option.span,
+                );
             }
         }
     }
+}
+
+fn validate_gesture_map(
+    gesture_map: &GestureMap,
+    descriptor: &Descriptor,
+    source: Option<&Path>,
+    diagnostics: &mut Diagnostics,
+) {
+    if !gesture_map.name.chars().all(is_name_char) {
+        diagnostics.error(
+            ValidationError::InvalidName(gesture_map.name.clone()),
+            source,
+            gesture_map.span,
+        );
+    }
+    let group = descriptor
+        .shape_groups
+        .iter()
+        .find(|g| g.common.name == gesture_map.group);
+    if group.is_none() {
+        diagnostics.error(
+            ValidationError::NameNotExist(gesture_map.group.clone()),
+            source,
+            gesture_map.span,
+        );
+    }
+    for (i, mapping) in gesture_map.mappings.iter().enumerate() {
+        let exists_label = group.is_none_or(|g| g.options.iter().any(|o| o.label == mapping.label));
+        if !exists_label {
+            diagnostics.error(
+                ValidationError::NameNotExist(gesture_map.group.clone()),
+                source,
+                mapping.span,
+            );
+        }
+
+        let duplicate = gesture_map.mappings[..i].iter().any(|m| {
+            m.gesture == mapping.gesture
+                && (m.hand == mapping.hand
+                    || m.hand == GestureHand::Both
+                    || mapping.hand == GestureHand::Both)
+        });
+        if duplicate {
+            diagnostics.error(
+                ValidationError::DuplicateGesture(gesture_map.name.clone(), mapping.gesture),
+                source,
+                mapping.span,
+            );
+        }
+
+        // The group layer blends the option by only one weight parameter.
+        let ambiguous_weight = mapping.weighted
+            && (mapping.hand == GestureHand::Both
+                || descriptor
+                    .gesture_maps
+                    .iter()
+                    .filter(|g| g.group == gesture_map.group)
+                    .flat_map(|g| g.mappings.iter())
+                    .any(|m| m.label == mapping.label && m.hand != mapping.hand));
+        if ambiguous_weight {
+            diagnostics.error(
+                ValidationError::AmbiguousGestureWeight(
+                    gesture_map.name.clone(),
+                    mapping.label.clone(),
+                ),
+                source,
+                mapping.span,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::validate_descriptor;
+    use crate::descriptor::{
+        Descriptor, MenuSettings, ParameterSettings, Severity, ShapeKeyGroup, ShapeKeyOption,
+        ShapeKeySwitch,
+    };
+
+    use std::path::PathBuf;
+
+    /// Messages of the diagnostics of the severity.
+    fn messages(descriptor: &Descriptor, severity: Severity) -> Vec<String> {
+        validate_descriptor(descriptor)
+            .iter()
+            .filter(|d| d.severity == severity)
+            .map(|d| d.message.clone())
+            .collect()
+    }
+
+    fn switch(name: &str) -> ShapeKeySwitch {
+        ShapeKeySwitch::builder(name, "cheek").mesh("Face").build()
+    }
+
+    #[test]
+    fn example_has_no_diagnostics() {
+        let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let descriptor = Descriptor::load(manifest_dir.join("../example.toml")).unwrap();
+        let diagnostics = validate_descriptor(&descriptor);
+        let messages: Vec<_> = diagnostics.iter().map(|d| &d.message).collect();
+        assert!(messages.is_empty(), "{messages:?}");
+    }
+
+    #[test]
+    fn parameter_default_out_of_range() {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_switch(
+                ShapeKeySwitch::builder("Cheek", "cheek")
+                    .mesh("Face")
+                    .parameter(ParameterSettings {
+                        default: 2.0,
+                        ..ParameterSettings::default()
+                    })
+                    .build(),
+            )
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .parameter(ParameterSettings {
+                        default: 1.5,
+                        ..ParameterSettings::default()
+                    })
+                    .option(ShapeKeyOption::new("eyelids_close"))
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            messages(&descriptor, Severity::Error),
+            [
+                "invalid default value for \"Cheek\": 2",
+                "invalid default value for \"Eyelids\": 1.5",
+            ]
+        );
+    }
+
+    #[test]
+    fn parameter_default_in_range() {
+        let parameter = ParameterSettings {
+            default: 1.0,
+            ..ParameterSettings::default()
+        };
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_switch(switch("Cheek"))
+            .shape_switch(
+                ShapeKeySwitch::builder("Tongue", "tongue")
+                    .mesh("Face")
+                    .parameter(parameter)
+                    .build(),
+            )
+            .build();
+        assert!(validate_descriptor(&descriptor).iter().next().is_none());
+    }
+
+    /// Descriptor with the switches, the first `in_sub_menu` of which are in the same sub-menu.
+    fn menu_descriptor(count: usize, in_sub_menu: usize) -> Descriptor {
+        let switches = (0..count).map(|i| {
+            let path = if i < in_sub_menu {
+                vec!["More".to_string()]
+            } else {
+                vec![]
+            };
+            ShapeKeySwitch::builder(format!("Switch{i}"), "cheek")
+                .mesh("Face")
+                .menu(MenuSettings {
+                    path,
+                    ..MenuSettings::default()
+                })
+                .build()
+        });
+        switches
+            .fold(Descriptor::builder("Avatar"), |b, s| b.shape_switch(s))
+            .build()
+    }
+
+    #[test]
+    fn root_menu_overflow() {
+        assert_eq!(
+            messages(&menu_descriptor(9, 0), Severity::Error),
+            ["root menu has 9 controls, exceeding the limit of 8; use `path` of `menu` to move them into sub-menus"]
+        );
+    }
+
+    #[test]
+    fn root_menu_counts_sub_menu_once() {
+        assert!(validate_descriptor(&menu_descriptor(8, 0))
+            .iter()
+            .next()
+            .is_none());
+        assert!(validate_descriptor(&menu_descriptor(10, 3))
+            .iter()
+            .next()
+            .is_none());
+    }
+
+    fn indexed_option(label: &str, index: usize) -> ShapeKeyOption {
+        ShapeKeyOption {
+            index: Some(index),
+            ..ShapeKeyOption::new(label)
+        }
+    }
 
-    Ok(())
+    #[test]
+    fn duplicate_names() {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_switch(switch("Cheek"))
+            .shape_group(
+                ShapeKeyGroup::builder("Cheek")
+                    .mesh("Face")
+                    .option(ShapeKeyOption::new("cheek_puff"))
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            messages(&descriptor, Severity::Error),
+            ["\"Cheek\" is defined twice"]
+        );
+    }
+
+    #[test]
+    fn duplicate_labels_and_indices() {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .option(ShapeKeyOption::new("eyelids_smile"))
+                    .option(ShapeKeyOption::new("eyelids_smile"))
+                    .option(indexed_option("eyelids_close", 1))
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            messages(&descriptor, Severity::Error),
+            [
+                "option \"eyelids_smile\" is defined twice in \"Eyelids\"",
+                "index 1 is used twice in \"Eyelids\"",
+            ]
+        );
+    }
+
+    #[test]
+    fn indices_out_of_range() {
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_group(
+                ShapeKeyGroup::builder("Eyelids")
+                    .mesh("Face")
+                    .option(indexed_option("eyelids_smile", 0))
+                    .option(indexed_option("eyelids_close", 256))
+                    .option(indexed_option("eyelids_wink", 255))
+                    .build(),
+            )
+            .build();
+        assert_eq!(
+            messages(&descriptor, Severity::Error),
+            [
+                "index of \"eyelids_smile\" in \"Eyelids\" must be non-zero",
+                "index 256 in \"Eyelids\" is out of range (1-255)",
+            ]
+        );
+    }
+
+    #[test]
+    fn identifier_collision() {
+        // `笑` is escaped as `_7B11` in C#.
+        let descriptor = Descriptor::builder("Avatar")
+            .shape_switch(switch("笑"))
+            .shape_switch(switch("_7B11"))
+            .build();
+        assert_eq!(
+            messages(&descriptor, Severity::Error),
+            ["\"笑\" and \"_7B11\" result in the same identifier \"_7B11\""]
+        );
+    }
+
+    #[test]
+    fn unicode_names_are_valid() {
+        let descriptor = Descriptor::builder("アバター")
+            .shape_switch(switch("笑"))
+            .shape_switch(switch("泣"))
+            .build();
+        assert!(validate_descriptor(&descriptor).iter().next().is_none());
+    }
 }
@@ -0,0 +1,10 @@
+//! Generates Animator As Code scripts from shape key descriptors.
+
+pub mod animator;
+pub mod codegen;
+pub mod descriptor;
+
+pub use crate::{
+    codegen::write_descriptor_code,
+    descriptor::{validate_descriptor, Descriptor, ValidatedDescriptor},
+};
@@ -1,32 +1,445 @@
-mod codegen;
-mod descriptor;
+mod watch;
 
-use crate::{
-    codegen::write_descriptor_code,
-    descriptor::{validate_descriptor, Descriptor},
+use crate::watch::watch_descriptor;
+
+use sk2aac::{
+    animator::{simulate, Assignment, SyncUsage, TestFile},
+    codegen::{
+        generate_unity_assets, validate_unity_assets, write_descriptor_code,
+        write_descriptor_graph, Backend, GraphFormat,
+    },
+    descriptor::{
+        format_file, Descriptor, Diagnostic, Diagnostics, FormatError, LoadError,
+        ShapeKeyInventory, ValidatedDescriptor,
+    },
 };
 
 use std::{
-    env::args,
-    fs::{read_to_string, File},
-    io::BufWriter,
+    collections::{BTreeSet, HashMap, HashSet},
+    fs::{create_dir_all, read, read_dir, read_to_string, remove_file, write},
+    io::{prelude::*, stderr, stdout, ErrorKind, Result as IoResult},
+    path::{absolute, Path, PathBuf},
 };
 
 use anyhow::{bail, Result};
-use toml::from_str as toml_from_str;
+use clap::{Args, Parser, Subcommand};
+
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Generates Animator As Code scripts from shape key descriptors"
+)]
+struct Arguments {
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Generates `<ClassName>.cs`, or Unity assets with `--backend unity`, into the output directory.
+    Generate {
+        #[command(flatten)]
+        input: InputArguments,
+
+        #[command(flatten)]
+        output: OutputArguments,
+
+        /// Keeps regenerating the code when the descriptor or its included files change.
+        #[arg(short, long)]
+        watch: bool,
+    },
+
+    /// Parses and validates the descriptor only.
+    Validate {
+        #[command(flatten)]
+        input: InputArguments,
+    },
+
+    /// Shows the bits used to sync Expression Parameters.
+    Budget {
+        #[command(flatten)]
+        input: InputArguments,
+    },
+
+    /// Fails if the generated files in the output directory are not up to date.
+    Check {
+        #[command(flatten)]
+        input: InputArguments,
+
+        #[command(flatten)]
+        output: OutputArguments,
+    },
+
+    /// Writes the generated FX layers as a state machine graph.
+    Graph {
+        #[command(flatten)]
+        input: InputArguments,
+
+        /// Graph format, `dot` or `mermaid`.
+        #[arg(short, long, default_value = "dot")]
+        format: GraphFormat,
+
+        /// Output file. Writes to stdout if omitted.
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+
+    /// Sets parameters in order and shows the resulting blend shapes and tracking control.
+    Simulate {
+        #[command(flatten)]
+        input: InputArguments,
+
+        /// Parameter assignments like `Eyelids=2`. Commas also separate assignments.
+        assignments: Vec<String>,
+    },
+
+    /// Runs the cases in test files, `<descriptor>.test.toml` next to the descriptor by default.
+    Test {
+        #[command(flatten)]
+        input: InputArguments,
+
+        /// Test files.
+        files: Vec<PathBuf>,
+    },
+
+    /// Rewrites descriptor files in the canonical form.
+    Fmt {
+        /// Descriptor files.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+}
+
+#[derive(Debug, Args)]
+struct InputArguments {
+    /// Descriptor TOML file.
+    descriptor: PathBuf,
+
+    /// Reads a shape key inventory exported by the Blender plugin instead of a descriptor.
+    #[arg(long)]
+    inventory: bool,
+}
+
+#[derive(Debug, Args)]
+struct OutputArguments {
+    /// Output directory.
+    #[arg(short, long, default_value = ".")]
+    output_dir: PathBuf,
+
+    /// `aac` for an Animator As Code script, or `unity` for controller, clip and parameter assets.
+    #[arg(short, long, default_value = "aac")]
+    backend: Backend,
+
+    /// Unity project directory which material paths are relative to, for `--backend unity`.
+    /// Defaults to the nearest ancestor of the output directory with `Assets`, or the current directory.
+    #[arg(long)]
+    project_dir: Option<PathBuf>,
+}
 
 fn main() -> Result<()> {
-    let args: Vec<String> = args().collect();
-    if args.len() <= 2 {
-        bail!("Usage: sk2aac <descriptor TOML> <output cs>");
+    let arguments = Arguments::parse();
+    match arguments.command {
+        Command::Generate {
+            input,
+            output,
+            watch: true,
+        } => watch_descriptor(&input, &output)?,
+        Command::Generate { input, output, .. } => {
+            let descriptor = load_descriptor(&input)?;
+            write_files(descriptor, &output)?;
+        }
+        Command::Validate { input } => {
+            let descriptor = load_descriptor(&input)?;
+            let usage = SyncUsage::new(&descriptor);
+            println!(
+                "{} is valid ({} / {} synced bits)",
+                input.descriptor.display(),
+                usage.total(),
+                usage.budget
+            );
+        }
+        Command::Budget { input } => {
+            // The table is also shown for descriptors over the budget.
+            let descriptor = match read_descriptor(&input) {
+                Ok(d) => d,
+                Err(e) => {
+                    report_diagnostics([&e.to_diagnostic()])?;
+                    bail!("Failed to load the descriptor");
+                }
+            };
+            let usage = SyncUsage::new(&descriptor);
+            usage.write_report(&mut stdout().lock())?;
+            check_descriptor(descriptor)?;
+        }
+        Command::Check { input, output } => {
+            let descriptor = load_descriptor(&input)?;
+            let files = generate_files(descriptor, &output)?;
+            for (path, content) in &files {
+                let output_path = output.output_dir.join(path);
+                match read(&output_path) {
+                    Ok(existing) if &existing == content => (),
+                    Ok(_) => bail!("{} is out of date", output_path.display()),
+                    Err(e) => bail!("Failed to read {}: {e}", output_path.display()),
+                }
+            }
+            if let Some(path) = stale_files(&output.output_dir, &files)?.first() {
+                bail!("{} is no longer generated", path.display());
+            }
+            match files.as_slice() {
+                [(path, _)] => println!("{} is up to date", output.output_dir.join(path).display()),
+                files => println!("{} file(s) are up to date", files.len()),
+            }
+        }
+        Command::Graph {
+            input,
+            format,
+            output,
+        } => {
+            let descriptor = load_descriptor(&input)?;
+            let mut graph = vec![];
+            write_descriptor_graph(&mut graph, &descriptor, format)?;
+            match output {
+                Some(path) => write(path, graph)?,
+                None => stdout().lock().write_all(&graph)?,
+            }
+        }
+        Command::Simulate { input, assignments } => {
+            let descriptor = load_descriptor(&input)?;
+            let assignments = assignments
+                .iter()
+                .map(|a| Assignment::parse_list(a))
+                .collect::<Result<Vec<_>, _>>()?;
+            let simulation = simulate(&descriptor, &assignments.concat())?;
+            simulation.write_report(&mut stdout().lock())?;
+        }
+        Command::Test { input, files } => {
+            let descriptor = load_descriptor(&input)?;
+            let files = if files.is_empty() {
+                TestFile::find(&input.descriptor)?
+            } else {
+                files
+            };
+            if files.is_empty() {
+                bail!("No test files found for {}", input.descriptor.display());
+            }
+            run_tests(&descriptor, &files)?;
+        }
+        Command::Fmt { files } => format_files(&files)?,
     }
 
-    let descriptor: Descriptor = toml_from_str(&read_to_string(&args[1])?)?;
-    validate_descriptor(&descriptor)?;
+    Ok(())
+}
+
+/// Loads and validates the descriptor, reporting the diagnostics.
+fn load_descriptor(input: &InputArguments) -> Result<ValidatedDescriptor> {
+    let descriptor = match read_descriptor(input) {
+        Ok(d) => d,
+        Err(e) => {
+            report_diagnostics([&e.to_diagnostic()])?;
+            bail!("Failed to load the descriptor");
+        }
+    };
+    check_descriptor(descriptor)
+}
 
-    let mut output_file = BufWriter::new(File::create(&args[2])?);
-    let class_name = write_descriptor_code(&mut output_file, descriptor)?;
-    println!("You should rename the file to {class_name}.cs");
+fn read_descriptor(input: &InputArguments) -> Result<Descriptor, LoadError> {
+    if input.inventory {
+        ShapeKeyInventory::load(&input.descriptor).map(|i| i.into_descriptor())
+    } else {
+        Descriptor::load(&input.descriptor)
+    }
+}
 
+/// Validates the descriptor, reporting the diagnostics.
+fn check_descriptor(descriptor: Descriptor) -> Result<ValidatedDescriptor> {
+    match ValidatedDescriptor::new(descriptor) {
+        Ok(validated) => {
+            report_diagnostics(validated.warnings().iter())?;
+            Ok(validated)
+        }
+        Err(diagnostics) => {
+            report_diagnostics(diagnostics.iter())?;
+            bail!("{} error(s) found", diagnostics.error_count());
+        }
+    }
+}
+
+/// Generates the files in memory. Returns the paths relative to the output directory and the contents.
+fn generate_files(
+    descriptor: ValidatedDescriptor,
+    output: &OutputArguments,
+) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    match output.backend {
+        Backend::Aac => {
+            let mut code = vec![];
+            let class_name = write_descriptor_code(&mut code, descriptor)?;
+            Ok(vec![(PathBuf::from(format!("{class_name}.cs")), code)])
+        }
+        Backend::Unity => {
+            let mut diagnostics = Diagnostics::new();
+            validate_unity_assets(&descriptor, &mut diagnostics);
+            report_diagnostics(diagnostics.iter())?;
+            if diagnostics.has_errors() {
+                bail!("{} error(s) found", diagnostics.error_count());
+            }
+
+            let project_dir = match &output.project_dir {
+                Some(dir) => dir.clone(),
+                None => unity_project_dir(&output.output_dir)?,
+            };
+            let assets = generate_unity_assets(&descriptor, &project_dir)?;
+            Ok(assets.into_iter().map(|a| (a.path, a.content)).collect())
+        }
+    }
+}
+
+/// Finds the Unity project which the output directory is in.
+fn unity_project_dir(output_dir: &Path) -> IoResult<PathBuf> {
+    let output_dir = absolute(output_dir)?;
+    let project_dir = output_dir
+        .ancestors()
+        .find(|d| d.join("Assets").is_dir())
+        .unwrap_or(Path::new("."));
+    Ok(project_dir.to_path_buf())
+}
+
+/// Files left in the generated subdirectories but no longer generated, e.g. clips of removed states.
+/// The output directory itself is shared with other files, so it is not scanned.
+fn stale_files(output_dir: &Path, files: &[(PathBuf, Vec<u8>)]) -> IoResult<Vec<PathBuf>> {
+    let generated: HashSet<_> = files.iter().map(|(p, _)| output_dir.join(p)).collect();
+    let dirs: BTreeSet<_> = files
+        .iter()
+        .filter_map(|(p, _)| p.parent())
+        .filter(|p| !p.as_os_str().is_empty())
+        .map(|p| output_dir.join(p))
+        .collect();
+
+    let mut stale = vec![];
+    for dir in dirs {
+        let entries = match read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == ErrorKind::NotFound => continue,
+            Err(e) => return Err(e),
+        };
+        for entry in entries {
+            let path = entry?.path();
+            let extension = path.extension().and_then(|e| e.to_str());
+            if matches!(extension, Some("anim" | "meta"))
+                && path.is_file()
+                && !generated.contains(&path)
+            {
+                stale.push(path);
+            }
+        }
+    }
+    stale.sort();
+    Ok(stale)
+}
+
+/// Writes the generated files into the directory. Files are not touched if the contents are the same.
+/// Stale files in the generated subdirectories are removed.
+fn write_files(descriptor: ValidatedDescriptor, output: &OutputArguments) -> Result<()> {
+    let files = generate_files(descriptor, output)?;
+    let mut unchanged = vec![];
+    for (path, content) in &files {
+        let output_path = output.output_dir.join(path);
+        if read(&output_path).ok().as_ref() == Some(content) {
+            unchanged.push(output_path);
+            continue;
+        }
+        if let Some(parent) = output_path.parent() {
+            create_dir_all(parent)?;
+        }
+        write(&output_path, content)?;
+        println!("Generated {}", output_path.display());
+    }
+    for path in stale_files(&output.output_dir, &files)? {
+        remove_file(&path)?;
+        println!("Removed {}", path.display());
+    }
+
+    // Assets are too many to list one by one.
+    match unchanged.as_slice() {
+        [] => (),
+        [path] => println!("{} is up to date", path.display()),
+        paths => println!("{} file(s) are up to date", paths.len()),
+    }
+    Ok(())
+}
+
+/// Runs the cases in the test files and reports the result of each case.
+fn run_tests(descriptor: &ValidatedDescriptor, paths: &[PathBuf]) -> Result<()> {
+    let (mut passed, mut failed) = (0, 0);
+    for path in paths {
+        let test_file = match TestFile::load(path) {
+            Ok(t) => t,
+            Err(e) => {
+                report_diagnostics([&e.to_diagnostic()])?;
+                bail!("Failed to load the test file");
+            }
+        };
+
+        println!("{}", path.display());
+        for (i, case) in test_file.cases.iter().enumerate() {
+            let name = match &case.name {
+                Some(name) => name.clone(),
+                None => format!("#{}", i + 1),
+            };
+            let failures = case.run(descriptor);
+            if failures.is_empty() {
+                println!("    {name} ... ok");
+                passed += 1;
+            } else {
+                println!("    {name} ... FAILED");
+                for failure in failures {
+                    println!("        {failure}");
+                }
+                failed += 1;
+            }
+        }
+    }
+
+    println!("{passed} passed, {failed} failed");
+    if failed > 0 {
+        bail!("{failed} case(s) failed");
+    }
+    Ok(())
+}
+
+/// Rewrites descriptor files in the canonical form.
+fn format_files(paths: &[PathBuf]) -> Result<()> {
+    for path in paths {
+        let formatted = match format_file(path) {
+            Ok(f) => f,
+            Err(FormatError::Load(e)) => {
+                report_diagnostics([&e.to_diagnostic()])?;
+                bail!("Failed to load the descriptor");
+            }
+            Err(e) => return Err(e.into()),
+        };
+        if read_to_string(path)? != formatted {
+            write(path, formatted)?;
+            println!("Formatted {}", path.display());
+        }
+    }
+
+    Ok(())
+}
+
+/// Prints diagnostics with snippets of their source files.
+fn report_diagnostics<'a>(diagnostics: impl IntoIterator<Item = &'a Diagnostic>) -> IoResult<()> {
+    let mut texts: HashMap<PathBuf, Option<String>> = HashMap::new();
+    let mut stderr = stderr().lock();
+    for diagnostic in diagnostics {
+        let text = match &diagnostic.source {
+            Some(path) => texts
+                .entry(path.clone())
+                .or_insert_with(|| read_to_string(path).ok())
+                .as_deref(),
+            None => None,
+        };
+        diagnostic.write_report(&mut stderr, text)?;
+        writeln!(stderr)?;
+    }
     Ok(())
 }
@@ -0,0 +1,118 @@
+use crate::{
+    check_descriptor, read_descriptor, report_diagnostics, write_files, InputArguments,
+    OutputArguments,
+};
+
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    time::Duration,
+};
+
+use anyhow::{bail, Result};
+use notify::{recommended_watcher, Event, RecursiveMode, Watcher};
+
+/// Editors may write a file in several steps, so events in this duration are merged.
+const DEBOUNCE_DURATION: Duration = Duration::from_millis(100);
+
+/// Regenerates the code whenever the descriptor or its included files change.
+/// Errors are reported and the watch continues.
+pub fn watch_descriptor(input: &InputArguments, output: &OutputArguments) -> Result<()> {
+    let (sender, receiver) = channel();
+    let mut watcher = recommended_watcher(sender)?;
+    let mut files = BTreeSet::new();
+    let mut watched_dirs = BTreeSet::new();
+
+    loop {
+        if let Err(e) = regenerate(input, output, &mut files) {
+            eprintln!("Error: {e}");
+        }
+
+        // Directories are watched instead of the files because editors may replace them on save.
+        let watched_files: BTreeSet<_> = files.iter().filter_map(|f| watched_path(f)).collect();
+        let mut dirs: BTreeSet<_> = watched_files
+            .iter()
+            .filter_map(|f| f.parent().map(|d| d.to_path_buf()))
+            .collect();
+        for dir in watched_dirs.difference(&dirs) {
+            if let Err(e) = watcher.unwatch(dir) {
+                eprintln!("Error: failed to unwatch {}: {e}", dir.display());
+            }
+        }
+        let new_dirs: Vec<_> = dirs.difference(&watched_dirs).cloned().collect();
+        for dir in new_dirs {
+            if let Err(e) = watcher.watch(&dir, RecursiveMode::NonRecursive) {
+                // Tried again after the next change.
+                eprintln!("Error: failed to watch {}: {e}", dir.display());
+                dirs.remove(&dir);
+            }
+        }
+        watched_dirs = dirs;
+
+        println!("Watching {} file(s) for changes...", watched_files.len());
+        wait_for_change(&receiver, &watched_files)?;
+        println!();
+    }
+}
+
+/// Loads the descriptor and writes the code.
+/// `files` is updated with the descriptor files to watch.
+fn regenerate(
+    input: &InputArguments,
+    output: &OutputArguments,
+    files: &mut BTreeSet<PathBuf>,
+) -> Result<()> {
+    files.insert(input.descriptor.clone());
+    let descriptor = match read_descriptor(input) {
+        Ok(d) => d,
+        Err(e) => {
+            // Keeps watching the previous files until the descriptor is loaded again.
+            files.insert(e.path().to_path_buf());
+            report_diagnostics([&e.to_diagnostic()])?;
+            bail!("Failed to load the descriptor");
+        }
+    };
+
+    *files = descriptor
+        .sources
+        .files()
+        .map(|p| p.to_path_buf())
+        .collect();
+    files.insert(input.descriptor.clone());
+    let descriptor = check_descriptor(descriptor)?;
+    write_files(descriptor, output)
+}
+
+/// Absolute path of the file as notified by the watcher.
+/// The file itself need not exist, as it may be being replaced.
+fn watched_path(path: &Path) -> Option<PathBuf> {
+    let dir = match path.parent() {
+        Some(p) if p.as_os_str().is_empty() => Path::new("."),
+        Some(p) => p,
+        None => return None,
+    };
+    Some(dir.canonicalize().ok()?.join(path.file_name()?))
+}
+
+/// Waits until any of the files changes. Errors of the watcher are reported and ignored.
+fn wait_for_change(
+    receiver: &Receiver<notify::Result<Event>>,
+    files: &BTreeSet<PathBuf>,
+) -> Result<()> {
+    loop {
+        let event = match receiver.recv()? {
+            Ok(event) => event,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                continue;
+            }
+        };
+        if event.kind.is_access() || !event.paths.iter().any(|p| files.contains(p)) {
+            continue;
+        }
+
+        while receiver.recv_timeout(DEBOUNCE_DURATION).is_ok() {}
+        return Ok(());
+    }
+}
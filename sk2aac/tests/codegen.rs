@@ -0,0 +1,18 @@
+use sk2aac::{write_descriptor_code, Descriptor, ValidatedDescriptor};
+
+use std::path::PathBuf;
+
+/// Generated code for `example.toml` must stay the same unless it is intended.
+/// Replace `tests/golden` with the new output after checking the changes.
+#[test]
+fn example_code_is_unchanged() {
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    let descriptor = Descriptor::load(manifest_dir.join("../example.toml")).unwrap();
+    let descriptor = ValidatedDescriptor::new(descriptor).unwrap();
+
+    let mut code = vec![];
+    let class_name = write_descriptor_code(&mut code, descriptor).unwrap();
+    let expected = include_str!("golden/SK2AACGenerator_AvatarName.cs");
+    assert_eq!(class_name, "SK2AACGenerator_AvatarName");
+    assert_eq!(String::from_utf8(code).unwrap(), expected);
+}
@@ -0,0 +1,355 @@
+// This file is generated by sk2aac
+using UnityEngine;
+#if UNITY_EDITOR
+using UnityEditor;
+using UnityEditor.Animations;
+using VRC.SDK3.Avatars.Components;
+using VRC.SDK3.Avatars.ScriptableObjects;
+using static AnimatorAsCode.V0.AacFlState;
+using AnimatorAsCodeFramework.Examples;
+#endif
+
+#if UNITY_EDITOR
+[CustomEditor(typeof(SK2AACGenerator_AvatarName))]
+public class SK2AACGenerator_AvatarName_Editor : Editor
+{
+    public override void OnInspectorGUI()
+    {
+        base.OnInspectorGUI();
+        var executor = target as SK2AACGenerator_AvatarName;
+        if (GUILayout.Button("Generate"))
+        {
+            executor.GenerateAnimator();
+        }
+    }
+}
+#endif
+
+public class SK2AACGenerator_AvatarName : MonoBehaviour
+{
+    public AnimatorController TargetContainer;
+    public string AssetKey = "SK2AAC";
+
+    public void GenerateAnimator()
+    {
+        var avatarDescriptor = GetComponent<VRCAvatarDescriptor>();
+        var aac = AacExample.AnimatorAsCode("SK2AAC", avatarDescriptor, TargetContainer, AssetKey, AacExample.Options().WriteDefaultsOff());
+        // var fxDefault = aac.CreateMainFxLayer();
+
+        // Prevents Animation
+        {
+            var layer = aac.CreateSupportingFxLayer("Eyes_TrackingControl");
+            var paramEyelids = layer.IntParameter("Eyelids");
+            var paramGestureLeft = layer.IntParameter("GestureLeft");
+            var paramGestureRight = layer.IntParameter("GestureRight");
+
+            var tracking = layer.NewState("Tracking").WithAnimation(aac.NewClip());
+            tracking.TrackingTracks(TrackingElement.Eyes);
+            var animated = layer.NewState("Animated").WithAnimation(aac.NewClip());
+            animated.TrackingAnimates(TrackingElement.Eyes);
+
+            tracking.TransitionsTo(animated).When(paramEyelids.IsNotEqualTo(0)).Or().When(paramGestureLeft.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(4));
+            animated.TransitionsTo(tracking).When(paramEyelids.IsEqualTo(0)).And(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+        }
+
+        // Prevents Animation
+        {
+            var layer = aac.CreateSupportingFxLayer("Mouth_TrackingControl");
+            var paramMouthOpen = layer.FloatParameter("MouthOpen");
+
+            var tracking = layer.NewState("Tracking").WithAnimation(aac.NewClip());
+            tracking.TrackingTracks(TrackingElement.Mouth);
+            var animated = layer.NewState("Animated").WithAnimation(aac.NewClip());
+            animated.TrackingAnimates(TrackingElement.Mouth);
+
+            tracking.TransitionsTo(animated).When(paramMouthOpen.IsGreaterThan(0.01f)).Or().When(paramMouthOpen.IsLessThan(-0.01f));
+            animated.TransitionsTo(tracking).When(paramMouthOpen.IsLessThan(0.01f)).And(paramMouthOpen.IsGreaterThan(-0.01f));
+        }
+
+        // Shape Key Switch "Cheek"
+        {
+            var layer = aac.CreateSupportingFxLayer("Cheek");
+            var renderer = (SkinnedMeshRenderer) gameObject.transform.Find("Face").GetComponent<SkinnedMeshRenderer>();
+            var parameter = layer.BoolParameter("Cheek");
+
+            var disabled = layer.NewState("false: Disabled").WithAnimation(aac.NewClip().BlendShape(renderer, "face_cheek", 0.0f));
+            var enabled = layer.NewState("true: Enabled").WithAnimation(aac.NewClip().BlendShape(renderer, "face_cheek", 100.0f));
+
+            disabled.TransitionsTo(enabled).When(parameter.IsTrue());
+            enabled.TransitionsTo(disabled).When(parameter.IsFalse());
+        }
+
+        // Shape Key Switch "Eyelids"
+        {
+            var layer = aac.CreateSupportingFxLayer("Eyelids");
+            var renderer = (SkinnedMeshRenderer) gameObject.transform.Find("Face").GetComponent<SkinnedMeshRenderer>();
+            var parameter = layer.IntParameter("Eyelids");
+            var paramGestureLeft = layer.IntParameter("GestureLeft");
+            var paramGestureRight = layer.IntParameter("GestureRight");
+            var paramGestureLeftWeight = layer.FloatParameter("GestureLeftWeight");
+
+            var disabled = layer.NewState("0: Disabled").WithAnimation(
+                aac.NewClip()
+                    .BlendShape(renderer, "eyelids_close", 0.0f)
+                    .BlendShape(renderer, "eyelids_smile", 0.0f)
+                    .SwappingMaterial(renderer, 1, renderer.sharedMaterials[1])
+            );
+
+            var enabled1 = layer.NewState("1: eyelids_smile").RightOf(disabled).WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_smile", 100.0f));
+            disabled.TransitionsTo(enabled1).When(parameter.IsEqualTo(1)).And(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+            enabled1.Exits().When(parameter.IsNotEqualTo(1)).Or().When(paramGestureLeft.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(4));
+
+            var enabled2 = layer.NewState("2: eyelids_close").WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_close", 100.0f));
+            disabled.TransitionsTo(enabled2).When(parameter.IsEqualTo(2)).And(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+            enabled2.Exits().When(parameter.IsNotEqualTo(2)).Or().When(paramGestureLeft.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(4));
+
+            var enabled3 = layer.NewState("3: eyelids_close_1").WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_close", 50.0f));
+            disabled.TransitionsTo(enabled3).When(parameter.IsEqualTo(3)).And(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+            enabled3.Exits().When(parameter.IsNotEqualTo(3)).Or().When(paramGestureLeft.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(4));
+
+            var enabled4 = layer.NewState("4: eyelids_close_2").WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_close", 100.0f));
+            disabled.TransitionsTo(enabled4).When(parameter.IsEqualTo(4)).And(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+            enabled4.Exits().When(parameter.IsNotEqualTo(4)).Or().When(paramGestureLeft.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(4));
+
+            var enabled5 = layer.NewState("5: eyelids_tear").WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_close", 100.0f).SwappingMaterial(renderer, 1, AssetDatabase.LoadAssetAtPath<Material>("Assets/Avatar/Materials/EyeTear.mat")));
+            disabled.TransitionsTo(enabled5).When(parameter.IsEqualTo(5)).And(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+            enabled5.Exits().When(parameter.IsNotEqualTo(5)).Or().When(paramGestureLeft.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(1)).Or().When(paramGestureRight.IsEqualTo(4));
+
+            var gesture1 = layer.NewState("1: eyelids_smile (Gesture)").RightOf(enabled1).WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_smile", 100.0f));
+            disabled.TransitionsTo(gesture1).When(paramGestureRight.IsEqualTo(4)).And(paramGestureLeft.IsNotEqualTo(1));
+            gesture1.Exits().When(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+
+            var tree2 = aac.NewBlendTreeAsRaw();
+            tree2.blendType = BlendTreeType.Simple1D;
+            tree2.blendParameter = paramGestureLeftWeight.Name;
+            tree2.useAutomaticThresholds = false;
+            tree2.AddChild(aac.NewClip().BlendShape(renderer, "eyelids_close", 0.0f).Clip, 0.0f);
+            tree2.AddChild(aac.NewClip().BlendShape(renderer, "eyelids_close", 100.0f).Clip, 1.0f);
+            var gesture2 = layer.NewState("2: eyelids_close (Gesture)").WithAnimation(tree2);
+            gesture2.TransitionsTo(gesture1).When(paramGestureRight.IsEqualTo(4)).And(paramGestureLeft.IsNotEqualTo(1));
+            disabled.TransitionsTo(gesture2).When(paramGestureLeft.IsEqualTo(1));
+            gesture1.TransitionsTo(gesture2).When(paramGestureLeft.IsEqualTo(1));
+            gesture2.Exits().When(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+
+            var gesture4 = layer.NewState("4: eyelids_close_2 (Gesture)").WithAnimation(aac.NewClip().BlendShape(renderer, "eyelids_close", 100.0f));
+            gesture4.TransitionsTo(gesture1).When(paramGestureRight.IsEqualTo(4)).And(paramGestureLeft.IsNotEqualTo(1));
+            gesture4.TransitionsTo(gesture2).When(paramGestureLeft.IsEqualTo(1));
+            disabled.TransitionsTo(gesture4).When(paramGestureRight.IsEqualTo(1)).And(paramGestureLeft.IsNotEqualTo(1));
+            gesture1.TransitionsTo(gesture4).When(paramGestureRight.IsEqualTo(1)).And(paramGestureLeft.IsNotEqualTo(1));
+            gesture2.TransitionsTo(gesture4).When(paramGestureRight.IsEqualTo(1)).And(paramGestureLeft.IsNotEqualTo(1));
+            gesture4.Exits().When(paramGestureLeft.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(1)).And(paramGestureRight.IsNotEqualTo(4));
+        }
+
+        // Shape Key Slider "MouthOpen"
+        {
+            var layer = aac.CreateSupportingFxLayer("MouthOpen");
+            var renderer = (SkinnedMeshRenderer) gameObject.transform.Find("Face").GetComponent<SkinnedMeshRenderer>();
+            var renderer1 = (SkinnedMeshRenderer) gameObject.transform.Find("Teeth").GetComponent<SkinnedMeshRenderer>();
+            var parameter = layer.FloatParameter("MouthOpen");
+
+            var tree = aac.NewBlendTreeAsRaw();
+            tree.blendType = BlendTreeType.Simple1D;
+            tree.blendParameter = parameter.Name;
+            tree.useAutomaticThresholds = false;
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "mouth_open", 0.0f).BlendShape(renderer, "mouth_smile", 0.0f).BlendShape(renderer1, "teeth_open", 0.0f).Clip, 0.0f);
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "mouth_open", 100.0f).BlendShape(renderer, "mouth_smile", 30.0f).BlendShape(renderer1, "teeth_open", 100.0f).Clip, 1.0f);
+
+            var blend = layer.NewState("Blend").WithAnimation(tree);
+        }
+
+        // Shape Key Puppet "EyeLook"
+        {
+            var layer = aac.CreateSupportingFxLayer("EyeLook");
+            var renderer = (SkinnedMeshRenderer) gameObject.transform.Find("Face").GetComponent<SkinnedMeshRenderer>();
+            var parameterX = layer.FloatParameter("EyeLookX");
+            var parameterY = layer.FloatParameter("EyeLookY");
+
+            var tree = aac.NewBlendTreeAsRaw();
+            tree.blendType = BlendTreeType.FreeformDirectional2D;
+            tree.blendParameter = parameterX.Name;
+            tree.blendParameterY = parameterY.Name;
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "eye_look_down", 0.0f).BlendShape(renderer, "eye_look_left", 0.0f).BlendShape(renderer, "eye_look_right", 0.0f).BlendShape(renderer, "eye_look_up", 0.0f).Clip, new Vector2(0f, 0f));
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "eye_look_down", 0.0f).BlendShape(renderer, "eye_look_left", 100.0f).BlendShape(renderer, "eye_look_right", 0.0f).BlendShape(renderer, "eye_look_up", 0.0f).Clip, new Vector2(-1f, 0f));
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "eye_look_down", 0.0f).BlendShape(renderer, "eye_look_left", 0.0f).BlendShape(renderer, "eye_look_right", 100.0f).BlendShape(renderer, "eye_look_up", 0.0f).Clip, new Vector2(1f, 0f));
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "eye_look_down", 0.0f).BlendShape(renderer, "eye_look_left", 0.0f).BlendShape(renderer, "eye_look_right", 0.0f).BlendShape(renderer, "eye_look_up", 100.0f).Clip, new Vector2(0f, 1f));
+            tree.AddChild(aac.NewClip().BlendShape(renderer, "eye_look_down", 80.0f).BlendShape(renderer, "eye_look_left", 0.0f).BlendShape(renderer, "eye_look_right", 0.0f).BlendShape(renderer, "eye_look_up", 0.0f).Clip, new Vector2(0f, -1f));
+
+            var blend = layer.NewState("Blend").WithAnimation(tree);
+        }
+
+        // Object Toggle "Glasses"
+        {
+            var layer = aac.CreateSupportingFxLayer("Glasses");
+            var objects = new GameObject[]
+            {
+                gameObject.transform.Find("Accessories/Glasses").gameObject,
+                gameObject.transform.Find("Accessories/GlassesChain").gameObject,
+            };
+            var parameter = layer.BoolParameter("Glasses");
+
+            var disabled = layer.NewState("false: Disabled").WithAnimation(aac.NewClip().Toggling(objects, false));
+            var enabled = layer.NewState("true: Enabled").WithAnimation(aac.NewClip().Toggling(objects, true));
+
+            disabled.TransitionsTo(enabled).When(parameter.IsTrue());
+            enabled.TransitionsTo(disabled).When(parameter.IsFalse());
+        }
+
+        // Driver "FacialExpression"
+        {
+            var layer = aac.CreateSupportingFxLayer("FacialExpression");
+            var parameter = layer.IntParameter("FacialExpression");
+            var waiting = layer.NewState("0: Waiting").WithAnimation(aac.NewClip());
+
+            var option1 = layer.NewState("1: Smile").RightOf(waiting).WithAnimation(aac.NewClip());
+            option1.DrivingLocally().Drives(layer.IntParameter("Eyelids"), 1).Drives(layer.BoolParameter("Cheek"), true).Drives(layer.BoolParameter("Glasses"), false);
+            waiting.TransitionsTo(option1).When(parameter.IsEqualTo(1));
+            option1.Exits().When(parameter.IsNotEqualTo(1));
+        }
+
+        UpdateExpressionParameters(avatarDescriptor);
+        UpdateExpressionsMenu(avatarDescriptor);
+    }
+
+    public void UpdateExpressionParameters(VRCAvatarDescriptor avatarDescriptor)
+    {
+        var expressionParameters = avatarDescriptor.expressionParameters;
+        if (expressionParameters == null)
+        {
+            var directory = System.IO.Path.GetDirectoryName(AssetDatabase.GetAssetPath(TargetContainer));
+            expressionParameters = ScriptableObject.CreateInstance<VRCExpressionParameters>();
+            expressionParameters.parameters = new VRCExpressionParameters.Parameter[0];
+            AssetDatabase.CreateAsset(expressionParameters, $"{directory}/{AssetKey}_ExpressionParameters.asset");
+            avatarDescriptor.customExpressions = true;
+            avatarDescriptor.expressionParameters = expressionParameters;
+        }
+
+        SetExpressionParameter(expressionParameters, "Cheek", VRCExpressionParameters.ValueType.Bool, 0f, true, true);
+        SetExpressionParameter(expressionParameters, "Eyelids", VRCExpressionParameters.ValueType.Int, 0f, true, true);
+        SetExpressionParameter(expressionParameters, "MouthOpen", VRCExpressionParameters.ValueType.Float, 0f, true, true);
+        SetExpressionParameter(expressionParameters, "EyeLookX", VRCExpressionParameters.ValueType.Float, 0f, true, true);
+        SetExpressionParameter(expressionParameters, "EyeLookY", VRCExpressionParameters.ValueType.Float, 0f, true, true);
+        SetExpressionParameter(expressionParameters, "Glasses", VRCExpressionParameters.ValueType.Bool, 0f, true, true);
+        SetExpressionParameter(expressionParameters, "FacialExpression", VRCExpressionParameters.ValueType.Int, 0f, true, true);
+
+        EditorUtility.SetDirty(expressionParameters);
+        AssetDatabase.SaveAssets();
+    }
+
+    private static void SetExpressionParameter(VRCExpressionParameters expressionParameters, string name, VRCExpressionParameters.ValueType valueType, float defaultValue, bool saved, bool synced)
+    {
+        var existing = System.Array.Find(expressionParameters.parameters, p => p.name == name);
+        if (existing == null)
+        {
+            existing = new VRCExpressionParameters.Parameter { name = name, valueType = valueType };
+            ArrayUtility.Add(ref expressionParameters.parameters, existing);
+        }
+        else if (existing.valueType != valueType)
+        {
+            throw new System.InvalidOperationException($"Expression Parameter \"{name}\" already exists as {existing.valueType}, but sk2aac requires {valueType}");
+        }
+
+        existing.defaultValue = defaultValue;
+        existing.saved = saved;
+        existing.networkSynced = synced;
+    }
+
+    public void UpdateExpressionsMenu(VRCAvatarDescriptor avatarDescriptor)
+    {
+        var directory = System.IO.Path.GetDirectoryName(AssetDatabase.GetAssetPath(TargetContainer));
+        var expressionsMenu = avatarDescriptor.expressionsMenu;
+        if (expressionsMenu == null)
+        {
+            expressionsMenu = ScriptableObject.CreateInstance<VRCExpressionsMenu>();
+            AssetDatabase.CreateAsset(expressionsMenu, $"{directory}/{AssetKey}_Menu.asset");
+            avatarDescriptor.customExpressions = true;
+            avatarDescriptor.expressionsMenu = expressionsMenu;
+        }
+
+        SetMenuControl(expressionsMenu, NewToggle("Cheek", null, "Cheek", 1));
+        var menu1 = GetOrCreateSubMenu(expressionsMenu, "Eyelids", null, $"{directory}/{AssetKey}_Menu_Eyelids.asset");
+        SetMenuControl(menu1, NewToggle("eyelids_smile", null, "Eyelids", 1));
+        SetMenuControl(menu1, NewToggle("eyelids_close", AssetDatabase.LoadAssetAtPath<Texture2D>("Assets/Icons/eyelids_close.png"), "Eyelids", 2));
+        SetMenuControl(menu1, NewToggle("eyelids_close_1", null, "Eyelids", 3));
+        SetMenuControl(menu1, NewToggle("eyelids_close_2", null, "Eyelids", 4));
+        SetMenuControl(menu1, NewToggle("eyelids_tear", null, "Eyelids", 5));
+        SetMenuControl(expressionsMenu, NewRadialPuppet("MouthOpen", null, "MouthOpen"));
+        SetMenuControl(expressionsMenu, NewTwoAxisPuppet("EyeLook", null, "EyeLookX", "EyeLookY"));
+        SetMenuControl(expressionsMenu, NewToggle("Glasses", null, "Glasses", 1));
+        var menu2 = GetOrCreateSubMenu(expressionsMenu, "FacialExpression", null, $"{directory}/{AssetKey}_Menu_FacialExpression.asset");
+        SetMenuControl(menu2, NewToggle("Smile", null, "FacialExpression", 1));
+
+        AssetDatabase.SaveAssets();
+    }
+
+    private static VRCExpressionsMenu GetOrCreateSubMenu(VRCExpressionsMenu parent, string name, Texture2D icon, string assetPath)
+    {
+        var existing = parent.controls.Find(c => c.name == name && c.type == VRCExpressionsMenu.Control.ControlType.SubMenu);
+        if (existing != null && existing.subMenu != null)
+        {
+            if (icon != null) existing.icon = icon;
+            EditorUtility.SetDirty(parent);
+            return existing.subMenu;
+        }
+
+        var subMenu = ScriptableObject.CreateInstance<VRCExpressionsMenu>();
+        AssetDatabase.CreateAsset(subMenu, assetPath);
+        SetMenuControl(parent, new VRCExpressionsMenu.Control { name = name, icon = icon, type = VRCExpressionsMenu.Control.ControlType.SubMenu, subMenu = subMenu });
+        return subMenu;
+    }
+
+    private static void SetMenuControl(VRCExpressionsMenu menu, VRCExpressionsMenu.Control control)
+    {
+        var index = menu.controls.FindIndex(c => c.name == control.name);
+        if (index >= 0)
+        {
+            menu.controls[index] = control;
+        }
+        else if (menu.controls.Count >= VRCExpressionsMenu.MAX_CONTROLS)
+        {
+            throw new System.InvalidOperationException($"Expressions Menu \"{menu.name}\" has no room for \"{control.name}\"");
+        }
+        else
+        {
+            menu.controls.Add(control);
+        }
+        EditorUtility.SetDirty(menu);
+    }
+
+    private static VRCExpressionsMenu.Control NewToggle(string name, Texture2D icon, string parameter, int value)
+    {
+        return new VRCExpressionsMenu.Control
+        {
+            name = name,
+            icon = icon,
+            type = VRCExpressionsMenu.Control.ControlType.Toggle,
+            parameter = new VRCExpressionsMenu.Control.Parameter { name = parameter },
+            value = value,
+        };
+    }
+
+    private static VRCExpressionsMenu.Control NewRadialPuppet(string name, Texture2D icon, string parameter)
+    {
+        return new VRCExpressionsMenu.Control
+        {
+            name = name,
+            icon = icon,
+            type = VRCExpressionsMenu.Control.ControlType.RadialPuppet,
+            subParameters = new[] { new VRCExpressionsMenu.Control.Parameter { name = parameter } },
+        };
+    }
+
+    private static VRCExpressionsMenu.Control NewTwoAxisPuppet(string name, Texture2D icon, string parameterX, string parameterY)
+    {
+        return new VRCExpressionsMenu.Control
+        {
+            name = name,
+            icon = icon,
+            type = VRCExpressionsMenu.Control.ControlType.TwoAxisPuppet,
+            subParameters = new[]
+            {
+                new VRCExpressionsMenu.Control.Parameter { name = parameterX },
+                new VRCExpressionsMenu.Control.Parameter { name = parameterY },
+            },
+        };
+    }
+}